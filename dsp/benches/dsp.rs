@@ -0,0 +1,214 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use montage_dsp::agc::Agc;
+use montage_dsp::character::{CharacterStage, VoiceCharacter};
+use montage_dsp::deesser::DeEsser;
+use montage_dsp::echo::Echo;
+use montage_dsp::harmonizer::Harmonizer;
+use montage_dsp::oversample::{Oversampler, OversamplingSettings};
+use montage_dsp::primitives::saturate;
+use montage_dsp::resample::Resampler;
+use montage_dsp::reverb::Reverb;
+use montage_dsp::vocoder::Vocoder;
+use montage_dsp::{DspProcessor, EffectSettings};
+use std::f32::consts::PI;
+use std::hint::black_box;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 96000];
+const BUFFER_SIZES: [usize; 4] = [64, 256, 512, 1024];
+
+/// a couple of partials and some noise, enough to keep the detectors and envelopes busy
+fn voice(sample_rate: u32, len: usize) -> Vec<f32> {
+    let mut seed = 1u32;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let noise = (seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+            (2.0 * PI * 180.0 * t).sin() * 0.4 + (2.0 * PI * 2700.0 * t).sin() * 0.1 + noise * 0.05
+        })
+        .collect()
+}
+
+/// every optional stage switched on, the heaviest chain the app or the plugin can build
+fn all_effects() -> EffectSettings {
+    let mut effects = EffectSettings::default();
+    effects.deesser.enabled = true;
+    effects.eq.enabled = true;
+    effects.character = VoiceCharacter::Radio;
+    effects.vocoder.enabled = true;
+    effects.harmonizer.enabled = true;
+    for voice in &mut effects.harmonizer.voices {
+        voice.enabled = true;
+    }
+    effects.echo.enabled = true;
+    effects.reverb.enabled = true;
+    effects.agc.enabled = true;
+    effects
+}
+
+/// time `process` over one buffer at every rate and buffer size, `new` builds the stage for a rate
+fn bench_stage<S>(
+    c: &mut Criterion,
+    name: &str,
+    new: impl Fn(u32) -> S,
+    mut process: impl FnMut(&mut S, &[f32], &mut [f32]),
+) {
+    let mut group = c.benchmark_group(name);
+    for sample_rate in SAMPLE_RATES {
+        for buffer_size in BUFFER_SIZES {
+            let input = voice(sample_rate, buffer_size);
+            let mut output = vec![0.0; buffer_size];
+            let mut stage = new(sample_rate);
+            group.throughput(Throughput::Elements(buffer_size as u64));
+            group.bench_function(BenchmarkId::new(format!("{}Hz", sample_rate), buffer_size), |b| {
+                b.iter(|| process(&mut stage, black_box(&input), black_box(&mut output)))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// adapts a stage that works one mono sample at a time
+fn mono(mut process: impl FnMut(f32) -> f32, input: &[f32], output: &mut [f32]) {
+    for (x, y) in input.iter().zip(output.iter_mut()) {
+        *y = process(*x);
+    }
+}
+
+/// adapts a stage with a stereo output, the channels are summed so neither is optimised away
+fn stereo(mut process: impl FnMut(f32) -> (f32, f32), input: &[f32], output: &mut [f32]) {
+    for (x, y) in input.iter().zip(output.iter_mut()) {
+        let (left, right) = process(*x);
+        *y = left + right;
+    }
+}
+
+fn stages(c: &mut Criterion) {
+    bench_stage(
+        c,
+        "pitch_shifter",
+        |rate| DspProcessor::new(Arc::new(Mutex::new(1.3)), rate),
+        |dsp, input, output| dsp.process(input, output),
+    );
+    bench_stage(
+        c,
+        "oversampled_saturation",
+        |_| Oversampler::new(OversamplingSettings::default()),
+        |oversampler, input, output| mono(|x| oversampler.process(x, |x| saturate(x, 4.0)), input, output),
+    );
+    bench_stage(
+        c,
+        "deesser",
+        |rate| {
+            let mut deesser = DeEsser::new(rate as f32);
+            deesser.set_settings(&all_effects().deesser);
+            deesser
+        },
+        |deesser, input, output| mono(|x| deesser.process(x), input, output),
+    );
+    bench_stage(
+        c,
+        "character",
+        |rate| {
+            let mut character = CharacterStage::new(rate as f32);
+            character.set_character(VoiceCharacter::Radio);
+            character
+        },
+        |character, input, output| mono(|x| character.process(x), input, output),
+    );
+    bench_stage(
+        c,
+        "vocoder",
+        |rate| {
+            let mut vocoder = Vocoder::new(rate as f32);
+            vocoder.set_settings(&all_effects().vocoder);
+            vocoder
+        },
+        |vocoder, input, output| mono(|x| vocoder.process(x), input, output),
+    );
+    bench_stage(
+        c,
+        "harmonizer",
+        |rate| {
+            let mut harmonizer = Harmonizer::new(rate as f32);
+            harmonizer.set_settings(&all_effects().harmonizer);
+            harmonizer
+        },
+        |harmonizer, input, output| stereo(|x| harmonizer.process(x), input, output),
+    );
+    bench_stage(
+        c,
+        "echo",
+        |rate| {
+            let mut echo = Echo::new(rate as f32);
+            echo.set_settings(&all_effects().echo);
+            echo
+        },
+        |echo, input, output| stereo(|x| echo.process(x, x), input, output),
+    );
+    bench_stage(
+        c,
+        "reverb",
+        |rate| {
+            let mut reverb = Reverb::new(rate as f32);
+            reverb.set_settings(&all_effects().reverb);
+            reverb
+        },
+        |reverb, input, output| stereo(|x| reverb.process(x), input, output),
+    );
+    bench_stage(
+        c,
+        "agc",
+        |rate| {
+            let mut agc = Agc::new(rate as f32);
+            agc.set_settings(&all_effects().agc);
+            agc
+        },
+        |agc, input, output| stereo(|x| agc.process(x, x), input, output),
+    );
+    bench_stage(
+        c,
+        "resampler_48k_to_44k1",
+        |_| (Resampler::new(48000, 44100), Vec::with_capacity(2048)),
+        |(resampler, scratch), input, output| {
+            scratch.clear();
+            resampler.process(input, scratch);
+            let len = scratch.len().min(output.len());
+            output[..len].copy_from_slice(&scratch[..len]);
+        },
+    );
+}
+
+fn chain(c: &mut Criterion) {
+    bench_stage(
+        c,
+        "chain_bypassed",
+        |rate| DspProcessor::new(Arc::new(Mutex::new(1.3)), rate),
+        |dsp, input, output| {
+            let mut right = [0.0; 1024];
+            dsp.process_stereo(input, output, &mut right[..input.len()]);
+        },
+    );
+    bench_stage(
+        c,
+        "chain_all_effects",
+        |rate| {
+            let mut dsp = DspProcessor::new(Arc::new(Mutex::new(1.3)), rate);
+            dsp.set_effects(&all_effects());
+            dsp
+        },
+        |dsp, input, output| {
+            let mut right = [0.0; 1024];
+            dsp.process_stereo(input, output, &mut right[..input.len()]);
+        },
+    );
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().warm_up_time(Duration::from_secs(1)).measurement_time(Duration::from_secs(2));
+    targets = stages, chain
+}
+criterion_main!(benches);
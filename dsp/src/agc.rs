@@ -0,0 +1,261 @@
+use super::biquad::Biquad;
+use super::smoothing_coefficient;
+use std::collections::VecDeque;
+
+/// loudness is integrated over 100ms blocks, 30 of them make the 3s short-term window
+const BLOCK_MS: f32 = 100.0;
+const SHORT_TERM_BLOCKS: usize = 30;
+/// absolute gate of BS.1770, anything below can't be measured
+pub const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+/// how far the AGC may turn a loud source down
+const MAX_CUT_DB: f32 = 24.0;
+/// glide between the per-block gain targets so the gain never steps
+const GAIN_SMOOTHING_MS: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AgcSettings {
+    pub enabled: bool,
+    pub target_lufs: f32,
+    /// limit on how much a quiet source is turned up
+    pub max_gain_db: f32,
+    /// below this short-term loudness the input counts as silence and the gain is held
+    pub gate_lufs: f32,
+    /// time constant of the gain adjustments
+    pub response_s: f32,
+}
+
+impl Default for AgcSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_lufs: -18.0,
+            max_gain_db: 12.0,
+            gate_lufs: -50.0,
+            response_s: 3.0,
+        }
+    }
+}
+
+/// the two stage K-weighting filter of ITU-R BS.1770 for any sample rate
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    use std::f64::consts::PI;
+
+    // stage 1: high shelf modelling the acoustic effect of the head
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10.0_f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let shelf = Biquad::from_coefficients(
+        (vh + vb * k / q + k * k) as f32,
+        (2.0 * (k * k - vh)) as f32,
+        (vh - vb * k / q + k * k) as f32,
+        (1.0 + k / q + k * k) as f32,
+        (2.0 * (k * k - 1.0)) as f32,
+        (1.0 - k / q + k * k) as f32,
+    );
+
+    // stage 2: the RLB high-pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / sample_rate).tan();
+    let highpass = Biquad::from_coefficients(
+        1.0,
+        -2.0,
+        1.0,
+        (1.0 + k / q + k * k) as f32,
+        (2.0 * (k * k - 1.0)) as f32,
+        (1.0 - k / q + k * k) as f32,
+    );
+
+    [shelf, highpass]
+}
+
+/// measures short-term loudness and slowly steers the gain towards a LUFS target
+pub struct Agc {
+    settings: AgcSettings,
+    filters: [[Biquad; 2]; 2],
+    block_len: usize,
+    block_position: usize,
+    block_energy: f64,
+    blocks: VecDeque<f64>,
+    /// latest short-term loudness of the input, None while below the absolute gate
+    loudness: Option<f32>,
+    target_gain_db: f32,
+    gain_db: f32,
+    gain: f32,
+    gain_smoothing: f32,
+}
+
+impl Agc {
+    pub fn new(sample_rate: f32) -> Self {
+        let filters = k_weighting(sample_rate as f64);
+        Self {
+            settings: AgcSettings::default(),
+            filters: [filters, filters],
+            block_len: ((BLOCK_MS / 1000.0) * sample_rate) as usize,
+            block_position: 0,
+            block_energy: 0.0,
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+            loudness: None,
+            target_gain_db: 0.0,
+            gain_db: 0.0,
+            gain: 1.0,
+            gain_smoothing: smoothing_coefficient(GAIN_SMOOTHING_MS, sample_rate),
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: &AgcSettings) {
+        // the measurement carried on while disabled, but the gain starts again from unity
+        if settings.enabled && !self.settings.enabled {
+            self.gain_db = 0.0;
+            self.gain = 1.0;
+        }
+        self.settings = *settings;
+        // a limit below the largest cut would leave the gain nowhere to go
+        self.settings.max_gain_db = settings.max_gain_db.max(-MAX_CUT_DB);
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().flatten().for_each(Biquad::reset);
+        self.block_position = 0;
+        self.block_energy = 0.0;
+        self.blocks.clear();
+        self.loudness = None;
+        self.target_gain_db = 0.0;
+        self.gain_db = 0.0;
+        self.gain = 1.0;
+    }
+
+    /// short-term loudness of the signal going into the AGC, in LUFS
+    pub fn loudness(&self) -> Option<f32> {
+        self.loudness
+    }
+
+    /// gain currently applied by the AGC, in dB
+    pub fn gain_db(&self) -> f32 {
+        if self.settings.enabled { self.gain_db } else { 0.0 }
+    }
+
+    /// measure and, when enabled, level a left/right pair
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let weighted_left = self.filters[0].iter_mut().fold(left, |x, f| f.process(x));
+        let weighted_right = self.filters[1].iter_mut().fold(right, |x, f| f.process(x));
+        self.block_energy += (weighted_left * weighted_left + weighted_right * weighted_right) as f64;
+
+        self.block_position += 1;
+        if self.block_position >= self.block_len {
+            self.finish_block();
+        }
+
+        if !self.settings.enabled {
+            return (left, right);
+        }
+
+        let target = 10.0_f32.powf(self.gain_db / 20.0);
+        self.gain += (target - self.gain) * self.gain_smoothing;
+        (left * self.gain, right * self.gain)
+    }
+
+    fn finish_block(&mut self) {
+        if self.blocks.len() == SHORT_TERM_BLOCKS {
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back(self.block_energy / self.block_len as f64);
+        self.block_position = 0;
+        self.block_energy = 0.0;
+
+        let mean_square = self.blocks.iter().sum::<f64>() / self.blocks.len() as f64;
+        let loudness = (-0.691 + 10.0 * mean_square.max(1e-12).log10()) as f32;
+        self.loudness = (loudness > ABSOLUTE_GATE_LUFS).then_some(loudness);
+
+        // silence holds the gain, otherwise aim for the target within the limits
+        if let Some(loudness) = self.loudness.filter(|&l| l > self.settings.gate_lufs) {
+            self.target_gain_db = (self.settings.target_lufs - loudness).clamp(-MAX_CUT_DB, self.settings.max_gain_db);
+        }
+        self.target_gain_db = self.target_gain_db.min(self.settings.max_gain_db);
+
+        // one-pole glide of the gain towards the target, stepped once per block
+        let block_seconds = BLOCK_MS / 1000.0;
+        let coefficient = 1.0 - (-block_seconds / self.settings.response_s.max(block_seconds)).exp();
+        self.gain_db += (self.target_gain_db - self.gain_db) * coefficient;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn full_scale_1khz_sine_reads_zero_lufs_on_both_channels() {
+        // BS.1770 calibration: a 0dBFS 1kHz sine measures -3.01 LUFS per channel
+        for sample_rate in [44100.0, 48000.0, 96000.0] {
+            let mut agc = Agc::new(sample_rate);
+            for i in 0..(sample_rate as usize * 4) {
+                let sample = (2.0 * PI * 1000.0 * i as f32 / sample_rate).sin();
+                agc.process(sample, sample);
+            }
+            let loudness = agc.loudness().unwrap();
+            assert!((loudness - 0.0).abs() < 0.1, "{} Hz: {:.2} LUFS", sample_rate, loudness);
+        }
+    }
+
+    #[test]
+    fn quiet_voice_is_raised_towards_the_target_but_silence_is_not() {
+        let sample_rate = 48000.0;
+        let mut agc = Agc::new(sample_rate);
+        agc.set_settings(&AgcSettings { enabled: true, response_s: 0.5, ..AgcSettings::default() });
+
+        // about -33 LUFS, the target is -18 and the limit +12dB
+        let tone = |i: usize| (2.0 * PI * 1000.0 * i as f32 / sample_rate).sin() * 0.02;
+        for i in 0..(sample_rate as usize * 10) {
+            agc.process(tone(i), tone(i));
+        }
+        assert!((agc.gain_db() - 12.0).abs() < 0.5, "{:.2} dB", agc.gain_db());
+
+        // the gain holds through silence instead of climbing further
+        for _ in 0..(sample_rate as usize * 5) {
+            agc.process(0.0, 0.0);
+        }
+        assert!((agc.gain_db() - 12.0).abs() < 0.5, "{:.2} dB", agc.gain_db());
+    }
+
+    #[test]
+    fn switching_back_on_glides_from_unity_gain() {
+        let sample_rate = 48000.0;
+        let enabled = AgcSettings { enabled: true, response_s: 0.5, ..AgcSettings::default() };
+        let mut agc = Agc::new(sample_rate);
+        let tone = |i: usize| (2.0 * PI * 1000.0 * i as f32 / sample_rate).sin() * 0.02;
+        agc.set_settings(&enabled);
+        for i in 0..(sample_rate as usize * 10) {
+            agc.process(tone(i), tone(i));
+        }
+
+        // a loud passage while disabled, then back on with the quiet source
+        agc.set_settings(&AgcSettings { enabled: false, ..enabled });
+        for i in 0..(sample_rate as usize * 5) {
+            agc.process(tone(i) * 50.0, tone(i) * 50.0);
+        }
+        agc.set_settings(&enabled);
+        assert_eq!(agc.gain_db(), 0.0);
+        let quarter = sample_rate as usize / 4000;
+        let (left, _) = agc.process(tone(quarter), tone(quarter));
+        assert!((left - tone(quarter)).abs() < 1e-6, "{} instead of {}", left, tone(quarter));
+    }
+
+    #[test]
+    fn max_gain_below_the_largest_cut_turns_down_as_far_as_it_can() {
+        let sample_rate = 48000.0;
+        let mut agc = Agc::new(sample_rate);
+        agc.set_settings(&AgcSettings { enabled: true, max_gain_db: -30.0, response_s: 0.5, ..AgcSettings::default() });
+        let tone = |i: usize| (2.0 * PI * 1000.0 * i as f32 / sample_rate).sin() * 0.02;
+        for i in 0..(sample_rate as usize * 10) {
+            agc.process(tone(i), tone(i));
+        }
+        assert!((agc.gain_db() + MAX_CUT_DB).abs() < 0.5, "{:.2} dB", agc.gain_db());
+    }
+}
@@ -0,0 +1,193 @@
+use std::f32::consts::PI;
+
+/// second order IIR section (RBJ audio EQ cookbook), transposed direct form II
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// raw coefficients, normalised by `a0`
+    pub fn from_coefficients(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// angular frequency terms shared by every cookbook filter
+    fn omega(freq: f32, q: f32, sample_rate: f32) -> (f32, f32) {
+        // keep the corner below nyquist so the coefficients stay stable
+        let freq = freq.clamp(1.0, sample_rate * 0.49);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * q.max(0.01)))
+    }
+
+    pub fn lowpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::omega(freq, q, sample_rate);
+        Self::from_coefficients(
+            (1.0 - cos) * 0.5,
+            1.0 - cos,
+            (1.0 - cos) * 0.5,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn highpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::omega(freq, q, sample_rate);
+        Self::from_coefficients(
+            (1.0 + cos) * 0.5,
+            -(1.0 + cos),
+            (1.0 + cos) * 0.5,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// band-pass with 0dB gain at `freq`
+    pub fn bandpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::omega(freq, q, sample_rate);
+        Self::from_coefficients(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    /// bell boost or cut of `gain_db` around `freq`
+    pub fn peaking(freq: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::omega(freq, q, sample_rate);
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        Self::from_coefficients(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    /// boost or cut of `gain_db` below `freq`
+    pub fn low_shelf(freq: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::omega(freq, q, sample_rate);
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let root = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) - (a - 1.0) * cos + root),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - root),
+            (a + 1.0) + (a - 1.0) * cos + root,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - root,
+        )
+    }
+
+    /// boost or cut of `gain_db` above `freq`
+    pub fn high_shelf(freq: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::omega(freq, q, sample_rate);
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let root = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) + (a - 1.0) * cos + root),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - root),
+            (a + 1.0) - (a - 1.0) * cos + root,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - root,
+        )
+    }
+
+    /// take over the coefficients of `other` while keeping the filter state, for glitch-free retuning
+    pub fn retune(&mut self, other: Biquad) {
+        self.b0 = other.b0;
+        self.b1 = other.b1;
+        self.b2 = other.b2;
+        self.a1 = other.a1;
+        self.a2 = other.a2;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// settled gain of a sine through the filter, in dB
+    fn response_db(mut filter: Biquad, freq: f32) -> f32 {
+        let len = SAMPLE_RATE as usize / 2;
+        let output: Vec<f32> = (0..len)
+            .map(|i| filter.process((2.0 * PI * freq * i as f32 / SAMPLE_RATE).sin()))
+            .collect();
+        let peak = output[len / 2..].iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        20.0 * peak.log10()
+    }
+
+    #[test]
+    fn cookbook_filters_have_the_expected_response() {
+        let q = std::f32::consts::FRAC_1_SQRT_2;
+        let lowpass = Biquad::lowpass(1000.0, q, SAMPLE_RATE);
+        assert!(response_db(lowpass, 100.0).abs() < 0.1);
+        assert!((response_db(lowpass, 1000.0) + 3.0).abs() < 0.1);
+        // 12dB per octave well above the corner and away from nyquist
+        assert!((response_db(lowpass, 4000.0) - response_db(lowpass, 2000.0) + 12.0).abs() < 1.0);
+
+        let highpass = Biquad::highpass(1000.0, q, SAMPLE_RATE);
+        assert!(response_db(highpass, 10000.0).abs() < 0.1);
+        assert!((response_db(highpass, 1000.0) + 3.0).abs() < 0.1);
+        assert!(response_db(highpass, 100.0) < -38.0);
+
+        let bandpass = Biquad::bandpass(2000.0, 2.0, SAMPLE_RATE);
+        assert!(response_db(bandpass, 2000.0).abs() < 0.1);
+        assert!(response_db(bandpass, 500.0) < -15.0);
+        assert!(response_db(bandpass, 8000.0) < -15.0);
+
+        let peaking = Biquad::peaking(3000.0, 1.0, 6.0, SAMPLE_RATE);
+        assert!((response_db(peaking, 3000.0) - 6.0).abs() < 0.1);
+        assert!(response_db(peaking, 50.0).abs() < 0.1);
+
+        let low_shelf = Biquad::low_shelf(300.0, q, 6.0, SAMPLE_RATE);
+        assert!((response_db(low_shelf, 30.0) - 6.0).abs() < 0.1);
+        assert!((response_db(low_shelf, 300.0) - 3.0).abs() < 0.2);
+        assert!(response_db(low_shelf, 5000.0).abs() < 0.1);
+
+        let high_shelf = Biquad::high_shelf(3000.0, q, -6.0, SAMPLE_RATE);
+        assert!((response_db(high_shelf, 15000.0) + 6.0).abs() < 0.2);
+        assert!(response_db(high_shelf, 100.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn retune_keeps_the_state() {
+        let mut filter = Biquad::lowpass(500.0, 0.7, SAMPLE_RATE);
+        for _ in 0..100 {
+            filter.process(1.0);
+        }
+        let before = filter.process(1.0);
+        filter.retune(Biquad::lowpass(600.0, 0.7, SAMPLE_RATE));
+        // a settled dc level doesn't move when only the corner changes
+        assert!((filter.process(1.0) - before).abs() < 0.01);
+    }
+}
@@ -0,0 +1,243 @@
+use super::biquad::Biquad;
+use super::oversample::{Oversampler, OversamplingSettings};
+use super::primitives::{saturate, BitCrusher, Noise, RingModulator};
+
+/// built-in voices, each a fixed combination of the primitives below
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VoiceCharacter {
+    #[default]
+    Natural,
+    Robot,
+    Monster,
+    Radio,
+    Telephone,
+}
+
+impl VoiceCharacter {
+    pub const ALL: [VoiceCharacter; 5] = [
+        VoiceCharacter::Natural,
+        VoiceCharacter::Robot,
+        VoiceCharacter::Monster,
+        VoiceCharacter::Radio,
+        VoiceCharacter::Telephone,
+    ];
+
+    /// factor applied on top of the pitch slider
+    pub fn pitch_factor(self) -> f32 {
+        match self {
+            VoiceCharacter::Monster => 0.75,
+            _ => 1.0,
+        }
+    }
+}
+
+impl std::fmt::Display for VoiceCharacter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            VoiceCharacter::Natural => "Natural",
+            VoiceCharacter::Robot => "Robot",
+            VoiceCharacter::Monster => "Monster",
+            VoiceCharacter::Radio => "Radio",
+            VoiceCharacter::Telephone => "Telephone",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// robot: ring modulation with a low sine
+const ROBOT_CARRIER_HZ: f32 = 60.0;
+/// monster: chest resonance boost and a dark top end to push the formants down
+const MONSTER_FORMANT_HZ: f32 = 220.0;
+const MONSTER_FORMANT_DB: f32 = 9.0;
+const MONSTER_LOWPASS_HZ: f32 = 2500.0;
+/// radio: narrow speaker band, overdriven, with hiss
+const RADIO_BAND_HZ: (f32, f32) = (500.0, 4000.0);
+const RADIO_DRIVE: f32 = 4.0;
+const RADIO_HISS: f32 = 0.006;
+/// telephone: the classic POTS voice band at 8 bits, 8kHz
+const TELEPHONE_BAND_HZ: (f32, f32) = (300.0, 3400.0);
+const TELEPHONE_BITS: u32 = 8;
+const TELEPHONE_RATE: f32 = 8000.0;
+/// most filter sections any character needs
+const MAX_FILTERS: usize = 4;
+const NOISE_SEED: u32 = 0x2545_f491;
+
+/// applies the selected voice character to the mono voice
+pub struct CharacterStage {
+    sample_rate: f32,
+    character: VoiceCharacter,
+    /// preallocated so switching characters never allocates, the first `active_filters` are used
+    filters: [Biquad; MAX_FILTERS],
+    active_filters: usize,
+    ring: RingModulator,
+    crusher: BitCrusher,
+    noise: Noise,
+    /// keeps the radio overdrive from aliasing
+    oversampler: Oversampler,
+}
+
+impl CharacterStage {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            character: VoiceCharacter::Natural,
+            filters: [Biquad::default(); MAX_FILTERS],
+            active_filters: 0,
+            ring: RingModulator::new(ROBOT_CARRIER_HZ, sample_rate),
+            crusher: BitCrusher::new(TELEPHONE_BITS, TELEPHONE_RATE, sample_rate),
+            noise: Noise::new(NOISE_SEED),
+            oversampler: Oversampler::new(OversamplingSettings::default()),
+        }
+    }
+
+    pub fn set_character(&mut self, character: VoiceCharacter) {
+        if character == self.character {
+            return;
+        }
+        self.character = character;
+
+        let sr = self.sample_rate;
+        // two sections per band edge for a steeper, more obviously band-limited sound
+        let band = |(low, high): (f32, f32)| {
+            [
+                Biquad::highpass(low, 0.707, sr),
+                Biquad::highpass(low, 0.707, sr),
+                Biquad::lowpass(high, 0.707, sr),
+                Biquad::lowpass(high, 0.707, sr),
+            ]
+        };
+        let filters: &[Biquad] = match character {
+            VoiceCharacter::Natural | VoiceCharacter::Robot => &[],
+            VoiceCharacter::Monster => &[
+                Biquad::peaking(MONSTER_FORMANT_HZ, 0.8, MONSTER_FORMANT_DB, sr),
+                Biquad::lowpass(MONSTER_LOWPASS_HZ, 0.707, sr),
+            ],
+            VoiceCharacter::Radio => &band(RADIO_BAND_HZ),
+            VoiceCharacter::Telephone => &band(TELEPHONE_BAND_HZ),
+        };
+        self.filters[..filters.len()].copy_from_slice(filters);
+        self.active_filters = filters.len();
+    }
+
+    pub fn set_oversampling(&mut self, settings: &OversamplingSettings) {
+        self.oversampler.set_settings(settings);
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(Biquad::reset);
+        self.ring.reset();
+        self.crusher.reset();
+        self.noise = Noise::new(NOISE_SEED);
+        self.oversampler.reset();
+    }
+
+    pub fn character(&self) -> VoiceCharacter {
+        self.character
+    }
+
+    /// only the radio drive runs through the oversampler and is held back by its filters
+    pub fn latency_samples(&self) -> usize {
+        match self.character {
+            VoiceCharacter::Radio => self.oversampler.latency_samples(),
+            _ => 0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        match self.character {
+            VoiceCharacter::Natural => input,
+            VoiceCharacter::Robot => self.ring.process(input),
+            VoiceCharacter::Monster => self.filter(input) * 0.6,
+            VoiceCharacter::Radio => {
+                let filtered = self.filter(input) * 2.0;
+                let driven = self.oversampler.process(filtered, |x| saturate(x, RADIO_DRIVE));
+                (driven + self.noise.next_sample() * RADIO_HISS) * 0.5
+            }
+            VoiceCharacter::Telephone => {
+                let band_limited = self.filter(input) * 1.5;
+                self.crusher.process(band_limited)
+            }
+        }
+    }
+
+    fn filter(&mut self, input: f32) -> f32 {
+        self.filters[..self.active_filters].iter_mut().fold(input, |sample, filter| filter.process(sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{amplitude, sine};
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn render(character: VoiceCharacter, freq: f32) -> Vec<f32> {
+        let mut stage = CharacterStage::new(SAMPLE_RATE);
+        stage.set_character(character);
+        sine(freq, SAMPLE_RATE as u32, 0.5, 0.5).into_iter().map(|x| stage.process(x)).collect()
+    }
+
+    /// amplitude of one frequency over the second half of the signal, past the filters settling
+    fn settled_amplitude(signal: &[f32], freq: f32) -> f32 {
+        amplitude(&signal[signal.len() / 2..], freq, SAMPLE_RATE as u32)
+    }
+
+    #[test]
+    fn natural_passes_the_voice_unchanged() {
+        assert_eq!(render(VoiceCharacter::Natural, 440.0), sine(440.0, SAMPLE_RATE as u32, 0.5, 0.5));
+    }
+
+    #[test]
+    fn robot_moves_a_tone_to_sidebands_around_the_carrier() {
+        let output = render(VoiceCharacter::Robot, 1000.0);
+        let below = settled_amplitude(&output, 1000.0 - ROBOT_CARRIER_HZ);
+        let above = settled_amplitude(&output, 1000.0 + ROBOT_CARRIER_HZ);
+        assert!((below - 0.25).abs() < 0.02 && (above - 0.25).abs() < 0.02, "{} {}", below, above);
+        assert!(settled_amplitude(&output, 1000.0) < 0.01);
+    }
+
+    #[test]
+    fn monster_lowers_the_pitch_and_darkens_the_voice() {
+        assert!(VoiceCharacter::Monster.pitch_factor() < 1.0);
+        let formant = settled_amplitude(&render(VoiceCharacter::Monster, MONSTER_FORMANT_HZ), MONSTER_FORMANT_HZ);
+        let top = settled_amplitude(&render(VoiceCharacter::Monster, 6000.0), 6000.0);
+        assert!(formant > 0.5, "the chest resonance is boosted: {}", formant);
+        assert!(top < 0.05, "the top end is cut: {}", top);
+    }
+
+    #[test]
+    fn radio_and_telephone_keep_only_their_band() {
+        for (character, (low, high)) in
+            [(VoiceCharacter::Radio, RADIO_BAND_HZ), (VoiceCharacter::Telephone, TELEPHONE_BAND_HZ)]
+        {
+            let centre = (low * high).sqrt();
+            let passed = settled_amplitude(&render(character, centre), centre);
+            let bass = settled_amplitude(&render(character, low / 4.0), low / 4.0);
+            assert!(passed > 0.2, "{}: {}", character, passed);
+            assert!(bass < passed / 30.0, "{}: {} vs {}", character, bass, passed);
+        }
+    }
+
+    #[test]
+    fn telephone_is_quantised_to_eight_bits() {
+        let levels = 2.0_f32.powi(TELEPHONE_BITS as i32 - 1);
+        for sample in render(VoiceCharacter::Telephone, 1000.0) {
+            assert_eq!(sample, (sample * levels).round() / levels);
+        }
+    }
+
+    #[test]
+    fn reset_starts_every_character_over() {
+        for character in VoiceCharacter::ALL {
+            let mut stage = CharacterStage::new(SAMPLE_RATE);
+            stage.set_character(character);
+            let tone = sine(700.0, SAMPLE_RATE as u32, 1001.0 / SAMPLE_RATE, 0.5);
+            let first: Vec<f32> = tone.iter().map(|&x| stage.process(x)).collect();
+            stage.reset();
+            let again: Vec<f32> = tone.iter().map(|&x| stage.process(x)).collect();
+            assert_eq!(first, again, "{}", character);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CompressorSettings {
+    /// level above which the peaks are squashed, in dBFS
+    pub threshold_db: f32,
+    /// how much the part of a peak above the threshold is scaled down, 4 leaves a quarter of it
+    pub ratio: f32,
+    /// width of the soft knee centred on the threshold, in dB. 0 is a hard knee
+    pub knee_db: f32,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: -3.1,
+            ratio: 3.33,
+            knee_db: 2.5,
+        }
+    }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// memoryless soft knee compression curve on the instantaneous level, run inside the
+/// oversampler after the pitch shifter. the levels are worked out once per settings change
+#[derive(Debug, Clone, Copy)]
+pub struct Compressor {
+    threshold: f32,
+    slope: f32,
+    knee_start: f32,
+    knee_end: f32,
+}
+
+impl Compressor {
+    pub fn new(settings: &CompressorSettings) -> Self {
+        let half_knee = settings.knee_db.max(0.0) / 2.0;
+        Self {
+            threshold: db_to_linear(settings.threshold_db),
+            slope: 1.0 / settings.ratio.max(1.0),
+            knee_start: db_to_linear(settings.threshold_db - half_knee),
+            knee_end: db_to_linear(settings.threshold_db + half_knee),
+        }
+    }
+
+    pub fn process(&self, input: f32) -> f32 {
+        let abs_input = input.abs();
+        let sign = if input >= 0.0 { 1.0 } else { -1.0 };
+
+        if abs_input <= self.knee_start {
+            // below the knee - no compression
+            input
+        } else if abs_input >= self.knee_end {
+            // above the knee - full compression
+            let excess = abs_input - self.threshold;
+            sign * (self.threshold + excess * self.slope)
+        } else {
+            // in the knee - smooth transition
+            let knee_position = (abs_input - self.knee_start) / (self.knee_end - self.knee_start);
+            let smooth = knee_position * knee_position * (3.0 - 2.0 * knee_position); // smoothstep
+            let current_slope = 1.0 - smooth * (1.0 - self.slope);
+            let excess = abs_input - self.threshold;
+            sign * (self.threshold + excess * current_slope)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_is_monotonic_symmetric_and_continuous() {
+        let settings = CompressorSettings::default();
+        let compressor = Compressor::new(&settings);
+        let inputs: Vec<f32> = (-2000..=2000).map(|i| i as f32 / 1000.0).collect();
+        let curve: Vec<f32> = inputs.iter().map(|&x| compressor.process(x)).collect();
+
+        for (pair, x) in curve.windows(2).zip(&inputs) {
+            assert!(pair[1] >= pair[0], "falls at {}", x);
+            // the knee bends the curve but never steps
+            assert!(pair[1] - pair[0] < 2e-3, "jumps at {}", x);
+        }
+        let knee_start = db_to_linear(settings.threshold_db - settings.knee_db / 2.0);
+        for &x in &inputs {
+            let y = compressor.process(x);
+            assert_eq!(compressor.process(-x), -y);
+            if x.abs() <= knee_start {
+                assert_eq!(y, x);
+            }
+        }
+        assert!(compressor.process(2.0) < 1.1);
+    }
+
+    #[test]
+    fn settings_shape_the_curve() {
+        let hard = Compressor::new(&CompressorSettings { threshold_db: -6.0, ratio: 4.0, knee_db: 0.0 });
+        let threshold = db_to_linear(-6.0);
+        assert_eq!(hard.process(threshold * 0.99), threshold * 0.99);
+        assert!((hard.process(threshold + 0.4) - (threshold + 0.1)).abs() < 1e-6);
+
+        // ratio 1 leaves everything alone
+        let off = Compressor::new(&CompressorSettings { ratio: 1.0, ..CompressorSettings::default() });
+        assert!((-20..=20).map(|i| i as f32 / 10.0).all(|x| (off.process(x) - x).abs() < 1e-6));
+    }
+}
@@ -0,0 +1,146 @@
+use super::biquad::Biquad;
+use super::smoothing_coefficient;
+
+const ATTACK_MS: f32 = 1.0;
+const RELEASE_MS: f32 = 60.0;
+const SPLIT_Q: f32 = 0.707;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DeEsserSettings {
+    pub enabled: bool,
+    /// split point, everything above is treated as sibilance
+    pub frequency_hz: f32,
+    /// level of the sibilant band above which it gets turned down
+    pub threshold_db: f32,
+    /// maximum amount of reduction
+    pub range_db: f32,
+    /// output only the sidechain to hear what is being detected
+    pub listen: bool,
+}
+
+impl Default for DeEsserSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency_hz: 6000.0,
+            threshold_db: -30.0,
+            range_db: 10.0,
+            listen: false,
+        }
+    }
+}
+
+/// linkwitz-riley split, two butterworth sections per band. the bands are in phase at
+/// every frequency and sum back to a flat response
+fn split(frequency_hz: f32, sample_rate: f32) -> ([Biquad; 2], [Biquad; 2]) {
+    let low = Biquad::lowpass(frequency_hz, SPLIT_Q, sample_rate);
+    let high = Biquad::highpass(frequency_hz, SPLIT_Q, sample_rate);
+    ([low, low], [high, high])
+}
+
+/// split-band de-esser: only the band above the split frequency is turned down
+/// while it is louder than the threshold, the rest of the voice is untouched
+pub struct DeEsser {
+    sample_rate: f32,
+    settings: DeEsserSettings,
+    low: [Biquad; 2],
+    high: [Biquad; 2],
+    envelope: f32,
+    attack: f32,
+    release: f32,
+    /// current reduction of the sibilant band, in dB
+    reduction_db: f32,
+}
+
+impl DeEsser {
+    pub fn new(sample_rate: f32) -> Self {
+        let settings = DeEsserSettings::default();
+        let (low, high) = split(settings.frequency_hz, sample_rate);
+        Self {
+            sample_rate,
+            settings,
+            low,
+            high,
+            envelope: 0.0,
+            attack: smoothing_coefficient(ATTACK_MS, sample_rate),
+            release: smoothing_coefficient(RELEASE_MS, sample_rate),
+            reduction_db: 0.0,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: &DeEsserSettings) {
+        if settings.enabled && !self.settings.enabled {
+            self.reset();
+        }
+        if settings.frequency_hz != self.settings.frequency_hz {
+            let (low, high) = split(settings.frequency_hz, self.sample_rate);
+            self.low.iter_mut().for_each(|f| f.retune(low[0]));
+            self.high.iter_mut().for_each(|f| f.retune(high[0]));
+        }
+        self.settings = *settings;
+    }
+
+    pub fn reset(&mut self) {
+        self.low.iter_mut().chain(self.high.iter_mut()).for_each(Biquad::reset);
+        self.envelope = 0.0;
+        self.reduction_db = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let low = self.low.iter_mut().fold(input, |x, f| f.process(x));
+        let high = self.high.iter_mut().fold(input, |x, f| f.process(x));
+
+        let level = high.abs();
+        let speed = if level > self.envelope { self.attack } else { self.release };
+        self.envelope += (level - self.envelope) * speed;
+
+        let level_db = 20.0 * self.envelope.max(1e-6).log10();
+        self.reduction_db = (level_db - self.settings.threshold_db).clamp(0.0, self.settings.range_db.max(0.0));
+
+        if self.settings.listen {
+            return high;
+        }
+        low + high * 10.0_f32.powf(-self.reduction_db / 20.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{rms, sine};
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// input and output of an enabled de-esser for a sine, past the first 100ms
+    fn run(freq: f32, amplitude: f32) -> (Vec<f32>, Vec<f32>) {
+        let mut deesser = DeEsser::new(SAMPLE_RATE);
+        deesser.set_settings(&DeEsserSettings { enabled: true, ..DeEsserSettings::default() });
+        let input = sine(freq, SAMPLE_RATE as u32, 0.5, amplitude);
+        let output: Vec<f32> = input.iter().map(|&x| deesser.process(x)).collect();
+        let settled = SAMPLE_RATE as usize / 10;
+        (input[settled..].to_vec(), output[settled..].to_vec())
+    }
+
+    #[test]
+    fn loud_sibilance_is_turned_down_by_the_range() {
+        // an octave above the split, where nearly all of it is in the sibilant band
+        let (input, output) = run(12000.0, 0.5);
+        let change_db = 20.0 * (rms(&output) / rms(&input)).log10();
+        let range_db = DeEsserSettings::default().range_db;
+        assert!(change_db < -range_db + 1.5 && change_db > -range_db - 0.5, "{:.1} dB", change_db);
+
+        // below the threshold the same frequency is left alone
+        let (input, output) = run(12000.0, 0.01);
+        let change_db = 20.0 * (rms(&output) / rms(&input)).log10();
+        assert!(change_db.abs() < 0.1, "{:.2} dB", change_db);
+    }
+
+    #[test]
+    fn low_tone_passes_at_unity() {
+        let (input, output) = run(200.0, 0.5);
+        let change_db = 20.0 * (rms(&output) / rms(&input)).log10();
+        assert!(change_db.abs() < 0.1, "{:.2} dB", change_db);
+    }
+}
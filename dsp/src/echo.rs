@@ -0,0 +1,299 @@
+use super::biquad::Biquad;
+use super::smoothing_coefficient;
+
+pub const MAX_ECHO_MS: f32 = 4000.0;
+/// glide time when the delay time changes, avoids clicks while moving the slider
+const TIME_SMOOTHING_MS: f32 = 50.0;
+const FILTER_Q: f32 = 0.707;
+
+/// note lengths the echo time can be locked to when synced to a tempo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoteDivision {
+    Half,
+    Quarter,
+    DottedEighth,
+    Eighth,
+    EighthTriplet,
+    Sixteenth,
+}
+
+impl NoteDivision {
+    pub const ALL: [NoteDivision; 6] = [
+        NoteDivision::Half,
+        NoteDivision::Quarter,
+        NoteDivision::DottedEighth,
+        NoteDivision::Eighth,
+        NoteDivision::EighthTriplet,
+        NoteDivision::Sixteenth,
+    ];
+
+    /// length in quarter notes
+    pub fn beats(self) -> f32 {
+        match self {
+            NoteDivision::Half => 2.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::DottedEighth => 0.75,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::EighthTriplet => 1.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+        }
+    }
+}
+
+impl std::fmt::Display for NoteDivision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            NoteDivision::Half => "1/2",
+            NoteDivision::Quarter => "1/4",
+            NoteDivision::DottedEighth => "1/8 dotted",
+            NoteDivision::Eighth => "1/8",
+            NoteDivision::EighthTriplet => "1/8 triplet",
+            NoteDivision::Sixteenth => "1/16",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EchoSettings {
+    pub enabled: bool,
+    /// delay time used when not synced to a tempo
+    pub time_ms: f32,
+    /// lock the delay time to `division` at `bpm`
+    pub sync: bool,
+    pub bpm: f32,
+    pub division: NoteDivision,
+    /// 0..1, amount of each repeat fed back into the line
+    pub feedback: f32,
+    /// filters in the feedback path, each repeat gets darker and thinner
+    pub low_cut_hz: f32,
+    pub high_cut_hz: f32,
+    /// bounce the repeats between the left and right channel
+    pub ping_pong: bool,
+    pub wet: f32,
+    pub dry: f32,
+}
+
+impl Default for EchoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time_ms: 350.0,
+            sync: false,
+            bpm: 120.0,
+            division: NoteDivision::Eighth,
+            feedback: 0.4,
+            low_cut_hz: 150.0,
+            high_cut_hz: 6000.0,
+            ping_pong: false,
+            wet: 0.35,
+            dry: 1.0,
+        }
+    }
+}
+
+impl EchoSettings {
+    /// effective delay time, either free running or derived from the tempo
+    pub fn delay_ms(&self) -> f32 {
+        let time = if self.sync {
+            60_000.0 / self.bpm.max(1.0) * self.division.beats()
+        } else {
+            self.time_ms
+        };
+        time.clamp(1.0, MAX_ECHO_MS)
+    }
+}
+
+/// one channel of the echo, a preallocated delay line with its feedback filters
+struct EchoLine {
+    buffer: Vec<f32>,
+    write_index: usize,
+    low_cut: Biquad,
+    high_cut: Biquad,
+}
+
+impl EchoLine {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len],
+            write_index: 0,
+            low_cut: Biquad::default(),
+            high_cut: Biquad::default(),
+        }
+    }
+
+    /// linearly interpolated read `delay` samples behind the write head
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let position = (self.write_index as f32 - delay).rem_euclid(len as f32);
+        let index = position.floor() as usize % len;
+        let next = (index + 1) % len;
+        let fraction = position.fract();
+        self.buffer[index] * (1.0 - fraction) + self.buffer[next] * fraction
+    }
+
+    fn write(&mut self, sample: f32) {
+        self.buffer[self.write_index] = sample;
+        self.write_index = (self.write_index + 1) % self.buffer.len();
+    }
+
+    fn filter(&mut self, sample: f32) -> f32 {
+        self.high_cut.process(self.low_cut.process(sample))
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.low_cut.reset();
+        self.high_cut.reset();
+    }
+}
+
+/// feedback delay with filtered repeats and optional stereo ping-pong
+pub struct Echo {
+    sample_rate: f32,
+    settings: EchoSettings,
+    left: EchoLine,
+    right: EchoLine,
+    /// smoothed delay time, in samples
+    current_delay: f32,
+    time_smoothing: f32,
+}
+
+impl Echo {
+    pub fn new(sample_rate: f32) -> Self {
+        // one extra sample so the longest delay doesn't read the sample being written
+        let len = (MAX_ECHO_MS / 1000.0 * sample_rate) as usize + 2;
+        let settings = EchoSettings::default();
+        let mut echo = Self {
+            sample_rate,
+            settings,
+            left: EchoLine::new(len),
+            right: EchoLine::new(len),
+            current_delay: settings.delay_ms() / 1000.0 * sample_rate,
+            time_smoothing: smoothing_coefficient(TIME_SMOOTHING_MS, sample_rate),
+        };
+        echo.retune_filters();
+        echo
+    }
+
+    pub fn set_settings(&mut self, settings: &EchoSettings) {
+        let previous = self.settings;
+        self.settings = *settings;
+
+        // start from an empty line instead of replaying old repeats
+        if settings.enabled && !previous.enabled {
+            self.reset();
+        }
+        if settings.low_cut_hz != previous.low_cut_hz || settings.high_cut_hz != previous.high_cut_hz {
+            self.retune_filters();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.left.clear();
+        self.right.clear();
+        self.current_delay = self.settings.delay_ms() / 1000.0 * self.sample_rate;
+    }
+
+    fn retune_filters(&mut self) {
+        let low_cut = Biquad::highpass(self.settings.low_cut_hz, FILTER_Q, self.sample_rate);
+        let high_cut = Biquad::lowpass(self.settings.high_cut_hz, FILTER_Q, self.sample_rate);
+        for line in [&mut self.left, &mut self.right] {
+            line.low_cut.retune(low_cut);
+            line.high_cut.retune(high_cut);
+        }
+    }
+
+    /// feed one left/right pair, returns the dry/wet mixed pair
+    pub fn process(&mut self, input_left: f32, input_right: f32) -> (f32, f32) {
+        let target_delay = self.settings.delay_ms() / 1000.0 * self.sample_rate;
+        self.current_delay += (target_delay - self.current_delay) * self.time_smoothing;
+
+        let echo_left = self.left.read(self.current_delay);
+        let echo_right = self.right.read(self.current_delay);
+
+        let feedback = self.settings.feedback.clamp(0.0, 0.95);
+        let feedback_left = self.left.filter(echo_left) * feedback;
+        let feedback_right = self.right.filter(echo_right) * feedback;
+
+        if self.settings.ping_pong {
+            // the voice enters on the left and every repeat crosses over
+            self.left.write((input_left + input_right) * 0.5 + feedback_right);
+            self.right.write(feedback_left);
+        } else {
+            self.left.write(input_left + feedback_left);
+            self.right.write(input_right + feedback_right);
+        }
+
+        (
+            input_left * self.settings.dry + echo_left * self.settings.wet,
+            input_right * self.settings.dry + echo_right * self.settings.wet,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const TIME_MS: f32 = 100.0;
+    const DELAY: usize = (TIME_MS / 1000.0 * SAMPLE_RATE) as usize;
+
+    fn impulse_response(settings: EchoSettings, repeats: usize) -> Vec<(f32, f32)> {
+        let mut echo = Echo::new(SAMPLE_RATE);
+        echo.set_settings(&EchoSettings {
+            enabled: true,
+            time_ms: TIME_MS,
+            low_cut_hz: 20.0,
+            high_cut_hz: 20000.0,
+            wet: 1.0,
+            dry: 0.0,
+            ..settings
+        });
+        (0..DELAY * repeats + DELAY / 2)
+            .map(|i| if i == 0 { echo.process(1.0, 1.0) } else { echo.process(0.0, 0.0) })
+            .collect()
+    }
+
+    /// where the largest sample of one channel lies around the nth repeat, and its size
+    fn peak(response: &[(f32, f32)], repeat: usize, channel: impl Fn(&(f32, f32)) -> f32) -> (usize, f32) {
+        let start = repeat * DELAY - DELAY / 2;
+        response[start..start + DELAY]
+            .iter()
+            .map(|sample| channel(sample).abs())
+            .enumerate()
+            .fold((0, 0.0), |best, (i, level)| if level > best.1 { (start + i, level) } else { best })
+    }
+
+    #[test]
+    fn repeats_arrive_at_the_set_time_and_decay() {
+        let response = impulse_response(EchoSettings { feedback: 0.5, ..EchoSettings::default() }, 4);
+        let mut previous = f32::MAX;
+        for repeat in 1..=4 {
+            let (position, left) = peak(&response, repeat, |s| s.0);
+            let (_, right) = peak(&response, repeat, |s| s.1);
+            assert!(position.abs_diff(repeat * DELAY) <= 2, "repeat {} at {}", repeat, position);
+            assert_eq!(left, right);
+            assert!(left < previous, "repeat {} is {} after {}", repeat, left, previous);
+            previous = left;
+        }
+        assert!(previous < 0.5_f32.powi(3) * 1.1, "{}", previous);
+    }
+
+    #[test]
+    fn ping_pong_alternates_the_repeats_between_left_and_right() {
+        let settings = EchoSettings { feedback: 0.5, ping_pong: true, ..EchoSettings::default() };
+        let response = impulse_response(settings, 4);
+        for repeat in 1..=4 {
+            let left = peak(&response, repeat, |s| s.0);
+            let right = peak(&response, repeat, |s| s.1);
+            let (loud, quiet) = if repeat % 2 == 1 { (left, right) } else { (right, left) };
+            assert!(loud.0.abs_diff(repeat * DELAY) <= 2, "repeat {} at {}", repeat, loud.0);
+            assert!(quiet.1 < loud.1 / 100.0, "repeat {}: {} vs {}", repeat, loud.1, quiet.1);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use super::biquad::Biquad;
+
+const SHELF_Q: f32 = 0.707;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EqSettings {
+    pub enabled: bool,
+    /// shelf below this frequency
+    pub low_hz: f32,
+    pub low_gain_db: f32,
+    /// bell around this frequency
+    pub mid_hz: f32,
+    pub mid_gain_db: f32,
+    /// width of the bell, higher is narrower
+    pub mid_q: f32,
+    /// shelf above this frequency
+    pub high_hz: f32,
+    pub high_gain_db: f32,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            low_hz: 200.0,
+            low_gain_db: 0.0,
+            mid_hz: 1500.0,
+            mid_gain_db: 0.0,
+            mid_q: 1.0,
+            high_hz: 5000.0,
+            high_gain_db: 0.0,
+        }
+    }
+}
+
+/// three band tone control: low shelf, mid bell and high shelf
+pub struct Equalizer {
+    sample_rate: f32,
+    settings: EqSettings,
+    bands: [Biquad; 3],
+}
+
+impl Equalizer {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut eq = Self {
+            sample_rate,
+            settings: EqSettings::default(),
+            bands: [Biquad::default(); 3],
+        };
+        eq.bands = eq.filters(&EqSettings::default());
+        eq
+    }
+
+    pub fn set_settings(&mut self, settings: &EqSettings) {
+        if settings.enabled && !self.settings.enabled {
+            self.reset();
+        }
+        if *settings != self.settings {
+            let filters = self.filters(settings);
+            for (band, filter) in self.bands.iter_mut().zip(filters) {
+                band.retune(filter);
+            }
+        }
+        self.settings = *settings;
+    }
+
+    pub fn reset(&mut self) {
+        self.bands.iter_mut().for_each(Biquad::reset);
+    }
+
+    fn filters(&self, settings: &EqSettings) -> [Biquad; 3] {
+        let sr = self.sample_rate;
+        [
+            Biquad::low_shelf(settings.low_hz, SHELF_Q, settings.low_gain_db, sr),
+            Biquad::peaking(settings.mid_hz, settings.mid_q, settings.mid_gain_db, sr),
+            Biquad::high_shelf(settings.high_hz, SHELF_Q, settings.high_gain_db, sr),
+        ]
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.bands.iter_mut().fold(input, |sample, band| band.process(sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn gain_db(settings: EqSettings, freq: f32) -> f32 {
+        let mut eq = Equalizer::new(SAMPLE_RATE);
+        eq.set_settings(&EqSettings { enabled: true, ..settings });
+        let len = SAMPLE_RATE as usize / 2;
+        let output: Vec<f32> = (0..len).map(|i| eq.process((2.0 * PI * freq * i as f32 / SAMPLE_RATE).sin())).collect();
+        let peak = output[len / 2..].iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        20.0 * peak.log10()
+    }
+
+    #[test]
+    fn flat_settings_pass_everything_at_unity() {
+        for freq in [50.0, 200.0, 1500.0, 5000.0, 15000.0] {
+            assert!(gain_db(EqSettings::default(), freq).abs() < 0.01, "{} Hz", freq);
+        }
+    }
+
+    #[test]
+    fn each_band_moves_its_own_range() {
+        let settings = EqSettings { low_gain_db: 6.0, mid_gain_db: -6.0, high_gain_db: 4.0, ..EqSettings::default() };
+        assert!((gain_db(settings, 30.0) - 6.0).abs() < 0.3);
+        assert!((gain_db(settings, 1500.0) + 6.0).abs() < 0.3);
+        assert!((gain_db(settings, 18000.0) - 4.0).abs() < 0.3);
+    }
+}
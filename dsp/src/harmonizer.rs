@@ -0,0 +1,193 @@
+use std::f32::consts::PI;
+
+pub const MAX_VOICES: usize = 4;
+pub const MAX_VOICE_DELAY_MS: f32 = 50.0;
+/// length of the sweeping read window of each shifter tap, longer is smoother but smears transients
+const WINDOW_MS: f32 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HarmonyVoiceSettings {
+    pub enabled: bool,
+    /// interval above (or below) the lead voice, in semitones
+    pub interval: f32,
+    pub level: f32,
+    /// -1 hard left .. 1 hard right
+    pub pan: f32,
+    /// slight mistuning for a doubled/choir sound, in cents
+    pub detune_cents: f32,
+    pub delay_ms: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct HarmonizerSettings {
+    pub enabled: bool,
+    /// level of the lead voice
+    pub dry: f32,
+    pub voices: [HarmonyVoiceSettings; MAX_VOICES],
+}
+
+impl Default for HarmonizerSettings {
+    fn default() -> Self {
+        let voice = |enabled, interval, pan, detune_cents, delay_ms| HarmonyVoiceSettings {
+            enabled,
+            interval,
+            level: 0.5,
+            pan,
+            detune_cents,
+            delay_ms,
+        };
+        Self {
+            enabled: false,
+            dry: 1.0,
+            voices: [
+                voice(true, 4.0, -0.5, 6.0, 12.0),
+                voice(true, 7.0, 0.5, -6.0, 18.0),
+                voice(false, -12.0, 0.0, 0.0, 0.0),
+                voice(false, 12.0, 0.0, 4.0, 8.0),
+            ],
+        }
+    }
+}
+
+/// one delay-line pitch shifter: two taps sweep through a short window half a period
+/// apart and are crossfaded with complementary hann windows
+#[derive(Debug, Clone, Copy, Default)]
+struct ShifterVoice {
+    /// position of the first tap in the window, 0..1
+    phase: f32,
+    ratio: f32,
+    delay: f32,
+    gain_left: f32,
+    gain_right: f32,
+}
+
+/// layers up to four pitch-shifted copies of the voice around it in the stereo field
+pub struct Harmonizer {
+    sample_rate: f32,
+    settings: HarmonizerSettings,
+    buffer: Vec<f32>,
+    write_index: usize,
+    window: f32,
+    voices: [ShifterVoice; MAX_VOICES],
+}
+
+impl Harmonizer {
+    pub fn new(sample_rate: f32) -> Self {
+        let window = WINDOW_MS / 1000.0 * sample_rate;
+        let len = ((WINDOW_MS + MAX_VOICE_DELAY_MS) / 1000.0 * sample_rate) as usize + 4;
+        let mut harmonizer = Self {
+            sample_rate,
+            settings: HarmonizerSettings::default(),
+            buffer: vec![0.0; len],
+            write_index: 0,
+            window,
+            voices: [ShifterVoice::default(); MAX_VOICES],
+        };
+        harmonizer.configure();
+        harmonizer
+    }
+
+    pub fn set_settings(&mut self, settings: &HarmonizerSettings) {
+        if settings.enabled && !self.settings.enabled {
+            self.reset();
+        }
+        if *settings != self.settings {
+            self.settings = *settings;
+            self.configure();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    fn configure(&mut self) {
+        for (voice, settings) in self.voices.iter_mut().zip(self.settings.voices.iter()) {
+            let semitones = settings.interval + settings.detune_cents / 100.0;
+            voice.ratio = 2.0_f32.powf(semitones / 12.0);
+            voice.delay = settings.delay_ms.clamp(0.0, MAX_VOICE_DELAY_MS) / 1000.0 * self.sample_rate;
+
+            // constant power pan
+            let angle = (settings.pan.clamp(-1.0, 1.0) + 1.0) * PI / 4.0;
+            let level = if settings.enabled { settings.level } else { 0.0 };
+            voice.gain_left = angle.cos() * level;
+            voice.gain_right = angle.sin() * level;
+        }
+    }
+
+    /// linearly interpolated read `delay` samples behind the newest sample
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let position = (self.write_index as f32 - 1.0 - delay).rem_euclid(len as f32);
+        let index = position.floor() as usize % len;
+        let fraction = position.fract();
+        self.buffer[index] * (1.0 - fraction) + self.buffer[(index + 1) % len] * fraction
+    }
+
+    /// feed one sample of the lead voice, returns the lead plus harmonies as a left/right pair
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        self.buffer[self.write_index] = input;
+        self.write_index = (self.write_index + 1) % self.buffer.len();
+
+        let dry = input * self.settings.dry;
+        let (mut left, mut right) = (dry, dry);
+        for i in 0..MAX_VOICES {
+            let voice = self.voices[i];
+            if voice.gain_left == 0.0 && voice.gain_right == 0.0 {
+                continue;
+            }
+
+            let phase_a = voice.phase;
+            let phase_b = (voice.phase + 0.5) % 1.0;
+            let tap_a = self.read(voice.delay + phase_a * self.window);
+            let tap_b = self.read(voice.delay + phase_b * self.window);
+            // sin² + cos² keeps the crossfade at unity gain
+            let weight_a = (PI * phase_a).sin().powi(2);
+            let shifted = tap_a * weight_a + tap_b * (1.0 - weight_a);
+
+            left += shifted * voice.gain_left;
+            right += shifted * voice.gain_right;
+
+            // the read taps move at `ratio` samples per sample relative to the writer
+            let voice = &mut self.voices[i];
+            voice.phase = (voice.phase + (1.0 - voice.ratio) / self.window).rem_euclid(1.0);
+        }
+        (left, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{amplitude, sine};
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn voice_a_fifth_up_sounds_at_the_shifted_frequency() {
+        let mut settings = HarmonizerSettings { enabled: true, dry: 0.0, ..HarmonizerSettings::default() };
+        settings.voices = [HarmonyVoiceSettings { enabled: false, ..settings.voices[0] }; MAX_VOICES];
+        settings.voices[0] =
+            HarmonyVoiceSettings { enabled: true, interval: 7.0, level: 1.0, pan: 0.0, detune_cents: 0.0, delay_ms: 0.0 };
+        let mut harmonizer = Harmonizer::new(SAMPLE_RATE);
+        harmonizer.set_settings(&settings);
+
+        // whole cycles in half the shifter window, so the tap crossfades don't spread the
+        // shifted tone over the sidebands either side of it
+        let lead_hz = 200.0;
+        let output: Vec<f32> =
+            sine(lead_hz, SAMPLE_RATE as u32, 2.0, 0.5).into_iter().map(|x| harmonizer.process(x).0).collect();
+        let settled = &output[SAMPLE_RATE as usize..];
+
+        let expected = lead_hz * 2.0_f32.powf(7.0 / 12.0);
+        let strongest = (100..500)
+            .map(|hz| hz as f32)
+            .max_by(|a, b| amplitude(settled, *a, SAMPLE_RATE as u32).total_cmp(&amplitude(settled, *b, SAMPLE_RATE as u32)))
+            .unwrap();
+        assert!((strongest - expected).abs() <= 1.0, "strongest at {} Hz instead of {:.1} Hz", strongest, expected);
+        assert!(amplitude(settled, lead_hz, SAMPLE_RATE as u32) < amplitude(settled, expected, SAMPLE_RATE as u32) / 10.0);
+    }
+}
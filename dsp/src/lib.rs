@@ -0,0 +1,698 @@
+//! the voice effect chain without any audio I/O, shared by the standalone app and the plugin.
+//! everything runs on caller provided buffers at the sample rate given to `DspProcessor::new`
+
+pub mod agc;
+pub mod biquad;
+pub mod character;
+pub mod compressor;
+pub mod deesser;
+pub mod echo;
+pub mod eq;
+pub mod harmonizer;
+pub mod oversample;
+pub mod primitives;
+pub mod resample;
+pub mod reverb;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_signals;
+pub mod vocoder;
+
+use agc::{Agc, AgcSettings};
+use character::{CharacterStage, VoiceCharacter};
+use compressor::{Compressor, CompressorSettings};
+use deesser::{DeEsser, DeEsserSettings};
+use echo::{Echo, EchoSettings};
+use eq::{EqSettings, Equalizer};
+use harmonizer::{Harmonizer, HarmonizerSettings};
+use oversample::{Oversampler, OversamplingSettings, MAX_LATENCY_SAMPLES};
+use primitives::SmoothedGain;
+use reverb::{Reverb, ReverbSettings};
+use vocoder::{Vocoder, VocoderSettings};
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// length of each pitch ring buffer
+const RING_BUFFER_MS: f32 = 5.8;
+/// time constant of the glide towards a new pitch
+const PITCH_SMOOTHING_MS: f32 = 2.25;
+/// rate of the sine crossfade between the two read heads
+const CROSSFADE_HZ: f32 = 35.0;
+/// cutoffs of the two anti-aliasing low-pass stages
+const FILTER_STAGE_1_HZ: f32 = 1140.0;
+const FILTER_STAGE_2_HZ: f32 = 740.0;
+/// corner frequency of the DC blocking filter
+const DC_BLOCK_HZ: f32 = 35.0;
+/// glide time of the input trim and output volume
+const GAIN_SMOOTHING_MS: f32 = 20.0;
+/// share of the pitch shifted and compressed signal in the mix, the rest is dry
+const DRY_WET_MIX: f32 = 0.8;
+/// samples per pass of the pitch shifter loops, the scratch buffers for one pass live on the stack
+const BLOCK_SIZE: usize = 64;
+
+/// per-sample coefficient of a one-pole smoother with the given time constant
+pub fn smoothing_coefficient(time_ms: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1000.0 / (time_ms * sample_rate)).exp()
+}
+
+/// per-sample coefficient of a one-pole low-pass with the given cutoff
+pub fn one_pole_coefficient(cutoff_hz: f32, sample_rate: f32) -> f32 {
+    1.0 - (-2.0 * PI * cutoff_hz / sample_rate).exp()
+}
+
+/// settings of the optional effect stages that follow the pitch shifter
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EffectSettings {
+    pub deesser: DeEsserSettings,
+    pub eq: EqSettings,
+    pub character: VoiceCharacter,
+    pub vocoder: VocoderSettings,
+    pub harmonizer: HarmonizerSettings,
+    pub echo: EchoSettings,
+    pub reverb: ReverbSettings,
+    pub agc: AgcSettings,
+    /// soft knee compressor right after the pitch shifter
+    pub compressor: CompressorSettings,
+    /// shared by every nonlinear stage
+    pub oversampling: OversamplingSettings,
+}
+
+pub struct DspProcessor {
+    /// input trim before and output volume after the whole chain
+    input_gain: SmoothedGain,
+    output_gain: SmoothedGain,
+    /// squashes the peaks of the pitched voice
+    compressor: Compressor,
+    /// runs the soft compressor above the base rate
+    oversampler: Oversampler,
+    /// holds back the dry signal by the oversampling delay so the dry/wet blend doesn't change
+    dry_delay: Vec<f32>,
+    dry_delay_index: usize,
+    /// shared pitch control (playback speed)
+    pitch: Arc<Mutex<f32>>,
+    /// ring buffer both read heads crossfade between, `ring_len` samples shifted up by one
+    /// with the last sample copied in front and the first two after the end, so the
+    /// four points of the interpolation are always contiguous
+    ring_buffer: Vec<f32>,
+    ring_len: usize,
+    write_index: usize,
+    read_index_a: f32,
+    read_index_b: f32,
+    /// crossfade position for smooth transitions
+    crossfade_pos: f32,
+    crossfade_step: f32,
+    /// multi-stage low-pass filters for better anti-aliasing
+    filter_state_1: f32,
+    filter_state_2: f32,
+    filter_coeff_1: f32,
+    filter_coeff_2: f32,
+    /// DC blocking filter
+    dc_filter_x: f32,
+    dc_filter_y: f32,
+    dc_filter_pole: f32,
+    /// smoothing for pitch changes
+    current_pitch: f32,
+    target_pitch: f32,
+    pitch_smoothing: f32,
+    /// tames the sibilance the pitch shift brings out
+    deesser: DeEsser,
+    /// tone control of the pitched voice
+    eq: Equalizer,
+    /// voice character applied to the pitched voice
+    character: CharacterStage,
+    /// vocoder driven by the pitched voice
+    vocoder: Vocoder,
+    /// stereo effects at the end of the chain
+    harmonizer: Harmonizer,
+    echo: Echo,
+    reverb: Reverb,
+    /// loudness meter and automatic gain control on the final mix
+    agc: Agc,
+    effects: EffectSettings,
+}
+
+impl DspProcessor {
+    pub fn new(pitch: Arc<Mutex<f32>>, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f32;
+        let ring_len = ((RING_BUFFER_MS / 1000.0) * sample_rate).round().max(4.0) as usize;
+
+        Self {
+            input_gain: SmoothedGain::new(GAIN_SMOOTHING_MS, sample_rate),
+            output_gain: SmoothedGain::new(GAIN_SMOOTHING_MS, sample_rate),
+            compressor: Compressor::new(&CompressorSettings::default()),
+            oversampler: Oversampler::new(OversamplingSettings::default()),
+            dry_delay: vec![0.0; MAX_LATENCY_SAMPLES + 1],
+            dry_delay_index: 0,
+            pitch,
+            ring_buffer: vec![0.0; ring_len + 3], // smaller buffers for lower latency
+            ring_len,
+            write_index: 0,
+            read_index_a: 0.0,
+            read_index_b: (ring_len * 4) as f32, // smaller offset for lower latency
+            crossfade_pos: 0.0,
+            crossfade_step: 2.0 * PI * CROSSFADE_HZ / sample_rate, // faster crossfade for lower latency
+            filter_state_1: 0.0,
+            filter_state_2: 0.0,
+            filter_coeff_1: one_pole_coefficient(FILTER_STAGE_1_HZ, sample_rate),
+            filter_coeff_2: one_pole_coefficient(FILTER_STAGE_2_HZ, sample_rate),
+            dc_filter_x: 0.0,
+            dc_filter_y: 0.0,
+            dc_filter_pole: 1.0 - one_pole_coefficient(DC_BLOCK_HZ, sample_rate),
+            current_pitch: 1.0,
+            target_pitch: 1.0,
+            pitch_smoothing: smoothing_coefficient(PITCH_SMOOTHING_MS, sample_rate),
+            deesser: DeEsser::new(sample_rate),
+            eq: Equalizer::new(sample_rate),
+            character: CharacterStage::new(sample_rate),
+            vocoder: Vocoder::new(sample_rate),
+            harmonizer: Harmonizer::new(sample_rate),
+            echo: Echo::new(sample_rate),
+            reverb: Reverb::new(sample_rate),
+            agc: Agc::new(sample_rate),
+            effects: EffectSettings::default(),
+        }
+    }
+
+    /// input trim and output volume in dB, muting fades the output to silence
+    pub fn set_levels(&mut self, input_gain_db: f32, output_volume_db: f32, muted: bool) {
+        self.input_gain.set_db(input_gain_db);
+        if muted {
+            self.output_gain.set_linear(0.0);
+        } else {
+            self.output_gain.set_db(output_volume_db);
+        }
+    }
+
+    /// clear every buffer and filter in the chain and silence the output,
+    /// used by the panic button to get rid of runaway feedback or noise at once
+    pub fn reset(&mut self) {
+        self.ring_buffer.fill(0.0);
+        self.write_index = 0;
+        self.read_index_a = 0.0;
+        self.read_index_b = (self.ring_len * 4) as f32;
+        self.crossfade_pos = 0.0;
+        self.filter_state_1 = 0.0;
+        self.filter_state_2 = 0.0;
+        self.dc_filter_x = 0.0;
+        self.dc_filter_y = 0.0;
+        self.current_pitch = self.target_pitch;
+        self.oversampler.reset();
+        self.dry_delay.fill(0.0);
+
+        self.deesser.reset();
+        self.eq.reset();
+        self.character.reset();
+        self.vocoder.reset();
+        self.harmonizer.reset();
+        self.echo.reset();
+        self.reverb.reset();
+        self.agc.reset();
+        self.output_gain.silence();
+    }
+
+    /// update the effect stages from the latest settings
+    pub fn set_effects(&mut self, effects: &EffectSettings) {
+        self.deesser.set_settings(&effects.deesser);
+        self.eq.set_settings(&effects.eq);
+        self.character.set_character(effects.character);
+        self.character.set_oversampling(&effects.oversampling);
+        if effects.compressor != self.effects.compressor {
+            self.compressor = Compressor::new(&effects.compressor);
+        }
+        self.oversampler.set_settings(&effects.oversampling);
+        self.vocoder.set_settings(&effects.vocoder);
+        self.harmonizer.set_settings(&effects.harmonizer);
+        self.echo.set_settings(&effects.echo);
+        self.reverb.set_settings(&effects.reverb);
+        self.agc.set_settings(&effects.agc);
+        self.effects = *effects;
+    }
+
+    /// process a mono buffer through the whole chain, ending in the stereo effects
+    pub fn process_stereo(&mut self, input: &[f32], left: &mut [f32], right: &mut [f32]) {
+        self.process(input, left);
+
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let mut voice = *l;
+            if self.effects.deesser.enabled {
+                voice = self.deesser.process(voice);
+            }
+            if self.effects.eq.enabled {
+                voice = self.eq.process(voice);
+            }
+            voice = self.character.process(voice);
+            if self.effects.vocoder.enabled {
+                voice = self.vocoder.process(voice);
+            }
+            let (mut out_l, mut out_r) = if self.effects.harmonizer.enabled {
+                self.harmonizer.process(voice)
+            } else {
+                (voice, voice)
+            };
+
+            if self.effects.echo.enabled {
+                (out_l, out_r) = self.echo.process(out_l, out_r);
+            }
+
+            if self.effects.reverb.enabled {
+                let (wet_l, wet_r) = self.reverb.process((out_l + out_r) * 0.5);
+                out_l += wet_l;
+                out_r += wet_r;
+            }
+
+            // always measured so the loudness can be shown, only levels when enabled
+            (out_l, out_r) = self.agc.process(out_l, out_r);
+
+            let volume = self.output_gain.next_gain();
+            *l = out_l * volume;
+            *r = out_r * volume;
+        }
+    }
+
+    /// short-term loudness of the final mix before the AGC, in LUFS
+    pub fn loudness(&self) -> Option<f32> {
+        self.agc.loudness()
+    }
+
+    pub fn agc_gain_db(&self) -> f32 {
+        self.agc.gain_db()
+    }
+
+    /// process audio buffer (mono, f32) with optimized quality
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        // safely get pitch value with error handling
+        self.target_pitch = match self.pitch.lock() {
+            // more conservative pitch range
+            Ok(pitch) => (*pitch * self.character.character().pitch_factor()).clamp(0.5, 2.0),
+            Err(_) => {
+                eprintln!("Failed to lock pitch mutex, using default value");
+                1.0
+            }
+        };
+
+        // ensure we don't process more samples than available
+        let process_len = input.len().min(output.len());
+        let mut gains = [0.0; BLOCK_SIZE];
+        let mut weights = [0.0; BLOCK_SIZE];
+        let mut dry = [0.0; BLOCK_SIZE];
+        let compressor = self.compressor;
+
+        for (input, output) in input[..process_len]
+            .chunks(BLOCK_SIZE)
+            .zip(output[..process_len].chunks_mut(BLOCK_SIZE))
+        {
+            let len = input.len();
+
+            // the gain and crossfade don't depend on the signal, work them out for the whole block first
+            for gain in &mut gains[..len] {
+                *gain = self.input_gain.next_gain();
+            }
+            self.crossfade_weights(&mut weights[..len]);
+
+            // the read heads can catch up with the write head, so the shifter goes sample by sample
+            for i in 0..len {
+                let in_sample = input[i] * gains[i];
+
+                // faster pitch smoothing for lower latency
+                self.current_pitch += (self.target_pitch - self.current_pitch) * self.pitch_smoothing;
+
+                // apply multi-stage low-pass filtering for better anti-aliasing
+                let filtered_input = self.multi_stage_filter(in_sample);
+                self.write_ring(filtered_input);
+
+                // calculate read step based on current pitch
+                let read_step = 1.0 / self.current_pitch;
+
+                // update read indices
+                self.read_index_a += read_step;
+                self.read_index_b += read_step;
+
+                // wrap read indices
+                let ring_len = self.ring_len as f32;
+                if self.read_index_a >= ring_len {
+                    self.read_index_a -= ring_len;
+                }
+                if self.read_index_b >= ring_len {
+                    self.read_index_b -= ring_len;
+                }
+
+                // read samples with cubic interpolation for smoother sound
+                let sample_a = self.cubic_interpolated_read(self.read_index_a);
+                let sample_b = self.cubic_interpolated_read(self.read_index_b);
+
+                // crossfade between the two read heads for smoother transitions
+                let crossfaded_sample = sample_a * (1.0 - weights[i]) + sample_b * weights[i];
+
+                // apply DC blocking filter to remove DC offset
+                let dc_blocked = self.dc_blocking_filter(crossfaded_sample);
+
+                // apply gentle compression with softer knee
+                output[i] = self.oversampler.process(dc_blocked, |x| compressor.process(x));
+                dry[i] = self.delay_dry(filtered_input);
+            }
+
+            // mix with dry signal for more natural sound
+            for (sample, dry) in output.iter_mut().zip(&dry[..len]) {
+                *sample = *sample * DRY_WET_MIX + dry * (1.0 - DRY_WET_MIX);
+            }
+        }
+
+        // fill remaining output with silence if output is longer than input
+        for sample in output[process_len..].iter_mut() {
+            *sample = 0.0;
+        }
+    }
+    
+    /// current distance between the ring buffer write head and the crossfaded read heads
+    /// plus the delay of the oversampling filters, in samples
+    pub fn latency_samples(&self) -> f32 {
+        let len = self.ring_len as f32;
+        // the newest sample sits one behind the write head. read_index_b starts outside
+        // the ring and is only folded back one wrap per sample
+        let newest = self.write_index as f32 - 1.0;
+        let lag_a = (newest - self.read_index_a).rem_euclid(len);
+        let lag_b = (newest - self.read_index_b).rem_euclid(len);
+        let crossfade_weight = (self.crossfade_pos.sin() + 1.0) * 0.5;
+        lag_a * (1.0 - crossfade_weight) + lag_b * crossfade_weight + self.oversampling_latency_samples() as f32
+    }
+
+    /// delay at unity pitch, where the read heads keep pace with the write head one ring
+    /// behind it, plus the oversampling filters. away from unity the heads sweep through
+    /// the ring and the delay wanders below this, so it is the figure to report to hosts
+    pub fn base_latency_samples(&self) -> usize {
+        self.ring_len - 1 + self.oversampling_latency_samples()
+    }
+
+    /// the compressor's oversampler, and the character's when it uses one
+    fn oversampling_latency_samples(&self) -> usize {
+        self.oversampler.latency_samples() + self.character.latency_samples()
+    }
+
+    /// multi-stage low-pass filter for better anti-aliasing
+    fn multi_stage_filter(&mut self, input: f32) -> f32 {
+        // first stage - aggressive filtering
+        self.filter_state_1 += (input - self.filter_state_1) * self.filter_coeff_1;
+        // second stage - gentler filtering
+        self.filter_state_2 += (self.filter_state_1 - self.filter_state_2) * self.filter_coeff_2;
+        self.filter_state_2
+    }
+    
+    /// sine crossfade weights for the next samples, from a rotating phasor
+    /// restarted at the exact phase every block instead of a sin() per sample
+    fn crossfade_weights(&mut self, weights: &mut [f32]) {
+        let (mut sin, mut cos) = self.crossfade_pos.sin_cos();
+        let (step_sin, step_cos) = self.crossfade_step.sin_cos();
+        for weight in weights {
+            *weight = (sin + 1.0) * 0.5;
+            (sin, cos) = (sin * step_cos + cos * step_sin, cos * step_cos - sin * step_sin);
+
+            self.crossfade_pos += self.crossfade_step;
+            if self.crossfade_pos >= PI * 2.0 {
+                self.crossfade_pos -= PI * 2.0;
+            }
+        }
+    }
+
+    /// store the next sample and keep the copies around the ring up to date
+    fn write_ring(&mut self, sample: f32) {
+        let len = self.ring_len;
+        self.ring_buffer[self.write_index + 1] = sample;
+        if self.write_index == len - 1 {
+            self.ring_buffer[0] = sample;
+        }
+        if self.write_index < 2 {
+            self.ring_buffer[len + 1 + self.write_index] = sample;
+        }
+        self.write_index += 1;
+        if self.write_index == len {
+            self.write_index = 0;
+        }
+    }
+
+    /// cubic interpolation for smoother sample reading
+    fn cubic_interpolated_read(&self, read_index: f32) -> f32 {
+        let mut index = read_index as usize;
+        let fraction = read_index.fract();
+        // only the second head's starting offset is ever more than one ring ahead
+        if index >= self.ring_len {
+            index %= self.ring_len;
+        }
+
+        // the 4 points around the read position, one before it to two after
+        let [y0, y1, y2, y3] = self.ring_buffer[index..index + 4] else {
+            unreachable!()
+        };
+        
+        // cubic interpolation (Catmull-Rom spline)
+        let a = -0.5 * y0 + 1.5 * y1 - 1.5 * y2 + 0.5 * y3;
+        let b = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+        let c = -0.5 * y0 + 0.5 * y2;
+        let d = y1;
+        
+        ((a * fraction + b) * fraction + c) * fraction + d
+    }
+    
+    /// dry signal delayed to line up with the oversampled compressor
+    fn delay_dry(&mut self, input: f32) -> f32 {
+        let len = self.dry_delay.len();
+        self.dry_delay[self.dry_delay_index] = input;
+        let mut read_index = self.dry_delay_index + len - self.oversampler.latency_samples();
+        if read_index >= len {
+            read_index -= len;
+        }
+        self.dry_delay_index += 1;
+        if self.dry_delay_index == len {
+            self.dry_delay_index = 0;
+        }
+        self.dry_delay[read_index]
+    }
+
+    /// DC blocking filter to remove DC offset
+    fn dc_blocking_filter(&mut self, input: f32) -> f32 {
+        let output = input - self.dc_filter_x + self.dc_filter_pole * self.dc_filter_y;
+        self.dc_filter_x = input;
+        self.dc_filter_y = output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{amplitude, rising_crossings, rms, sine};
+
+    const RATES: [u32; 4] = [22050, 44100, 48000, 96000];
+
+    fn processor(sample_rate: u32, pitch: f32) -> DspProcessor {
+        DspProcessor::new(Arc::new(Mutex::new(pitch)), sample_rate)
+    }
+
+    /// rms gain of a settled sine through a fresh processor, in dB
+    fn sine_gain_db(freq: f32, sample_rate: u32) -> f32 {
+        let input = sine(freq, sample_rate, 0.5, 0.5);
+        let mut output = vec![0.0; input.len()];
+        processor(sample_rate, 1.0).process(&input, &mut output);
+        let settled = input.len() / 2;
+        20.0 * (rms(&output[settled..]) / rms(&input[settled..])).log10()
+    }
+
+    #[test]
+    fn coefficients_match_the_original_tuning_at_44100() {
+        let dsp = processor(44100, 1.0);
+        assert_eq!(dsp.ring_len, 256);
+        assert!((dsp.pitch_smoothing - 0.01).abs() < 1e-4);
+        assert!((dsp.crossfade_step - 0.005).abs() < 1e-4);
+        assert!((dsp.filter_coeff_1 - 0.15).abs() < 1e-3);
+        assert!((dsp.filter_coeff_2 - 0.1).abs() < 1e-3);
+        assert!((dsp.dc_filter_pole - 0.995).abs() < 1e-4);
+    }
+
+    #[test]
+    fn frequency_response_is_rate_independent() {
+        for freq in [100.0, 300.0, 1000.0, 2000.0] {
+            let reference = sine_gain_db(freq, 44100);
+            for rate in RATES {
+                let gain = sine_gain_db(freq, rate);
+                assert!(
+                    (gain - reference).abs() < 0.5,
+                    "{} Hz at {} Hz: {:.2} dB vs {:.2} dB at 44100 Hz",
+                    freq, rate, gain, reference
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pitch_glide_time_is_rate_independent() {
+        for rate in RATES {
+            let mut dsp = processor(rate, 2.0);
+            let input = vec![0.0; (rate as f32 * PITCH_SMOOTHING_MS / 1000.0) as usize];
+            let mut output = vec![0.0; input.len()];
+            dsp.process(&input, &mut output);
+            // one time constant covers 63% of the glide
+            let progress = dsp.current_pitch - 1.0;
+            assert!((progress - 0.632).abs() < 0.01, "{} Hz: {:.3}", rate, progress);
+        }
+    }
+
+    #[test]
+    fn dc_decay_time_is_rate_independent() {
+        for rate in RATES {
+            let mut dsp = processor(rate, 1.0);
+            let input = vec![0.5; (rate as f32 * 0.1) as usize];
+            let mut output = vec![0.0; input.len()];
+            dsp.process(&input, &mut output);
+            // the blocked dc only leaks through the 20% dry path after 100ms
+            let tail = output[output.len() - 1];
+            assert!((tail - 0.1).abs() < 0.01, "{} Hz: {:.4}", rate, tail);
+        }
+    }
+
+    #[test]
+    fn ring_buffer_covers_the_same_time_at_every_rate() {
+        for rate in RATES {
+            let dsp = processor(rate, 1.0);
+            let ms = dsp.ring_len as f32 * 1000.0 / rate as f32;
+            assert!((ms - RING_BUFFER_MS).abs() < 0.1, "{} Hz: {:.2} ms", rate, ms);
+        }
+    }
+
+    #[test]
+    fn block_processing_matches_sample_by_sample() {
+        // one sample per call restarts the crossfade phasor every sample, which makes it an exact sin()
+        for pitch in [0.7, 1.0, 1.6] {
+            let input: Vec<f32> = sine(220.0, 48000, 0.25, 0.5)
+                .iter()
+                .zip(sine(3100.0, 48000, 0.25, 0.5))
+                .map(|(a, b)| a + b * 0.5)
+                .collect();
+
+            let mut exact = processor(48000, pitch);
+            let mut expected = vec![0.0; input.len()];
+            for (x, y) in input.iter().zip(expected.iter_mut()) {
+                exact.process(std::slice::from_ref(x), std::slice::from_mut(y));
+            }
+
+            for buffer_size in [37, 512, 4096] {
+                let mut dsp = processor(48000, pitch);
+                let mut output = vec![0.0; input.len()];
+                for (input, output) in input.chunks(buffer_size).zip(output.chunks_mut(buffer_size)) {
+                    dsp.process(input, output);
+                }
+                let error = output.iter().zip(&expected).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+                assert!(error < 1e-4, "pitch {} in {} sample buffers: error {:.6}", pitch, buffer_size, error);
+            }
+        }
+    }
+
+    /// the whole chain with every stage switched on, as heavy as the app or the plugin allow
+    fn all_effects() -> EffectSettings {
+        let mut effects = EffectSettings::default();
+        effects.deesser.enabled = true;
+        effects.eq.enabled = true;
+        effects.character = VoiceCharacter::Radio;
+        effects.vocoder.enabled = true;
+        effects.harmonizer.enabled = true;
+        effects.echo.enabled = true;
+        effects.reverb.enabled = true;
+        effects.agc.enabled = true;
+        effects
+    }
+
+    #[test]
+    fn unity_pitch_is_transparent() {
+        // the dry blend makes the level depend on frequency, but at unity pitch the
+        // crossfading heads must not move the tone, modulate it or add harmonics
+        let input = sine(200.0, 48000, 1.0, 0.5);
+        let mut output = vec![0.0; input.len()];
+        processor(48000, 1.0).process(&input, &mut output);
+        let settled = &output[24000..];
+
+        let (expected, actual) = (rising_crossings(&input[24000..]), rising_crossings(settled));
+        assert!(expected.abs_diff(actual) <= 1, "{} periods in, {} out", expected, actual);
+
+        let levels: Vec<f32> = settled.chunks(2400).map(rms).collect();
+        let (quietest, loudest) = levels.iter().fold((f32::MAX, 0.0_f32), |(lo, hi), &l| (lo.min(l), hi.max(l)));
+        assert!(20.0 * (loudest / quietest).log10() < 0.05, "level wobbles between {} and {}", quietest, loudest);
+
+        let fundamental = amplitude(settled, 200.0, 48000);
+        for harmonic in 2..6 {
+            let level_db = 20.0 * (amplitude(settled, 200.0 * harmonic as f32, 48000) / fundamental).log10();
+            assert!(level_db < -80.0, "harmonic {} at {:.1} dB", harmonic, level_db);
+        }
+    }
+
+    #[test]
+    fn impulse_comes_out_after_the_reported_latency() {
+        for rate in RATES {
+            let mut dsp = processor(rate, 1.0);
+            let mut input = vec![0.0; rate as usize / 10];
+            input[0] = 1.0;
+            let mut output = vec![0.0; input.len()];
+            dsp.process(&input, &mut output);
+
+            let latency = dsp.base_latency_samples();
+            assert_eq!(dsp.latency_samples(), latency as f32, "{} Hz", rate);
+            // the wet path peaks a few samples late, smeared by the anti-aliasing filters
+            let peak = (1..output.len()).max_by(|&a, &b| output[a].abs().total_cmp(&output[b].abs())).unwrap();
+            assert!(peak >= latency && peak - latency < rate as usize / 4000, "{} Hz: {} vs {}", rate, peak, latency);
+        }
+    }
+
+    #[test]
+    fn radio_character_adds_its_oversampling_to_the_latency() {
+        let mut dsp = processor(48000, 1.0);
+        let natural = dsp.base_latency_samples();
+        dsp.set_effects(&EffectSettings { character: VoiceCharacter::Radio, ..EffectSettings::default() });
+        assert!(dsp.base_latency_samples() > natural);
+        assert_eq!(dsp.latency_samples(), dsp.base_latency_samples() as f32);
+    }
+
+    #[test]
+    fn dc_blocker_removes_a_constant_offset() {
+        let mut dsp = processor(48000, 1.0);
+        let mut output = 0.0;
+        for _ in 0..48000 {
+            output = dsp.dc_blocking_filter(0.5);
+        }
+        assert!(output.abs() < 1e-4, "{:.6}", output);
+    }
+
+    #[test]
+    fn silence_after_signal_has_no_nan_or_denormals() {
+        let mut dsp = processor(48000, 1.3);
+        dsp.set_effects(&all_effects());
+        let mut input = sine(440.0, 48000, 0.25, 0.5);
+        input.iter_mut().for_each(|x| *x *= 1.8);
+        input.resize(48000 * 4, 0.0);
+
+        let mut left = vec![0.0; input.len()];
+        let mut right = vec![0.0; input.len()];
+        for ((input, left), right) in input.chunks(256).zip(left.chunks_mut(256)).zip(right.chunks_mut(256)) {
+            dsp.process_stereo(input, left, right);
+        }
+        for (i, sample) in left.iter().chain(&right).enumerate() {
+            assert!(sample.is_finite(), "sample {} is {}", i, sample);
+            assert!(*sample == 0.0 || sample.is_normal(), "sample {} is denormal: {:e}", i, sample);
+        }
+    }
+
+    #[test]
+    fn anti_aliasing_filters_pass_the_voice_and_roll_off_above() {
+        let response_db = |freq: f32| {
+            let mut dsp = processor(48000, 1.0);
+            let input = sine(freq, 48000, 0.2, 0.5);
+            let output: Vec<f32> = input.iter().map(|&x| dsp.multi_stage_filter(x)).collect();
+            let settled = input.len() / 2;
+            20.0 * (rms(&output[settled..]) / rms(&input[settled..])).log10()
+        };
+
+        assert!(response_db(100.0) > -0.5, "{:.2} dB at 100 Hz", response_db(100.0));
+        assert!(response_db(1000.0) < -3.0, "{:.2} dB at 1 kHz", response_db(1000.0));
+        // two one-pole stages fall by 12dB per octave well above both corners
+        let (high, higher) = (response_db(8000.0), response_db(16000.0));
+        assert!(high < -18.0, "{:.2} dB at 8 kHz", high);
+        assert!(higher < high - 9.0, "{:.2} dB at 16 kHz vs {:.2} dB at 8 kHz", higher, high);
+    }
+}
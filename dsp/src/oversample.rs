@@ -0,0 +1,331 @@
+use std::f32::consts::PI;
+
+/// the buffers are sized once for the largest factor and the longest filters,
+/// so changing the settings never allocates on the audio thread
+const MAX_FACTOR: usize = 8;
+const MAX_TAPS_PER_PHASE: usize = 64;
+const MAX_KERNEL_LEN: usize = (MAX_TAPS_PER_PHASE - 1) * MAX_FACTOR + 1;
+
+/// longest delay the filters can add, in base rate samples
+pub const MAX_LATENCY_SAMPLES: usize = MAX_TAPS_PER_PHASE - 1;
+
+/// how many times faster than the base rate the nonlinear stages run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Oversampling {
+    Off,
+    #[default]
+    X2,
+    X4,
+    X8,
+}
+
+impl Oversampling {
+    pub const ALL: [Oversampling; 4] = [Oversampling::Off, Oversampling::X2, Oversampling::X4, Oversampling::X8];
+
+    pub fn factor(self) -> usize {
+        match self {
+            Oversampling::Off => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+        }
+    }
+}
+
+impl std::fmt::Display for Oversampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Oversampling::Off => write!(f, "Off"),
+            factor => write!(f, "{}x", factor.factor()),
+        }
+    }
+}
+
+/// length of the interpolation/decimation filters, longer filters reject more
+/// of the aliasing but cost more cpu and add latency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OversamplingQuality {
+    Low,
+    #[default]
+    Standard,
+    High,
+}
+
+impl OversamplingQuality {
+    pub const ALL: [OversamplingQuality; 3] =
+        [OversamplingQuality::Low, OversamplingQuality::Standard, OversamplingQuality::High];
+
+    /// filter taps per polyphase branch, the latency is one sample less at the base rate
+    fn taps_per_phase(self) -> usize {
+        match self {
+            OversamplingQuality::Low => 16,
+            OversamplingQuality::Standard => 32,
+            OversamplingQuality::High => 64,
+        }
+    }
+}
+
+impl std::fmt::Display for OversamplingQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OversamplingQuality::Low => "Low",
+            OversamplingQuality::Standard => "Standard",
+            OversamplingQuality::High => "High",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct OversamplingSettings {
+    pub factor: Oversampling,
+    pub quality: OversamplingQuality,
+}
+
+/// runs a memoryless nonlinearity at a multiple of the base rate so the harmonics
+/// it creates above nyquist are filtered out instead of folding back as aliasing.
+/// upsampling uses a polyphase windowed-sinc interpolator, the same filter
+/// band-limits the shaped signal again before it is decimated
+pub struct Oversampler {
+    settings: OversamplingSettings,
+    factor: usize,
+    taps: usize,
+    /// length of the decimation filter in use
+    len: usize,
+    /// interpolation filter split into one branch of `taps` per output phase, reversed
+    /// for the dot product
+    phases: Vec<f32>,
+    /// decimation filter, symmetric so it doesn't need reversing
+    kernel: Vec<f32>,
+    /// base rate input, stored twice so the newest `taps` samples are always contiguous
+    up_history: Vec<f32>,
+    up_index: usize,
+    /// oversampled shaped signal, stored the same way
+    down_history: Vec<f32>,
+    down_index: usize,
+}
+
+impl Oversampler {
+    pub fn new(settings: OversamplingSettings) -> Self {
+        let mut oversampler = Self {
+            settings,
+            factor: 1,
+            taps: 0,
+            len: 0,
+            phases: vec![0.0; MAX_FACTOR * MAX_TAPS_PER_PHASE],
+            kernel: vec![0.0; MAX_KERNEL_LEN],
+            up_history: vec![0.0; MAX_TAPS_PER_PHASE * 2],
+            up_index: 0,
+            down_history: vec![0.0; MAX_KERNEL_LEN * 2],
+            down_index: 0,
+        };
+        oversampler.configure();
+        oversampler
+    }
+
+    pub fn set_settings(&mut self, settings: &OversamplingSettings) {
+        if *settings != self.settings {
+            self.settings = *settings;
+            self.configure();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.up_history.fill(0.0);
+        self.down_history.fill(0.0);
+    }
+
+    /// added delay in base rate samples
+    pub fn latency_samples(&self) -> usize {
+        if self.factor == 1 { 0 } else { self.taps - 1 }
+    }
+
+    fn configure(&mut self) {
+        self.factor = self.settings.factor.factor();
+        self.reset();
+        self.up_index = 0;
+        self.down_index = 0;
+        if self.factor == 1 {
+            return;
+        }
+
+        // odd length so the filter delay is a whole number of oversampled samples,
+        // together the two filters then delay by exactly `taps - 1` base samples
+        let factor = self.factor;
+        let per_phase = self.settings.quality.taps_per_phase();
+        let len = (per_phase - 1) * factor + 1;
+        let kernel = &mut self.kernel[..len];
+        lowpass_kernel(kernel, 0.5 / factor as f32);
+
+        // padded to a whole number of taps per branch
+        self.taps = per_phase;
+        self.len = len;
+        for (phase, branch) in self.phases.chunks_exact_mut(per_phase).take(factor).enumerate() {
+            for (tap, k) in branch.iter_mut().zip((0..per_phase).rev()) {
+                *tap = kernel.get(phase + k * factor).copied().unwrap_or(0.0) * factor as f32;
+            }
+        }
+    }
+
+    /// feed one base rate sample through `shaper` at the oversampled rate
+    pub fn process(&mut self, input: f32, mut shaper: impl FnMut(f32) -> f32) -> f32 {
+        if self.factor == 1 {
+            return shaper(input);
+        }
+
+        let taps = self.taps;
+        self.up_history[self.up_index] = input;
+        self.up_history[self.up_index + taps] = input;
+        self.up_index = (self.up_index + 1) % taps;
+        let recent = &self.up_history[self.up_index..self.up_index + taps];
+
+        let len = self.len;
+        let mut output = 0.0;
+        for (i, phase) in self.phases.chunks_exact(taps).take(self.factor).enumerate() {
+            let upsampled = dot(phase, recent);
+            self.down_history[self.down_index] = shaper(upsampled);
+            self.down_history[self.down_index + len] = self.down_history[self.down_index];
+            self.down_index = (self.down_index + 1) % len;
+
+            // only every `factor`th output of the decimation filter is needed, taking it
+            // on the first phase keeps the total delay a whole number of base samples
+            if i == 0 {
+                output = dot(&self.kernel[..len], &self.down_history[self.down_index..self.down_index + len]);
+            }
+        }
+        output
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// blackman windowed sinc low-pass with unity dc gain filling `kernel`, cutoff relative
+/// to the sample rate
+fn lowpass_kernel(kernel: &mut [f32], cutoff: f32) {
+    let len = kernel.len();
+    let centre = (len - 1) as f32 / 2.0;
+    for (i, tap) in kernel.iter_mut().enumerate() {
+        let x = i as f32 - centre;
+        let sinc = if x == 0.0 { 1.0 } else { (2.0 * PI * cutoff * x).sin() / (PI * x) / (2.0 * cutoff) };
+        let phase = 2.0 * PI * i as f32 / (len - 1) as f32;
+        let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+        *tap = sinc * window;
+    }
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|tap| *tap /= sum);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::saturate;
+    use crate::test_signals::{amplitude, sine};
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    /// 10Hz bins, every frequency below lands exactly on one
+    const WINDOW: usize = 4800;
+    const WINDOW_SECONDS: f32 = WINDOW as f32 / SAMPLE_RATE;
+    const FUNDAMENTAL_HZ: f32 = 7000.0;
+
+    fn settings(factor: Oversampling, quality: OversamplingQuality) -> OversamplingSettings {
+        OversamplingSettings { factor, quality }
+    }
+
+    /// level of the folded back harmonics of a hard driven sine, relative to the fundamental in dB
+    fn aliasing_db(settings: OversamplingSettings) -> f32 {
+        let mut oversampler = Oversampler::new(settings);
+        let output: Vec<f32> = sine(FUNDAMENTAL_HZ, SAMPLE_RATE as u32, WINDOW_SECONDS * 2.0, 0.9)
+            .into_iter()
+            .map(|x| oversampler.process(x, |x| saturate(x, 8.0)))
+            .collect();
+        let settled = &output[WINDOW..];
+
+        // odd harmonics from the 5th up fold back below nyquist
+        let nyquist = SAMPLE_RATE / 2.0;
+        let aliases: f32 = (2..40)
+            .map(|n| FUNDAMENTAL_HZ * (2 * n + 1) as f32)
+            .map(|harmonic| {
+                let folded = harmonic % SAMPLE_RATE;
+                if folded > nyquist { SAMPLE_RATE - folded } else { folded }
+            })
+            .filter(|&folded| folded % FUNDAMENTAL_HZ != 0.0)
+            .map(|folded| amplitude(settled, folded, SAMPLE_RATE as u32).powi(2))
+            .sum();
+        10.0 * (aliases / amplitude(settled, FUNDAMENTAL_HZ, SAMPLE_RATE as u32).powi(2)).log10()
+    }
+
+    #[test]
+    fn oversampling_reduces_aliasing() {
+        let off = aliasing_db(settings(Oversampling::Off, OversamplingQuality::Standard));
+        let x2 = aliasing_db(settings(Oversampling::X2, OversamplingQuality::Standard));
+        let x4 = aliasing_db(settings(Oversampling::X4, OversamplingQuality::Standard));
+        let x8 = aliasing_db(settings(Oversampling::X8, OversamplingQuality::Standard));
+
+        assert!(off > -30.0, "the test signal should alias badly without oversampling: {:.1} dB", off);
+        assert!(x2 < off - 6.0, "2x: {:.1} dB vs {:.1} dB", x2, off);
+        assert!(x4 < x2 - 6.0, "4x: {:.1} dB vs {:.1} dB", x4, x2);
+        assert!(x8 < x4 - 6.0, "8x: {:.1} dB vs {:.1} dB", x8, x4);
+    }
+
+    #[test]
+    fn higher_quality_rejects_more_aliasing() {
+        // a cubic only adds the 3rd harmonic, 27kHz sits just above nyquist and folds to 21kHz,
+        // so what is left of it depends on how steep the filters are
+        let folded_third_db = |quality| {
+            let mut oversampler = Oversampler::new(settings(Oversampling::X2, quality));
+            let output: Vec<f32> = sine(9000.0, SAMPLE_RATE as u32, WINDOW_SECONDS * 2.0, 0.9)
+                .into_iter()
+                .map(|x| oversampler.process(x, |x| x - x * x * x / 3.0))
+                .collect();
+            let settled = &output[WINDOW..];
+            20.0 * (amplitude(settled, 21000.0, SAMPLE_RATE as u32) / amplitude(settled, 9000.0, SAMPLE_RATE as u32)).log10()
+        };
+
+        let low = folded_third_db(OversamplingQuality::Low);
+        let standard = folded_third_db(OversamplingQuality::Standard);
+        let high = folded_third_db(OversamplingQuality::High);
+        assert!(standard < low - 6.0, "standard {:.1} dB vs low {:.1} dB", standard, low);
+        assert!(high < standard - 6.0, "high {:.1} dB vs standard {:.1} dB", high, standard);
+    }
+
+    #[test]
+    fn linear_signal_passes_unchanged_after_the_latency() {
+        for factor in Oversampling::ALL {
+            for quality in OversamplingQuality::ALL {
+                let mut oversampler = Oversampler::new(settings(factor, quality));
+                let latency = oversampler.latency_samples();
+                let input = sine(1000.0, SAMPLE_RATE as u32, 2000.0 / SAMPLE_RATE, 0.5);
+                let output: Vec<f32> = input.iter().map(|&x| oversampler.process(x, |x| x)).collect();
+
+                let error = input[500..1500]
+                    .iter()
+                    .zip(&output[500 + latency..1500 + latency])
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f32::max);
+                assert!(error < 1e-3, "{} {}: error {:.5}", factor, quality, error);
+            }
+        }
+    }
+
+    #[test]
+    fn changing_the_settings_reuses_the_buffers() {
+        let mut oversampler = Oversampler::new(settings(Oversampling::X2, OversamplingQuality::Low));
+        let buffers = |o: &Oversampler| {
+            [o.phases.as_ptr(), o.kernel.as_ptr(), o.up_history.as_ptr(), o.down_history.as_ptr()]
+        };
+        let before = buffers(&oversampler);
+        for factor in Oversampling::ALL {
+            for quality in OversamplingQuality::ALL {
+                oversampler.set_settings(&settings(factor, quality));
+                oversampler.process(0.5, |x| x);
+                assert_eq!(buffers(&oversampler), before, "{} {}", factor, quality);
+            }
+        }
+    }
+}
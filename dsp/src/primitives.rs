@@ -0,0 +1,121 @@
+use super::smoothing_coefficient;
+use std::f32::consts::PI;
+
+/// multiplies the signal with a sine carrier, the classic metallic robot voice
+pub struct RingModulator {
+    phase: f32,
+    step: f32,
+}
+
+impl RingModulator {
+    pub fn new(freq: f32, sample_rate: f32) -> Self {
+        Self {
+            phase: 0.0,
+            step: 2.0 * PI * freq / sample_rate,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = input * self.phase.sin();
+        self.phase += self.step;
+        if self.phase >= PI * 2.0 {
+            self.phase -= PI * 2.0;
+        }
+        output
+    }
+}
+
+/// reduces the bit depth and holds samples to fake a lower sample rate
+pub struct BitCrusher {
+    levels: f32,
+    hold_samples: f32,
+    counter: f32,
+    held: f32,
+}
+
+impl BitCrusher {
+    pub fn new(bits: u32, target_rate: f32, sample_rate: f32) -> Self {
+        Self {
+            levels: 2.0_f32.powi(bits as i32 - 1),
+            hold_samples: (sample_rate / target_rate).max(1.0),
+            counter: 0.0,
+            held: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.counter = 0.0;
+        self.held = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.counter -= 1.0;
+        if self.counter <= 0.0 {
+            self.counter += self.hold_samples;
+            self.held = (input * self.levels).round() / self.levels;
+        }
+        self.held
+    }
+}
+
+/// cheap xorshift white noise in -1..1
+pub struct Noise {
+    state: u32,
+}
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+/// tanh waveshaper normalised so full scale input stays at full scale
+pub fn saturate(input: f32, drive: f32) -> f32 {
+    (input * drive).tanh() / drive.tanh()
+}
+
+/// gain that glides towards its target instead of jumping, avoids zipper noise on level changes
+pub struct SmoothedGain {
+    current: f32,
+    target: f32,
+    coefficient: f32,
+}
+
+impl SmoothedGain {
+    pub fn new(time_ms: f32, sample_rate: f32) -> Self {
+        Self {
+            current: 1.0,
+            target: 1.0,
+            coefficient: smoothing_coefficient(time_ms, sample_rate),
+        }
+    }
+
+    pub fn set_db(&mut self, gain_db: f32) {
+        self.target = 10.0_f32.powf(gain_db / 20.0);
+    }
+
+    pub fn set_linear(&mut self, gain: f32) {
+        self.target = gain;
+    }
+
+    /// drop to silence at once, the next samples fade back in towards the target
+    pub fn silence(&mut self) {
+        self.current = 0.0;
+    }
+
+    pub fn next_gain(&mut self) -> f32 {
+        self.current += (self.target - self.current) * self.coefficient;
+        self.current
+    }
+}
@@ -0,0 +1,243 @@
+/// freeverb comb and allpass tunings, in samples at 44.1kHz
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+/// offset of the right channel delay lines for stereo spread
+const STEREO_SPREAD: usize = 23;
+const TUNING_RATE: f32 = 44100.0;
+/// fixed attenuation of the input going into the parallel combs
+const INPUT_GAIN: f32 = 0.015;
+/// makes up for the input attenuation so a wet level of 1 sits around the dry level
+const WET_SCALE: f32 = 3.0;
+pub const MAX_PRE_DELAY_MS: f32 = 200.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ReverbSettings {
+    pub enabled: bool,
+    /// 0..1, longer decay as it grows
+    pub room_size: f32,
+    /// 0..1, how fast high frequencies die out in the tail
+    pub damping: f32,
+    pub pre_delay_ms: f32,
+    /// 0..1, from mono to fully decorrelated left/right tails
+    pub width: f32,
+    /// 0..1, level of the reverb mixed on top of the dry voice
+    pub wet: f32,
+}
+
+impl Default for ReverbSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            room_size: 0.5,
+            damping: 0.5,
+            pre_delay_ms: 20.0,
+            width: 1.0,
+            wet: 0.25,
+        }
+    }
+}
+
+/// feedback comb filter with a one-pole low-pass in the loop
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filter_state: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            index: 0,
+            filter_state: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filter_state = output * (1.0 - damping) + self.filter_state * damping;
+        self.buffer[self.index] = input + self.filter_state * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.filter_state = 0.0;
+    }
+}
+
+/// schroeder allpass used to diffuse the comb output
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            index: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.buffer[self.index];
+        self.buffer[self.index] = input + buffered * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+        buffered - input
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+}
+
+/// one channel of the freeverb network
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+    fn new(sample_rate: f32, spread: usize) -> Self {
+        let scale = |len: usize| ((len + spread) as f32 * sample_rate / TUNING_RATE).round() as usize;
+        Self {
+            combs: COMB_TUNINGS.iter().map(|&len| Comb::new(scale(len))).collect(),
+            allpasses: ALLPASS_TUNINGS.iter().map(|&len| Allpass::new(scale(len))).collect(),
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let mut output = 0.0;
+        for comb in &mut self.combs {
+            output += comb.process(input, feedback, damping);
+        }
+        for allpass in &mut self.allpasses {
+            output = allpass.process(output);
+        }
+        output
+    }
+
+    fn clear(&mut self) {
+        self.combs.iter_mut().for_each(Comb::clear);
+        self.allpasses.iter_mut().for_each(Allpass::clear);
+    }
+}
+
+/// freeverb style room simulation with pre-delay, producing a stereo wet signal
+pub struct Reverb {
+    sample_rate: f32,
+    settings: ReverbSettings,
+    left: Tank,
+    right: Tank,
+    /// pre-delay line sized for the maximum pre-delay
+    pre_delay: Vec<f32>,
+    pre_delay_index: usize,
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f32) -> Self {
+        let pre_delay_len = (MAX_PRE_DELAY_MS / 1000.0 * sample_rate) as usize + 1;
+        Self {
+            sample_rate,
+            settings: ReverbSettings::default(),
+            left: Tank::new(sample_rate, 0),
+            right: Tank::new(sample_rate, STEREO_SPREAD),
+            pre_delay: vec![0.0; pre_delay_len],
+            pre_delay_index: 0,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: &ReverbSettings) {
+        // start from a silent tank instead of replaying an old tail
+        if settings.enabled && !self.settings.enabled {
+            self.reset();
+        }
+        self.settings = *settings;
+    }
+
+    pub fn reset(&mut self) {
+        self.left.clear();
+        self.right.clear();
+        self.pre_delay.fill(0.0);
+    }
+
+    /// feed one dry sample, returns the wet left/right pair
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let settings = &self.settings;
+        let feedback = 0.7 + settings.room_size.clamp(0.0, 1.0) * 0.28;
+        let damping = settings.damping.clamp(0.0, 1.0) * 0.4;
+
+        // pre-delay
+        let delay_samples = ((settings.pre_delay_ms.clamp(0.0, MAX_PRE_DELAY_MS) / 1000.0) * self.sample_rate) as usize;
+        let len = self.pre_delay.len();
+        self.pre_delay[self.pre_delay_index] = input;
+        let delayed = self.pre_delay[(self.pre_delay_index + len - delay_samples.min(len - 1)) % len];
+        self.pre_delay_index = (self.pre_delay_index + 1) % len;
+
+        let tank_input = delayed * INPUT_GAIN;
+        let out_left = self.left.process(tank_input, feedback, damping);
+        let out_right = self.right.process(tank_input, feedback, damping);
+
+        // mix the two tails according to width
+        let width = settings.width.clamp(0.0, 1.0);
+        let wet = settings.wet.clamp(0.0, 1.0) * WET_SCALE;
+        let wet1 = wet * (width * 0.5 + 0.5);
+        let wet2 = wet * ((1.0 - width) * 0.5);
+        (
+            out_left * wet1 + out_right * wet2,
+            out_right * wet1 + out_left * wet2,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn impulse_response(settings: ReverbSettings, seconds: f32) -> Vec<(f32, f32)> {
+        let mut reverb = Reverb::new(SAMPLE_RATE);
+        reverb.set_settings(&ReverbSettings { enabled: true, ..settings });
+        (0..(seconds * SAMPLE_RATE) as usize).map(|i| reverb.process(if i == 0 { 1.0 } else { 0.0 })).collect()
+    }
+
+    /// energy of both channels between two times in seconds
+    fn energy(response: &[(f32, f32)], from: f32, to: f32) -> f32 {
+        response[(from * SAMPLE_RATE) as usize..(to * SAMPLE_RATE) as usize]
+            .iter()
+            .map(|(left, right)| left * left + right * right)
+            .sum()
+    }
+
+    #[test]
+    fn tail_decays_and_grows_with_the_room_size() {
+        let small = impulse_response(ReverbSettings { room_size: 0.2, ..ReverbSettings::default() }, 2.0);
+        let large = impulse_response(ReverbSettings { room_size: 0.9, ..ReverbSettings::default() }, 2.0);
+        for response in [&small, &large] {
+            assert!(energy(response, 1.0, 1.5) < energy(response, 0.1, 0.6) / 10.0);
+        }
+        assert!(energy(&large, 1.0, 1.5) > energy(&small, 1.0, 1.5) * 100.0);
+    }
+
+    #[test]
+    fn pre_delay_shifts_the_onset() {
+        let onset = |pre_delay_ms| {
+            let response = impulse_response(ReverbSettings { pre_delay_ms, ..ReverbSettings::default() }, 0.5);
+            response.iter().position(|&(left, right)| left != 0.0 || right != 0.0).unwrap()
+        };
+        assert_eq!(onset(50.0) - onset(0.0), (0.05 * SAMPLE_RATE) as usize);
+    }
+
+    #[test]
+    fn zero_width_is_mono() {
+        let response = impulse_response(ReverbSettings { width: 0.0, ..ReverbSettings::default() }, 0.5);
+        assert!(response.iter().all(|(left, right)| left == right));
+        let wide = impulse_response(ReverbSettings::default(), 0.5);
+        assert!(wide.iter().any(|(left, right)| left != right));
+    }
+}
@@ -0,0 +1,31 @@
+//! signals and measurements for the tests of the stages here and of the engine around them
+
+use std::f64::consts::PI;
+
+/// phase worked out in f64, f32 loses enough precision over a second to add audible noise
+pub fn sine(freq: f32, sample_rate: u32, seconds: f32, amplitude: f32) -> Vec<f32> {
+    let len = (seconds * sample_rate as f32).round() as usize;
+    (0..len)
+        .map(|i| (2.0 * PI * freq as f64 * i as f64 / sample_rate as f64).sin() as f32 * amplitude)
+        .collect()
+}
+
+pub fn rms(signal: &[f32]) -> f32 {
+    (signal.iter().map(|x| x * x).sum::<f32>() / signal.len() as f32).sqrt()
+}
+
+/// zero crossings going up, counts whole periods of a steady tone
+pub fn rising_crossings(signal: &[f32]) -> usize {
+    signal.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count()
+}
+
+/// amplitude of one frequency, the signal should hold a whole number of its periods
+pub fn amplitude(signal: &[f32], freq: f32, sample_rate: u32) -> f32 {
+    let (mut re, mut im) = (0.0_f64, 0.0_f64);
+    for (i, &sample) in signal.iter().enumerate() {
+        let phase = 2.0 * PI * freq as f64 * i as f64 / sample_rate as f64;
+        re += sample as f64 * phase.cos();
+        im += sample as f64 * phase.sin();
+    }
+    ((re * re + im * im).sqrt() * 2.0 / signal.len() as f64) as f32
+}
@@ -0,0 +1,335 @@
+use super::biquad::Biquad;
+use super::primitives::Noise;
+use super::smoothing_coefficient;
+
+pub const MIN_BANDS: u32 = 4;
+pub const MAX_BANDS: u32 = 32;
+/// overlap between neighbouring bands, higher is narrower
+const BAND_Q_SCALE: f32 = 1.2;
+
+/// waveform of the internal carrier synth
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CarrierWave {
+    #[default]
+    Saw,
+    Pulse,
+    Noise,
+}
+
+impl CarrierWave {
+    pub const ALL: [CarrierWave; 3] = [CarrierWave::Saw, CarrierWave::Pulse, CarrierWave::Noise];
+
+    /// brings the summed bands back to roughly the level of the dry voice,
+    /// the carriers spread different amounts of energy into each band
+    fn makeup_gain(self) -> f32 {
+        match self {
+            CarrierWave::Saw => 11.0,
+            CarrierWave::Pulse => 6.0,
+            CarrierWave::Noise => 36.0,
+        }
+    }
+}
+
+impl std::fmt::Display for CarrierWave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CarrierWave::Saw => "Saw",
+            CarrierWave::Pulse => "Pulse",
+            CarrierWave::Noise => "Noise",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// notes played by the carrier synth, relative to the carrier pitch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Chord {
+    #[default]
+    Single,
+    Octaves,
+    Fifth,
+    Major,
+    Minor,
+}
+
+impl Chord {
+    pub const ALL: [Chord; 5] = [Chord::Single, Chord::Octaves, Chord::Fifth, Chord::Major, Chord::Minor];
+
+    /// intervals in semitones above the carrier note
+    pub fn intervals(self) -> &'static [f32] {
+        match self {
+            Chord::Single => &[0.0],
+            Chord::Octaves => &[0.0, 12.0],
+            Chord::Fifth => &[0.0, 7.0],
+            Chord::Major => &[0.0, 4.0, 7.0],
+            Chord::Minor => &[0.0, 3.0, 7.0],
+        }
+    }
+}
+
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Chord::Single => "Single note",
+            Chord::Octaves => "Octaves",
+            Chord::Fifth => "Fifth",
+            Chord::Major => "Major",
+            Chord::Minor => "Minor",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VocoderSettings {
+    pub enabled: bool,
+    pub bands: u32,
+    /// centre frequencies of the lowest and highest band
+    pub low_hz: f32,
+    pub high_hz: f32,
+    /// envelope follower speeds
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub carrier: CarrierWave,
+    pub chord: Chord,
+    /// MIDI note number of the carrier root
+    pub carrier_note: f32,
+    /// 0..1, blend between the dry voice and the vocoder
+    pub mix: f32,
+}
+
+impl Default for VocoderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bands: 16,
+            low_hz: 120.0,
+            high_hz: 6000.0,
+            attack_ms: 5.0,
+            release_ms: 60.0,
+            carrier: CarrierWave::Saw,
+            chord: Chord::Single,
+            carrier_note: 45.0,
+            mix: 1.0,
+        }
+    }
+}
+
+/// frequency of a MIDI note number
+pub fn note_to_hz(note: f32) -> f32 {
+    440.0 * 2.0_f32.powf((note - 69.0) / 12.0)
+}
+
+/// naive saw/pulse oscillator with polyBLEP smoothing of the discontinuities
+#[derive(Debug, Clone, Copy, Default)]
+struct Oscillator {
+    phase: f32,
+    increment: f32,
+}
+
+impl Oscillator {
+    /// polynomial band-limited step correction around a discontinuity
+    fn poly_blep(phase: f32, increment: f32) -> f32 {
+        if phase < increment {
+            let t = phase / increment;
+            t + t - t * t - 1.0
+        } else if phase > 1.0 - increment {
+            let t = (phase - 1.0) / increment;
+            t * t + t + t + 1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn next_sample(&mut self, wave: CarrierWave) -> f32 {
+        let phase = self.phase;
+        let increment = self.increment;
+        let sample = match wave {
+            CarrierWave::Pulse => {
+                let square = if phase < 0.5 { 1.0 } else { -1.0 };
+                square + Self::poly_blep(phase, increment) - Self::poly_blep((phase + 0.5) % 1.0, increment)
+            }
+            _ => 2.0 * phase - 1.0 - Self::poly_blep(phase, increment),
+        };
+        self.phase = (phase + increment) % 1.0;
+        sample
+    }
+}
+
+/// one analysis/synthesis band pair with its envelope follower
+#[derive(Debug, Clone, Copy, Default)]
+struct Band {
+    analysis: [Biquad; 2],
+    synthesis: [Biquad; 2],
+    envelope: f32,
+}
+
+/// classic channel vocoder: the voice modulates an internal carrier synth
+pub struct Vocoder {
+    sample_rate: f32,
+    settings: VocoderSettings,
+    bands: Vec<Band>,
+    active_bands: usize,
+    oscillators: Vec<Oscillator>,
+    noise: Noise,
+    attack: f32,
+    release: f32,
+}
+
+impl Vocoder {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut vocoder = Self {
+            sample_rate,
+            settings: VocoderSettings::default(),
+            bands: vec![Band::default(); MAX_BANDS as usize],
+            active_bands: 0,
+            oscillators: vec![Oscillator::default(); 3],
+            noise: Noise::new(0x9e37_79b9),
+            attack: 0.0,
+            release: 0.0,
+        };
+        vocoder.configure(&VocoderSettings::default());
+        vocoder
+    }
+
+    pub fn set_settings(&mut self, settings: &VocoderSettings) {
+        // don't let stale envelopes open the bands when switching on
+        if settings.enabled && !self.settings.enabled {
+            self.reset();
+        }
+        if *settings != self.settings {
+            self.configure(settings);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for band in &mut self.bands {
+            band.analysis.iter_mut().chain(band.synthesis.iter_mut()).for_each(Biquad::reset);
+            band.envelope = 0.0;
+        }
+    }
+
+    fn configure(&mut self, settings: &VocoderSettings) {
+        let previous = self.settings;
+        self.settings = *settings;
+        let sr = self.sample_rate;
+
+        // log spaced band centres between the low and high frequency
+        let count = settings.bands.clamp(MIN_BANDS, MAX_BANDS) as usize;
+        let low = settings.low_hz.max(20.0);
+        let high = settings.high_hz.max(low * 2.0);
+        let ratio = (high / low).powf(1.0 / (count - 1) as f32);
+        let q = BAND_Q_SCALE / (ratio.sqrt() - 1.0 / ratio.sqrt());
+        if count != self.active_bands
+            || settings.low_hz != previous.low_hz
+            || settings.high_hz != previous.high_hz
+        {
+            for (i, band) in self.bands.iter_mut().take(count).enumerate() {
+                let filter = Biquad::bandpass(low * ratio.powi(i as i32), q, sr);
+                band.analysis.iter_mut().chain(band.synthesis.iter_mut()).for_each(|f| f.retune(filter));
+            }
+            self.active_bands = count;
+        }
+
+        self.attack = smoothing_coefficient(settings.attack_ms.max(0.1), sr);
+        self.release = smoothing_coefficient(settings.release_ms.max(0.1), sr);
+
+        let root = settings.carrier_note;
+        for (oscillator, interval) in self.oscillators.iter_mut().zip(settings.chord.intervals()) {
+            oscillator.increment = (note_to_hz(root + interval) / sr).min(0.5);
+        }
+    }
+
+    fn carrier(&mut self) -> f32 {
+        match self.settings.carrier {
+            CarrierWave::Noise => self.noise.next_sample(),
+            wave => {
+                let voices = self.settings.chord.intervals().len();
+                let sum: f32 = self.oscillators[..voices].iter_mut().map(|osc| osc.next_sample(wave)).sum();
+                sum / voices as f32
+            }
+        }
+    }
+
+    /// feed one sample of the voice, returns the dry/vocoded mix
+    pub fn process(&mut self, input: f32) -> f32 {
+        let carrier = self.carrier();
+        let (attack, release) = (self.attack, self.release);
+
+        let mut vocoded = 0.0;
+        for band in &mut self.bands[..self.active_bands] {
+            let analysed = band.analysis.iter_mut().fold(input, |x, f| f.process(x));
+            let level = analysed.abs();
+            let speed = if level > band.envelope { attack } else { release };
+            band.envelope += (level - band.envelope) * speed;
+
+            let synthesised = band.synthesis.iter_mut().fold(carrier, |x, f| f.process(x));
+            vocoded += synthesised * band.envelope;
+        }
+
+        let mix = self.settings.mix.clamp(0.0, 1.0);
+        input * (1.0 - mix) + vocoded * self.settings.carrier.makeup_gain() * mix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{rms, sine};
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn enabled(settings: VocoderSettings) -> Vocoder {
+        let mut vocoder = Vocoder::new(SAMPLE_RATE);
+        vocoder.set_settings(&VocoderSettings { enabled: true, ..settings });
+        vocoder
+    }
+
+    /// stand-in for a voiced vowel: a 150Hz buzz with harmonics falling off like a glottal pulse
+    fn voice(i: usize) -> f32 {
+        let t = i as f32 / SAMPLE_RATE;
+        (1..40).map(|n| (2.0 * PI * 150.0 * n as f32 * t).sin() / n as f32).sum::<f32>() * 0.2
+    }
+
+    #[test]
+    fn silent_modulator_gives_silence() {
+        for carrier in CarrierWave::ALL {
+            let mut vocoder = enabled(VocoderSettings { carrier, ..VocoderSettings::default() });
+            assert!((0..SAMPLE_RATE as usize).all(|_| vocoder.process(0.0) == 0.0), "{}", carrier);
+        }
+    }
+
+    #[test]
+    fn tone_in_one_band_opens_mainly_that_band() {
+        let settings = VocoderSettings::default();
+        let ratio = (settings.high_hz / settings.low_hz).powf(1.0 / (settings.bands - 1) as f32);
+        let band = 8;
+        let freq = settings.low_hz * ratio.powi(band as i32);
+        let mut vocoder = enabled(settings);
+        for x in sine(freq, SAMPLE_RATE as u32, 0.5, 0.5) {
+            vocoder.process(x);
+        }
+
+        let envelopes: Vec<f32> = vocoder.bands[..vocoder.active_bands].iter().map(|b| b.envelope).collect();
+        let loudest = envelopes.iter().copied().fold(0.0, f32::max);
+        assert_eq!(envelopes[band], loudest, "{:?}", envelopes);
+        let others: f32 = envelopes.iter().enumerate().filter(|(i, _)| i.abs_diff(band) > 1).map(|(_, e)| e).sum();
+        assert!(others < loudest / 5.0, "{:?}", envelopes);
+    }
+
+    #[test]
+    fn makeup_gain_levels_the_vocoder_with_the_dry_voice() {
+        let dry: Vec<f32> = (0..SAMPLE_RATE as usize).map(voice).collect();
+        for carrier in CarrierWave::ALL {
+            let mut vocoder = enabled(VocoderSettings { carrier, ..VocoderSettings::default() });
+            let wet: Vec<f32> = dry.iter().map(|&x| vocoder.process(x)).collect();
+            let difference_db = 20.0 * (rms(&wet[dry.len() / 2..]) / rms(&dry[dry.len() / 2..])).log10();
+            assert!(difference_db.abs() < 3.0, "{}: {:.1} dB", carrier, difference_db);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! renders a fixed test signal through a set of presets and compares the result with
+//! the reference recordings in `tests/golden`. after an intended change to the sound,
+//! listen to the new output and write it back with
+//! `MONTAGE_BLESS=1 cargo test -p montage-dsp --test golden`
+
+use montage_dsp::character::VoiceCharacter;
+use montage_dsp::{DspProcessor, EffectSettings};
+use std::f32::consts::PI;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const SAMPLE_RATE: u32 = 22050;
+const FRAMES: usize = SAMPLE_RATE as usize * 2 / 5;
+const BUFFER_SIZE: usize = 256;
+/// about 7 lsb at 16 bits, far below anything audible but above rounding differences between platforms
+const TOLERANCE: f32 = 2e-4;
+
+struct Preset {
+    name: &'static str,
+    pitch: f32,
+    effects: EffectSettings,
+}
+
+fn presets() -> Vec<Preset> {
+    let preset = |name, pitch, configure: fn(&mut EffectSettings)| {
+        let mut effects = EffectSettings::default();
+        configure(&mut effects);
+        Preset { name, pitch, effects }
+    };
+    vec![
+        preset("natural", 1.0, |_| {}),
+        preset("chipmunk", 1.6, |_| {}),
+        preset("deep", 0.7, |_| {}),
+        preset("robot", 1.0, |effects| effects.character = VoiceCharacter::Robot),
+        preset("radio", 1.0, |effects| effects.character = VoiceCharacter::Radio),
+        preset("vocoder", 1.0, |effects| effects.vocoder.enabled = true),
+        preset("harmonizer", 1.0, |effects| effects.harmonizer.enabled = true),
+        preset("full_chain", 1.2, |effects| {
+            effects.deesser.enabled = true;
+            effects.character = VoiceCharacter::Monster;
+            effects.harmonizer.enabled = true;
+            effects.echo.enabled = true;
+            effects.reverb.enabled = true;
+            effects.agc.enabled = true;
+        }),
+    ]
+}
+
+/// a buzzy 140Hz voice with a vibrato, a gap, then a burst of hiss like an "s"
+fn test_signal() -> Vec<f32> {
+    let mut seed = 22222u32;
+    (0..FRAMES)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            if t < 0.2 {
+                let freq = 140.0 + (2.0 * PI * 5.0 * t).sin() * 4.0;
+                let phase = 2.0 * PI * freq * t;
+                (1..8).map(|n| (phase * n as f32).sin() / n as f32).sum::<f32>() * 0.25
+            } else if t < 0.25 {
+                0.0
+            } else if t < 0.3 {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                ((seed >> 8) as f32 / (1 << 24) as f32 - 0.5) * 0.4
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// interleaved stereo output of one preset, fed in buffers like the audio callback
+fn render(preset: &Preset) -> Vec<f32> {
+    let mut dsp = DspProcessor::new(Arc::new(Mutex::new(preset.pitch)), SAMPLE_RATE);
+    dsp.set_effects(&preset.effects);
+    let input = test_signal();
+    let mut left = [0.0; BUFFER_SIZE];
+    let mut right = [0.0; BUFFER_SIZE];
+    let mut output = Vec::with_capacity(FRAMES * 2);
+    for block in input.chunks(BUFFER_SIZE) {
+        let len = block.len();
+        dsp.process_stereo(block, &mut left[..len], &mut right[..len]);
+        for (l, r) in left[..len].iter().zip(&right[..len]) {
+            output.extend([*l, *r]);
+        }
+    }
+    output
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.wav", name))
+}
+
+/// 32-bit float stereo wav, so the references can be listened to
+fn write_wav(path: &PathBuf, samples: &[f32]) {
+    let data_len = (samples.len() * 4) as u32;
+    let mut bytes = Vec::with_capacity(44 + samples.len() * 4);
+    bytes.extend(b"RIFF");
+    bytes.extend((36 + data_len).to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend(3u16.to_le_bytes()); // ieee float
+    bytes.extend(2u16.to_le_bytes());
+    bytes.extend(SAMPLE_RATE.to_le_bytes());
+    bytes.extend((SAMPLE_RATE * 8).to_le_bytes());
+    bytes.extend(8u16.to_le_bytes());
+    bytes.extend(32u16.to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend(sample.to_le_bytes());
+    }
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, bytes).unwrap();
+}
+
+/// reads back what `write_wav` wrote, nothing more general
+fn read_wav(path: &PathBuf) -> Vec<f32> {
+    let bytes = std::fs::read(path).unwrap_or_else(|e| {
+        panic!("{}: {}, create it with MONTAGE_BLESS=1 cargo test -p montage-dsp --test golden", path.display(), e)
+    });
+    assert!(bytes.len() >= 44 && &bytes[..4] == b"RIFF" && &bytes[36..40] == b"data", "{} is not a wav file we wrote", path.display());
+    bytes[44..]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+#[test]
+fn presets_match_the_reference_renders() {
+    let bless = std::env::var_os("MONTAGE_BLESS").is_some();
+    let mut failures = Vec::new();
+
+    for preset in presets() {
+        let output = render(&preset);
+        assert!(output.iter().all(|x| x.is_finite()), "{} produced a NaN or infinity", preset.name);
+
+        let path = golden_path(preset.name);
+        if bless {
+            write_wav(&path, &output);
+            continue;
+        }
+
+        let expected = read_wav(&path);
+        if expected.len() != output.len() {
+            failures.push(format!("{}: {} samples, expected {}", preset.name, output.len(), expected.len()));
+            continue;
+        }
+        let (index, error) = output
+            .iter()
+            .zip(&expected)
+            .map(|(a, b)| (a - b).abs())
+            .enumerate()
+            .fold((0, 0.0), |worst, (i, e)| if e > worst.1 { (i, e) } else { worst });
+        if error > TOLERANCE {
+            failures.push(format!(
+                "{}: off by {:.6} at frame {} ({:.3}s)",
+                preset.name,
+                error,
+                index / 2,
+                (index / 2) as f32 / SAMPLE_RATE as f32
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "output differs from the references:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn presets_are_not_silent() {
+    // a reference of silence would match a broken chain
+    for preset in presets() {
+        let output = render(&preset);
+        let peak = output.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        assert!(peak > 0.05, "{} peaks at {}", preset.name, peak);
+    }
+}
@@ -0,0 +1,67 @@
+//! the montage effect chain as a CLAP plugin: a voice comes in, the processed stereo mix
+//! goes out, and the main controls of the standalone app are automatable parameters
+
+mod params;
+mod plugin;
+mod processor;
+
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::host::clap_host;
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::version::{clap_version_is_compatible, CLAP_VERSION};
+use std::ffi::{c_char, c_void, CStr};
+use std::ptr::null;
+
+/// the symbol hosts look up after loading the library
+#[allow(non_upper_case_globals)]
+#[unsafe(no_mangle)]
+pub static clap_entry: clap_plugin_entry = clap_plugin_entry {
+    clap_version: CLAP_VERSION,
+    init: Some(init),
+    deinit: Some(deinit),
+    get_factory: Some(get_factory),
+};
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+    get_plugin_count: Some(plugin_count),
+    get_plugin_descriptor: Some(plugin_descriptor),
+    create_plugin: Some(create_plugin),
+};
+
+unsafe extern "C" fn init(_plugin_path: *const c_char) -> bool {
+    true
+}
+
+unsafe extern "C" fn deinit() {}
+
+unsafe extern "C" fn get_factory(factory_id: *const c_char) -> *const c_void {
+    if !factory_id.is_null() && unsafe { CStr::from_ptr(factory_id) } == CLAP_PLUGIN_FACTORY_ID {
+        &FACTORY as *const clap_plugin_factory as *const c_void
+    } else {
+        null()
+    }
+}
+
+unsafe extern "C" fn plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn plugin_descriptor(_factory: *const clap_plugin_factory, index: u32) -> *const clap_plugin_descriptor {
+    if index == 0 { &plugin::DESCRIPTOR } else { null() }
+}
+
+unsafe extern "C" fn create_plugin(
+    _factory: *const clap_plugin_factory,
+    host: *const clap_host,
+    plugin_id: *const c_char,
+) -> *const clap_plugin {
+    if host.is_null() || plugin_id.is_null() {
+        return null();
+    }
+    let (host_version, plugin_id) = unsafe { ((*host).clap_version, CStr::from_ptr(plugin_id)) };
+    if !clap_version_is_compatible(host_version) || plugin_id != plugin::ID {
+        return null();
+    }
+    plugin::Plugin::create(host)
+}
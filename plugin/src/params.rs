@@ -0,0 +1,298 @@
+use montage_dsp::EffectSettings;
+use montage_dsp::character::VoiceCharacter;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// longest output delay, the same as in the standalone app
+pub const MAX_DELAY_MS: f64 = 100.0;
+
+/// first bytes of a saved state, followed by the format version
+const STATE_MAGIC: &[u8; 4] = b"MNTG";
+const STATE_VERSION: u32 = 1;
+
+/// how a parameter is shown and whether the host should step it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Ratio,
+    Milliseconds,
+    Decibels,
+    Hertz,
+    Lufs,
+    /// compression ratio, shown as `4.0:1`
+    Compression,
+    Switch,
+    Character,
+}
+
+pub struct ParamInfo {
+    /// stable across versions, hosts store automation and state by it
+    pub id: u32,
+    pub name: &'static str,
+    /// group shown by hosts that sort parameters into folders
+    pub module: &'static str,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+    pub unit: Unit,
+}
+
+impl ParamInfo {
+    pub fn stepped(&self) -> bool {
+        matches!(self.unit, Unit::Switch | Unit::Character)
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        match self.unit {
+            Unit::Ratio => format!("{:.2}x", value),
+            Unit::Milliseconds => format!("{:.0}ms", value),
+            Unit::Decibels => format!("{:+.1}dB", value),
+            Unit::Hertz => format!("{:.0}Hz", value),
+            Unit::Lufs => format!("{:.1} LUFS", value),
+            Unit::Compression => format!("{:.1}:1", value),
+            Unit::Switch => if value >= 0.5 { "On" } else { "Off" }.to_string(),
+            Unit::Character => character(value).to_string(),
+        }
+    }
+
+    /// reads what `format` wrote, and plain numbers
+    pub fn parse(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        let value = match self.unit {
+            Unit::Switch => match text.to_ascii_lowercase().as_str() {
+                "on" | "1" => 1.0,
+                "off" | "0" => 0.0,
+                _ => return None,
+            },
+            Unit::Character => match VoiceCharacter::ALL.iter().position(|c| c.to_string().eq_ignore_ascii_case(text)) {
+                Some(index) => index as f64,
+                None => text.parse().ok()?,
+            },
+            Unit::Compression => text.trim_end_matches(":1").trim().parse().ok()?,
+            _ => {
+                let number = text.trim_end_matches(|c: char| c.is_alphabetic() || c.is_whitespace());
+                number.trim_start_matches('+').parse().ok()?
+            }
+        };
+        Some(value.clamp(self.min, self.max))
+    }
+}
+
+const fn param(id: u32, name: &'static str, module: &'static str, range: (f64, f64), default: f64, unit: Unit) -> ParamInfo {
+    ParamInfo { id, name, module, min: range.0, max: range.1, default, unit }
+}
+
+/// everything the plugin exposes, ranges match the sliders of the standalone app
+pub const PARAMS: [ParamInfo; 23] = [
+    param(0, "Pitch", "", (0.5, 2.0), 1.0, Unit::Ratio),
+    param(1, "Delay", "", (0.0, MAX_DELAY_MS), 0.0, Unit::Milliseconds),
+    param(2, "Input gain", "", (-24.0, 24.0), 0.0, Unit::Decibels),
+    param(3, "Output volume", "", (-60.0, 12.0), 0.0, Unit::Decibels),
+    param(4, "Character", "", (0.0, VoiceCharacter::ALL.len() as f64 - 1.0), 0.0, Unit::Character),
+    param(5, "De-esser", "De-esser", (0.0, 1.0), 0.0, Unit::Switch),
+    param(6, "De-esser frequency", "De-esser", (2000.0, 12000.0), 6000.0, Unit::Hertz),
+    param(7, "De-esser threshold", "De-esser", (-60.0, 0.0), -30.0, Unit::Decibels),
+    param(8, "De-esser range", "De-esser", (0.0, 24.0), 10.0, Unit::Decibels),
+    param(9, "Auto gain", "Auto gain", (0.0, 1.0), 0.0, Unit::Switch),
+    param(10, "Auto gain target", "Auto gain", (-36.0, -10.0), -18.0, Unit::Lufs),
+    param(11, "Auto gain max gain", "Auto gain", (0.0, 30.0), 12.0, Unit::Decibels),
+    param(12, "Echo", "Effects", (0.0, 1.0), 0.0, Unit::Switch),
+    param(13, "Reverb", "Effects", (0.0, 1.0), 0.0, Unit::Switch),
+    param(14, "Harmonizer", "Effects", (0.0, 1.0), 0.0, Unit::Switch),
+    param(15, "Compressor threshold", "Compressor", (-24.0, 0.0), -3.1, Unit::Decibels),
+    param(16, "Compressor ratio", "Compressor", (1.0, 20.0), 3.33, Unit::Compression),
+    param(17, "Compressor knee", "Compressor", (0.0, 12.0), 2.5, Unit::Decibels),
+    param(18, "EQ", "EQ", (0.0, 1.0), 0.0, Unit::Switch),
+    param(19, "EQ low", "EQ", (-12.0, 12.0), 0.0, Unit::Decibels),
+    param(20, "EQ mid", "EQ", (-12.0, 12.0), 0.0, Unit::Decibels),
+    param(21, "EQ mid frequency", "EQ", (300.0, 5000.0), 1500.0, Unit::Hertz),
+    param(22, "EQ high", "EQ", (-12.0, 12.0), 0.0, Unit::Decibels),
+];
+
+pub fn index_of(id: u32) -> Option<usize> {
+    PARAMS.iter().position(|param| param.id == id)
+}
+
+fn character(value: f64) -> VoiceCharacter {
+    VoiceCharacter::ALL[(value.round().max(0.0) as usize).min(VoiceCharacter::ALL.len() - 1)]
+}
+
+/// what the processor needs from the parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainSettings {
+    pub pitch: f32,
+    pub delay_ms: f32,
+    pub input_gain_db: f32,
+    pub output_volume_db: f32,
+    pub effects: EffectSettings,
+}
+
+/// current value of every parameter, written by the host from the main thread
+/// (state, flush) or the audio thread (automation) and read from both
+pub struct ParamValues {
+    values: [AtomicU64; PARAMS.len()],
+}
+
+impl ParamValues {
+    pub fn new() -> Self {
+        Self { values: std::array::from_fn(|index| AtomicU64::new(PARAMS[index].default.to_bits())) }
+    }
+
+    pub fn get(&self, index: usize) -> f64 {
+        f64::from_bits(self.values[index].load(Ordering::Relaxed))
+    }
+
+    /// clamped to the range, and rounded for switches and choices
+    pub fn set(&self, index: usize, value: f64) {
+        let info = &PARAMS[index];
+        let value = if info.stepped() { value.round() } else { value };
+        self.values[index].store(value.clamp(info.min, info.max).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn settings(&self) -> ChainSettings {
+        let value = |index| self.get(index) as f32;
+        let switch = |index| self.get(index) >= 0.5;
+        let mut effects = EffectSettings { character: character(self.get(4)), ..Default::default() };
+        effects.deesser.enabled = switch(5);
+        effects.deesser.frequency_hz = value(6);
+        effects.deesser.threshold_db = value(7);
+        effects.deesser.range_db = value(8);
+        effects.agc.enabled = switch(9);
+        effects.agc.target_lufs = value(10);
+        effects.agc.max_gain_db = value(11);
+        effects.echo.enabled = switch(12);
+        effects.reverb.enabled = switch(13);
+        effects.harmonizer.enabled = switch(14);
+        effects.compressor.threshold_db = value(15);
+        effects.compressor.ratio = value(16);
+        effects.compressor.knee_db = value(17);
+        effects.eq.enabled = switch(18);
+        effects.eq.low_gain_db = value(19);
+        effects.eq.mid_gain_db = value(20);
+        effects.eq.mid_hz = value(21);
+        effects.eq.high_gain_db = value(22);
+        ChainSettings {
+            pitch: value(0),
+            delay_ms: value(1),
+            input_gain_db: value(2),
+            output_volume_db: value(3),
+            effects,
+        }
+    }
+
+    /// id and value of every parameter, so a state survives parameters being added or reordered
+    pub fn save(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + PARAMS.len() * 12);
+        bytes.extend(STATE_MAGIC);
+        bytes.extend(STATE_VERSION.to_le_bytes());
+        bytes.extend((PARAMS.len() as u32).to_le_bytes());
+        for (index, info) in PARAMS.iter().enumerate() {
+            bytes.extend(info.id.to_le_bytes());
+            bytes.extend(self.get(index).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// parameters missing from the state go back to their defaults, unknown ones are skipped.
+    /// nothing changes if the state is not one `save` wrote
+    pub fn load(&self, bytes: &[u8]) -> bool {
+        let Some(entries) = parse_state(bytes) else { return false };
+        for (index, info) in PARAMS.iter().enumerate() {
+            self.set(index, info.default);
+        }
+        for (id, value) in entries {
+            if let Some(index) = index_of(id) {
+                self.set(index, value);
+            }
+        }
+        true
+    }
+}
+
+fn parse_state(bytes: &[u8]) -> Option<Vec<(u32, f64)>> {
+    let read_u32 = |bytes: &[u8]| bytes.try_into().ok().map(u32::from_le_bytes);
+    let (header, entries) = bytes.split_at_checked(12)?;
+    let count = read_u32(&header[8..])? as usize;
+    if &header[..4] != STATE_MAGIC || read_u32(&header[4..8])? != STATE_VERSION || entries.len() != count.checked_mul(12)? {
+        return None;
+    }
+    entries
+        .chunks_exact(12)
+        .map(|entry| Some((read_u32(&entry[..4])?, f64::from_le_bytes(entry[4..].try_into().ok()?))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_unique_and_defaults_in_range() {
+        for (index, info) in PARAMS.iter().enumerate() {
+            assert_eq!(index_of(info.id), Some(index), "{} shares its id", info.name);
+            assert!((info.min..=info.max).contains(&info.default), "{} defaults out of range", info.name);
+        }
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let values = ParamValues::new();
+        values.set(0, 1.5);
+        values.set(4, 3.0);
+        values.set(5, 1.0);
+        let state = values.save();
+
+        let loaded = ParamValues::new();
+        assert!(loaded.load(&state));
+        assert_eq!(loaded.settings(), values.settings());
+        assert_eq!(loaded.settings().effects.character, VoiceCharacter::Radio);
+    }
+
+    #[test]
+    fn broken_states_are_rejected() {
+        let values = ParamValues::new();
+        values.set(0, 1.5);
+        let state = values.save();
+        assert!(!values.load(&state[..state.len() - 1]));
+        assert!(!values.load(b"something else"));
+        assert_eq!(values.get(0), 1.5);
+    }
+
+    #[test]
+    fn unknown_ids_are_skipped() {
+        let mut state = ParamValues::new().save();
+        state[8..12].copy_from_slice(&(PARAMS.len() as u32 + 1).to_le_bytes());
+        state.extend(999u32.to_le_bytes());
+        state.extend(0.5f64.to_le_bytes());
+
+        let values = ParamValues::new();
+        values.set(1, 50.0);
+        assert!(values.load(&state));
+        assert_eq!(values.get(1), 0.0);
+    }
+
+    #[test]
+    fn defaults_match_the_chain() {
+        let defaults = ParamValues::new().settings().effects;
+        assert_eq!(defaults.compressor, EffectSettings::default().compressor);
+        assert_eq!(defaults.deesser, EffectSettings::default().deesser);
+        assert_eq!(defaults.eq, EffectSettings::default().eq);
+    }
+
+    #[test]
+    fn values_are_clamped_and_stepped() {
+        let values = ParamValues::new();
+        values.set(0, 5.0);
+        values.set(4, 1.4);
+        assert_eq!(values.get(0), 2.0);
+        assert_eq!(values.get(4), 1.0);
+    }
+
+    #[test]
+    fn formatted_values_parse_back() {
+        for info in &PARAMS {
+            for value in [info.min, info.default, info.max] {
+                let parsed = info.parse(&info.format(value)).unwrap_or_else(|| panic!("{}: {}", info.name, info.format(value)));
+                assert!((parsed - value).abs() < 0.05, "{}: {} became {}", info.name, value, parsed);
+            }
+        }
+    }
+}
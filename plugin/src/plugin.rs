@@ -0,0 +1,732 @@
+use crate::params::{index_of, ParamValues, Unit, PARAMS};
+use crate::processor::Processor;
+use clap_sys::events::{
+    clap_event_header, clap_event_param_value, clap_input_events, clap_output_events, CLAP_CORE_EVENT_SPACE_ID,
+    CLAP_EVENT_PARAM_VALUE,
+};
+use clap_sys::ext::audio_ports::{
+    clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS, CLAP_PORT_STEREO,
+};
+use clap_sys::ext::latency::{clap_plugin_latency, CLAP_EXT_LATENCY};
+use clap_sys::ext::params::{
+    clap_host_params, clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS, CLAP_PARAM_IS_AUTOMATABLE,
+    CLAP_PARAM_IS_ENUM, CLAP_PARAM_IS_STEPPED, CLAP_PARAM_RESCAN_VALUES,
+};
+use clap_sys::ext::state::{clap_plugin_state, CLAP_EXT_STATE};
+use clap_sys::host::clap_host;
+use clap_sys::id::clap_id;
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::plugin_features::{
+    CLAP_PLUGIN_FEATURE_AUDIO_EFFECT, CLAP_PLUGIN_FEATURE_PITCH_SHIFTER, CLAP_PLUGIN_FEATURE_STEREO,
+};
+use clap_sys::process::{clap_process, clap_process_status, CLAP_PROCESS_CONTINUE, CLAP_PROCESS_ERROR};
+use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::version::CLAP_VERSION;
+use std::ffi::{c_char, c_void, CStr};
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+pub const ID: &CStr = c"rs.montage.voice";
+
+/// null terminated list of feature strings, raw pointers aren't `Sync` on their own
+struct Features([*const c_char; 4]);
+unsafe impl Sync for Features {}
+
+static FEATURES: Features = Features([
+    CLAP_PLUGIN_FEATURE_AUDIO_EFFECT.as_ptr(),
+    CLAP_PLUGIN_FEATURE_PITCH_SHIFTER.as_ptr(),
+    CLAP_PLUGIN_FEATURE_STEREO.as_ptr(),
+    null(),
+]);
+
+const VERSION: &CStr = match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+    Ok(version) => version,
+    Err(_) => panic!("package version is not a C string"),
+};
+
+pub static DESCRIPTOR: clap_plugin_descriptor = clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: ID.as_ptr(),
+    name: c"Montage".as_ptr(),
+    vendor: c"Montage".as_ptr(),
+    url: c"".as_ptr(),
+    manual_url: c"".as_ptr(),
+    support_url: c"".as_ptr(),
+    version: VERSION.as_ptr(),
+    description: c"Pitch shifting voice effects".as_ptr(),
+    features: FEATURES.0.as_ptr(),
+};
+
+static PARAMS_EXTENSION: clap_plugin_params = clap_plugin_params {
+    count: Some(Plugin::param_count),
+    get_info: Some(Plugin::param_info),
+    get_value: Some(Plugin::param_value),
+    value_to_text: Some(Plugin::param_value_to_text),
+    text_to_value: Some(Plugin::param_text_to_value),
+    flush: Some(Plugin::param_flush),
+};
+
+static AUDIO_PORTS_EXTENSION: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: Some(Plugin::audio_port_count),
+    get: Some(Plugin::audio_port_info),
+};
+
+static STATE_EXTENSION: clap_plugin_state = clap_plugin_state {
+    save: Some(Plugin::save_state),
+    load: Some(Plugin::load_state),
+};
+
+static LATENCY_EXTENSION: clap_plugin_latency = clap_plugin_latency { get: Some(Plugin::latency) };
+
+/// one instance in the host. the host holds a pointer to `clap`, whose `plugin_data`
+/// points back at the whole boxed struct
+pub struct Plugin {
+    clap: clap_plugin,
+    host: *const clap_host,
+    values: ParamValues,
+    /// only exists while the plugin is active, the host never processes and
+    /// (de)activates at the same time so the lock is not contended
+    processor: Mutex<Option<Processor>>,
+    /// of the active processor, kept apart so asking for it never holds up processing
+    latency: AtomicU32,
+    /// the latency moved and the host was asked to restart, once per activation
+    restart_requested: AtomicBool,
+}
+
+impl Plugin {
+    pub fn create(host: *const clap_host) -> *const clap_plugin {
+        let plugin = Box::into_raw(Box::new(Plugin {
+            clap: clap_plugin {
+                desc: &DESCRIPTOR,
+                plugin_data: null_mut(),
+                init: Some(Self::init),
+                destroy: Some(Self::destroy),
+                activate: Some(Self::activate),
+                deactivate: Some(Self::deactivate),
+                start_processing: Some(Self::start_processing),
+                stop_processing: Some(Self::stop_processing),
+                reset: Some(Self::reset),
+                process: Some(Self::process),
+                get_extension: Some(Self::get_extension),
+                on_main_thread: Some(Self::on_main_thread),
+            },
+            host,
+            values: ParamValues::new(),
+            processor: Mutex::new(None),
+            latency: AtomicU32::new(0),
+            restart_requested: AtomicBool::new(false),
+        }));
+        unsafe {
+            (*plugin).clap.plugin_data = plugin as *mut c_void;
+            &(*plugin).clap
+        }
+    }
+
+    /// # Safety
+    /// `plugin` must be a pointer handed out by `create` that was not destroyed yet
+    unsafe fn from_clap<'a>(plugin: *const clap_plugin) -> &'a Plugin {
+        unsafe { &*((*plugin).plugin_data as *const Plugin) }
+    }
+
+    unsafe extern "C" fn init(_plugin: *const clap_plugin) -> bool {
+        true
+    }
+
+    unsafe extern "C" fn destroy(plugin: *const clap_plugin) {
+        drop(unsafe { Box::from_raw((*plugin).plugin_data as *mut Plugin) });
+    }
+
+    unsafe extern "C" fn activate(plugin: *const clap_plugin, sample_rate: f64, _min_frames: u32, max_frames: u32) -> bool {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        match plugin.processor.lock() {
+            Ok(mut processor) => {
+                // the latency depends on the character and the oversampling
+                let mut new = Processor::new(sample_rate, max_frames as usize);
+                new.apply(&plugin.values.settings());
+                plugin.latency.store(new.latency(), Ordering::Relaxed);
+                plugin.restart_requested.store(false, Ordering::Relaxed);
+                *processor = Some(new);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    unsafe extern "C" fn deactivate(plugin: *const clap_plugin) {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        if let Ok(mut processor) = plugin.processor.lock() {
+            *processor = None;
+        }
+    }
+
+    unsafe extern "C" fn start_processing(_plugin: *const clap_plugin) -> bool {
+        true
+    }
+
+    unsafe extern "C" fn stop_processing(_plugin: *const clap_plugin) {}
+
+    unsafe extern "C" fn reset(plugin: *const clap_plugin) {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        if let Ok(mut processor) = plugin.processor.try_lock()
+            && let Some(processor) = processor.as_mut()
+        {
+            processor.reset();
+        }
+    }
+
+    /// the block is split at every parameter change, so automation lands on the right sample
+    unsafe extern "C" fn process(plugin: *const clap_plugin, process: *const clap_process) -> clap_process_status {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        let process = unsafe { &*process };
+        let Ok(mut processor) = plugin.processor.try_lock() else { return CLAP_PROCESS_ERROR };
+        let Some(processor) = processor.as_mut() else { return CLAP_PROCESS_ERROR };
+
+        let frames = (process.frames_count as usize).min(processor.max_frames());
+        let events = unsafe { InputEvents::new(process.in_events) };
+        let mut next_event = 0;
+        let mut start = 0;
+        while start < frames {
+            while let Some(event) = events.get(next_event)
+                && event.time as usize <= start
+            {
+                unsafe { plugin.handle_event(event) };
+                next_event += 1;
+            }
+            let end = events.get(next_event).map_or(frames, |event| (event.time as usize).min(frames));
+            processor.apply(&plugin.values.settings());
+            if !unsafe { render(processor, process, start, end) } {
+                return CLAP_PROCESS_ERROR;
+            }
+            start = end;
+        }
+        if processor.latency() != plugin.latency.load(Ordering::Relaxed) {
+            unsafe { plugin.request_restart() };
+        }
+        // changes stamped at or past the end of the block still count
+        while let Some(event) = events.get(next_event) {
+            unsafe { plugin.handle_event(event) };
+            next_event += 1;
+        }
+        CLAP_PROCESS_CONTINUE
+    }
+
+    unsafe extern "C" fn get_extension(_plugin: *const clap_plugin, id: *const c_char) -> *const c_void {
+        if id.is_null() {
+            return null();
+        }
+        match unsafe { CStr::from_ptr(id) } {
+            id if id == CLAP_EXT_PARAMS => &PARAMS_EXTENSION as *const clap_plugin_params as *const c_void,
+            id if id == CLAP_EXT_AUDIO_PORTS => &AUDIO_PORTS_EXTENSION as *const clap_plugin_audio_ports as *const c_void,
+            id if id == CLAP_EXT_STATE => &STATE_EXTENSION as *const clap_plugin_state as *const c_void,
+            id if id == CLAP_EXT_LATENCY => &LATENCY_EXTENSION as *const clap_plugin_latency as *const c_void,
+            _ => null(),
+        }
+    }
+
+    unsafe extern "C" fn on_main_thread(_plugin: *const clap_plugin) {}
+
+    /// in samples, only asked for while active
+    unsafe extern "C" fn latency(plugin: *const clap_plugin) -> u32 {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        plugin.latency.load(Ordering::Relaxed)
+    }
+
+    /// # Safety
+    /// `event` must point at a complete event of the type its header says
+    unsafe fn handle_event(&self, event: &clap_event_header) {
+        if event.space_id != CLAP_CORE_EVENT_SPACE_ID || event.type_ != CLAP_EVENT_PARAM_VALUE {
+            return;
+        }
+        let event = unsafe { &*(event as *const clap_event_header as *const clap_event_param_value) };
+        if let Some(index) = index_of(event.param_id) {
+            self.values.set(index, event.value);
+        }
+    }
+
+    unsafe extern "C" fn param_count(_plugin: *const clap_plugin) -> u32 {
+        PARAMS.len() as u32
+    }
+
+    unsafe extern "C" fn param_info(_plugin: *const clap_plugin, index: u32, info: *mut clap_param_info) -> bool {
+        let Some(param) = PARAMS.get(index as usize) else { return false };
+        let info = unsafe { &mut *info };
+        info.id = param.id;
+        info.flags = CLAP_PARAM_IS_AUTOMATABLE;
+        if param.stepped() {
+            info.flags |= CLAP_PARAM_IS_STEPPED;
+        }
+        if param.unit == Unit::Character {
+            info.flags |= CLAP_PARAM_IS_ENUM;
+        }
+        info.cookie = null_mut();
+        copy_str(param.name, &mut info.name);
+        copy_str(param.module, &mut info.module);
+        info.min_value = param.min;
+        info.max_value = param.max;
+        info.default_value = param.default;
+        true
+    }
+
+    unsafe extern "C" fn param_value(plugin: *const clap_plugin, id: clap_id, value: *mut f64) -> bool {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        let Some(index) = index_of(id) else { return false };
+        unsafe { *value = plugin.values.get(index) };
+        true
+    }
+
+    unsafe extern "C" fn param_value_to_text(
+        _plugin: *const clap_plugin,
+        id: clap_id,
+        value: f64,
+        buffer: *mut c_char,
+        capacity: u32,
+    ) -> bool {
+        let Some(index) = index_of(id) else { return false };
+        if buffer.is_null() || capacity == 0 {
+            return false;
+        }
+        copy_str(&PARAMS[index].format(value), unsafe { std::slice::from_raw_parts_mut(buffer, capacity as usize) });
+        true
+    }
+
+    unsafe extern "C" fn param_text_to_value(
+        _plugin: *const clap_plugin,
+        id: clap_id,
+        text: *const c_char,
+        value: *mut f64,
+    ) -> bool {
+        let Some(index) = index_of(id) else { return false };
+        if text.is_null() {
+            return false;
+        }
+        let Ok(text) = unsafe { CStr::from_ptr(text) }.to_str() else { return false };
+        let Some(parsed) = PARAMS[index].parse(text) else { return false };
+        unsafe { *value = parsed };
+        true
+    }
+
+    /// parameter changes while the plugin isn't processing
+    unsafe extern "C" fn param_flush(plugin: *const clap_plugin, events: *const clap_input_events, _out: *const clap_output_events) {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        let events = unsafe { InputEvents::new(events) };
+        for index in 0..events.len() {
+            if let Some(event) = events.get(index) {
+                unsafe { plugin.handle_event(event) };
+            }
+        }
+    }
+
+    unsafe extern "C" fn audio_port_count(_plugin: *const clap_plugin, _is_input: bool) -> u32 {
+        1
+    }
+
+    /// a stereo pair each way, the input is mixed down to the mono voice the chain expects
+    unsafe extern "C" fn audio_port_info(
+        _plugin: *const clap_plugin,
+        index: u32,
+        is_input: bool,
+        info: *mut clap_audio_port_info,
+    ) -> bool {
+        if index != 0 {
+            return false;
+        }
+        let info = unsafe { &mut *info };
+        info.id = 0;
+        copy_str(if is_input { "Voice" } else { "Output" }, &mut info.name);
+        info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+        info.channel_count = 2;
+        info.port_type = CLAP_PORT_STEREO.as_ptr();
+        // the input is copied out before the output is written, so both may share buffers
+        info.in_place_pair = 0;
+        true
+    }
+
+    unsafe extern "C" fn save_state(plugin: *const clap_plugin, stream: *const clap_ostream) -> bool {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        let stream = unsafe { &*stream };
+        let Some(write) = stream.write else { return false };
+        let state = plugin.values.save();
+        let mut written = 0;
+        while written < state.len() {
+            let rest = &state[written..];
+            match unsafe { write(stream, rest.as_ptr() as *const c_void, rest.len() as u64) } {
+                count if count > 0 => written += count as usize,
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    unsafe extern "C" fn load_state(plugin: *const clap_plugin, stream: *const clap_istream) -> bool {
+        let plugin = unsafe { Self::from_clap(plugin) };
+        let stream = unsafe { &*stream };
+        let Some(read) = stream.read else { return false };
+        let mut state = Vec::new();
+        let mut chunk = [0u8; 256];
+        loop {
+            match unsafe { read(stream, chunk.as_mut_ptr() as *mut c_void, chunk.len() as u64) } {
+                0 => break,
+                count if count > 0 => state.extend(&chunk[..count as usize]),
+                _ => return false,
+            }
+        }
+        if !plugin.values.load(&state) {
+            return false;
+        }
+        unsafe { plugin.rescan_values() };
+        true
+    }
+
+    /// the latency may only change while inactive, the host deactivates and activates
+    /// again and then asks for it. thread-safe, and only asked once per activation
+    unsafe fn request_restart(&self) {
+        if self.restart_requested.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let host = unsafe { &*self.host };
+        if let Some(request_restart) = host.request_restart {
+            unsafe { request_restart(self.host) };
+        }
+    }
+
+    /// tells the host the parameters changed behind its back, from the main thread
+    unsafe fn rescan_values(&self) {
+        let host = unsafe { &*self.host };
+        let Some(get_extension) = host.get_extension else { return };
+        let params = unsafe { get_extension(self.host, CLAP_EXT_PARAMS.as_ptr()) } as *const clap_host_params;
+        if let Some(params) = unsafe { params.as_ref() }
+            && let Some(rescan) = params.rescan
+        {
+            unsafe { rescan(self.host, CLAP_PARAM_RESCAN_VALUES) };
+        }
+    }
+}
+
+/// the host's event list for one block, sorted by time
+struct InputEvents<'a> {
+    list: Option<&'a clap_input_events>,
+    len: usize,
+}
+
+impl InputEvents<'_> {
+    /// # Safety
+    /// `list` must be null or a valid event list that outlives the returned value
+    unsafe fn new(list: *const clap_input_events) -> Self {
+        let list = unsafe { list.as_ref() };
+        let len = list.and_then(|list| Some(unsafe { list.size?(list) } as usize)).unwrap_or(0);
+        Self { list, len }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> Option<&clap_event_header> {
+        if index >= self.len {
+            return None;
+        }
+        let list = self.list?;
+        unsafe { list.get?(list, index as u32).as_ref() }
+    }
+}
+
+/// mixes the input down into the processor and writes frames `start..end` of the output
+///
+/// # Safety
+/// the buffers of `process` must hold at least `end` frames for every channel they announce
+unsafe fn render(processor: &mut Processor, process: &clap_process, start: usize, end: usize) -> bool {
+    let frames = end - start;
+    let input = processor.input(frames);
+    if process.audio_inputs_count > 0
+        && let Some(port) = unsafe { process.audio_inputs.as_ref() }
+        && !port.data32.is_null()
+    {
+        let channels = port.channel_count as usize;
+        for channel in 0..channels {
+            let samples = unsafe { *port.data32.add(channel) };
+            if samples.is_null() {
+                continue;
+            }
+            // dropped before the outputs are borrowed, they may be the same buffers
+            let samples = unsafe { std::slice::from_raw_parts(samples.add(start), frames) };
+            for (mono, sample) in input.iter_mut().zip(samples) {
+                *mono += sample / channels as f32;
+            }
+        }
+    }
+
+    if process.audio_outputs_count == 0 {
+        return true;
+    }
+    let Some(port) = (unsafe { process.audio_outputs.as_ref() }) else { return false };
+    if port.data32.is_null() || port.channel_count < 2 {
+        return false;
+    }
+    let (left, right) = unsafe { (*port.data32, *port.data32.add(1)) };
+    if left.is_null() || right.is_null() {
+        return false;
+    }
+    let (left, right) = unsafe {
+        (std::slice::from_raw_parts_mut(left.add(start), frames), std::slice::from_raw_parts_mut(right.add(start), frames))
+    };
+    processor.process(left, right);
+    true
+}
+
+/// truncated to fit, always null terminated
+fn copy_str(text: &str, dest: &mut [c_char]) {
+    let len = text.len().min(dest.len().saturating_sub(1));
+    for (dest, byte) in dest.iter_mut().zip(&text.as_bytes()[..len]) {
+        *dest = *byte as c_char;
+    }
+    if let Some(end) = dest.get_mut(len) {
+        *end = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clap_entry, create_plugin};
+    use clap_sys::audio_buffer::clap_audio_buffer;
+    use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+    use montage_dsp::character::VoiceCharacter;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+    const FRAMES: usize = 512;
+
+    static HOST: clap_host = clap_host {
+        clap_version: CLAP_VERSION,
+        host_data: null_mut(),
+        name: c"test host".as_ptr(),
+        vendor: c"".as_ptr(),
+        url: c"".as_ptr(),
+        version: c"1".as_ptr(),
+        get_extension: None,
+        request_restart: None,
+        request_process: None,
+        request_callback: None,
+    };
+
+    static RESTARTS: AtomicU32 = AtomicU32::new(0);
+
+    unsafe extern "C" fn count_restart(_host: *const clap_host) {
+        RESTARTS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// the only host that counts restart requests, so no other test adds to them
+    static RESTARTING_HOST: clap_host = clap_host { request_restart: Some(count_restart), ..HOST };
+
+    unsafe extern "C" fn events_size(list: *const clap_input_events) -> u32 {
+        let events = unsafe { &*((*list).ctx as *const Vec<clap_event_param_value>) };
+        events.len() as u32
+    }
+
+    unsafe extern "C" fn events_get(list: *const clap_input_events, index: u32) -> *const clap_event_header {
+        let events = unsafe { &*((*list).ctx as *const Vec<clap_event_param_value>) };
+        &events[index as usize].header
+    }
+
+    unsafe extern "C" fn stream_write(stream: *const clap_ostream, buffer: *const c_void, size: u64) -> i64 {
+        // a few bytes at a time, hosts may accept less than offered
+        let size = size.min(7) as usize;
+        let bytes = unsafe { std::slice::from_raw_parts(buffer as *const u8, size) };
+        let state = unsafe { &mut *((*stream).ctx as *mut Vec<u8>) };
+        state.extend(bytes);
+        size as i64
+    }
+
+    unsafe extern "C" fn stream_read(stream: *const clap_istream, buffer: *mut c_void, size: u64) -> i64 {
+        let source = unsafe { &mut *((*stream).ctx as *mut Vec<u8>) };
+        let size = (size as usize).min(source.len());
+        unsafe { std::ptr::copy_nonoverlapping(source.as_ptr(), buffer as *mut u8, size) };
+        source.drain(..size);
+        size as i64
+    }
+
+    fn param_event(time: u32, id: clap_id, value: f64) -> clap_event_param_value {
+        clap_event_param_value {
+            header: clap_event_header {
+                size: size_of::<clap_event_param_value>() as u32,
+                time,
+                space_id: CLAP_CORE_EVENT_SPACE_ID,
+                type_: CLAP_EVENT_PARAM_VALUE,
+                flags: 0,
+            },
+            param_id: id,
+            cookie: null_mut(),
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            value,
+        }
+    }
+
+    /// one block of a stereo sine through the plugin with the given events, in place
+    unsafe fn process_block(plugin: *const clap_plugin, events: &Vec<clap_event_param_value>) -> [Vec<f32>; 2] {
+        let sine: Vec<f32> = (0..FRAMES).map(|i| (2.0 * PI * 220.0 * i as f32 / SAMPLE_RATE as f32).sin() * 0.3).collect();
+        let mut channels = [sine.clone(), sine];
+        let mut pointers = [channels[0].as_mut_ptr(), channels[1].as_mut_ptr()];
+        let buffer = clap_audio_buffer {
+            data32: pointers.as_mut_ptr(),
+            data64: null_mut(),
+            channel_count: 2,
+            latency: 0,
+            constant_mask: 0,
+        };
+        let mut output = buffer;
+        let in_events = clap_input_events {
+            ctx: events as *const Vec<clap_event_param_value> as *mut c_void,
+            size: Some(events_size),
+            get: Some(events_get),
+        };
+        let process = clap_process {
+            steady_time: -1,
+            frames_count: FRAMES as u32,
+            transport: null(),
+            audio_inputs: &buffer,
+            audio_outputs: &mut output,
+            audio_inputs_count: 1,
+            audio_outputs_count: 1,
+            in_events: &in_events,
+            out_events: null(),
+        };
+        let status = unsafe { (*plugin).process.unwrap()(plugin, &process) };
+        assert_eq!(status, CLAP_PROCESS_CONTINUE);
+        channels
+    }
+
+    unsafe fn param(plugin: *const clap_plugin, id: clap_id) -> f64 {
+        let mut value = f64::NAN;
+        assert!(unsafe { Plugin::param_value(plugin, id, &mut value) });
+        value
+    }
+
+    #[test]
+    fn the_factory_hands_out_the_plugin() {
+        unsafe {
+            let factory = clap_entry.get_factory.unwrap()(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory;
+            assert!(!factory.is_null());
+            assert_eq!((*factory).get_plugin_count.unwrap()(factory), 1);
+            let descriptor = (*factory).get_plugin_descriptor.unwrap()(factory, 0);
+            assert_eq!(CStr::from_ptr((*descriptor).id), ID);
+            assert!(create_plugin(factory, &HOST, c"someone.else".as_ptr()).is_null());
+
+            let plugin = create_plugin(factory, &HOST, ID.as_ptr());
+            assert!(!plugin.is_null());
+            assert!((*plugin).init.unwrap()(plugin));
+            for extension in [CLAP_EXT_PARAMS, CLAP_EXT_AUDIO_PORTS, CLAP_EXT_STATE, CLAP_EXT_LATENCY] {
+                assert!(!(*plugin).get_extension.unwrap()(plugin, extension.as_ptr()).is_null());
+            }
+            (*plugin).destroy.unwrap()(plugin);
+        }
+    }
+
+    #[test]
+    fn automation_and_state_reach_the_chain() {
+        unsafe {
+            let plugin = Plugin::create(&HOST);
+            assert!((*plugin).activate.unwrap()(plugin, SAMPLE_RATE, 1, FRAMES as u32));
+            assert!((*plugin).start_processing.unwrap()(plugin));
+
+            let [left, right] = process_block(plugin, &Vec::new());
+            assert!(left.iter().chain(&right).all(|x| x.is_finite()));
+            assert!(left.iter().any(|x| x.abs() > 0.01), "the voice doesn't come through");
+
+            // muted from the middle of the block on
+            let volume = PARAMS[3].id;
+            let [left, _] = process_block(plugin, &vec![param_event(FRAMES as u32 / 2, volume, -60.0)]);
+            assert_eq!(param(plugin, volume), -60.0);
+            let first_half = left[..FRAMES / 2].iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+            for _ in 0..10 {
+                process_block(plugin, &Vec::new());
+            }
+            let [left, _] = process_block(plugin, &Vec::new());
+            let quiet = left.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+            assert!(quiet < first_half * 0.01, "{} is not quieter than {}", quiet, first_half);
+
+            let mut state = Vec::new();
+            let ostream = clap_ostream { ctx: &mut state as *mut Vec<u8> as *mut c_void, write: Some(stream_write) };
+            assert!(Plugin::save_state(plugin, &ostream));
+
+            let restored = Plugin::create(&HOST);
+            let istream = clap_istream { ctx: &mut state as *mut Vec<u8> as *mut c_void, read: Some(stream_read) };
+            assert!(Plugin::load_state(restored, &istream));
+            assert_eq!(param(restored, volume), -60.0);
+
+            (*plugin).stop_processing.unwrap()(plugin);
+            (*plugin).deactivate.unwrap()(plugin);
+            (*plugin).destroy.unwrap()(plugin);
+            (*restored).destroy.unwrap()(restored);
+        }
+    }
+
+    #[test]
+    fn latency_is_reported_for_the_active_rate() {
+        unsafe {
+            let plugin = Plugin::create(&HOST);
+            let latency = (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_LATENCY.as_ptr()) as *const clap_plugin_latency;
+            let get = (*latency).get.unwrap();
+            let mut reported = Vec::new();
+            for sample_rate in [44100.0, 96000.0] {
+                assert!((*plugin).activate.unwrap()(plugin, sample_rate, 1, FRAMES as u32));
+                reported.push(get(plugin));
+                assert_eq!(reported.last(), Some(&Processor::new(sample_rate, FRAMES).latency()));
+                (*plugin).deactivate.unwrap()(plugin);
+            }
+            // the pitch ring covers the same time at every rate
+            assert!(reported[0] > 0 && reported[1] > reported[0] * 2);
+            (*plugin).destroy.unwrap()(plugin);
+        }
+    }
+
+    #[test]
+    fn latency_change_asks_the_host_to_restart() {
+        unsafe {
+            let plugin = Plugin::create(&RESTARTING_HOST);
+            let latency = (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_LATENCY.as_ptr()) as *const clap_plugin_latency;
+            let get = (*latency).get.unwrap();
+            assert!((*plugin).activate.unwrap()(plugin, SAMPLE_RATE, 1, FRAMES as u32));
+            let natural = get(plugin);
+
+            // the radio character drives its saturation through the oversampler
+            let radio = VoiceCharacter::ALL.iter().position(|c| *c == VoiceCharacter::Radio).unwrap() as f64;
+            process_block(plugin, &vec![param_event(0, PARAMS[4].id, radio)]);
+            process_block(plugin, &Vec::new());
+            assert_eq!(RESTARTS.load(Ordering::Relaxed), 1);
+
+            (*plugin).deactivate.unwrap()(plugin);
+            assert!((*plugin).activate.unwrap()(plugin, SAMPLE_RATE, 1, FRAMES as u32));
+            assert!(get(plugin) > natural, "{} is not above {}", get(plugin), natural);
+            process_block(plugin, &Vec::new());
+            assert_eq!(RESTARTS.load(Ordering::Relaxed), 1);
+            (*plugin).deactivate.unwrap()(plugin);
+            (*plugin).destroy.unwrap()(plugin);
+        }
+    }
+
+    #[test]
+    fn values_are_shown_and_parsed() {
+        unsafe {
+            let plugin = Plugin::create(&HOST);
+            let mut info: clap_param_info = std::mem::zeroed();
+            assert!(Plugin::param_info(plugin, 4, &mut info));
+            assert_eq!(CStr::from_ptr(info.name.as_ptr()), c"Character");
+            assert_ne!(info.flags & CLAP_PARAM_IS_STEPPED, 0);
+            assert!(!Plugin::param_info(plugin, PARAMS.len() as u32, &mut info));
+
+            let mut text = [0 as c_char; 4];
+            assert!(Plugin::param_value_to_text(plugin, PARAMS[0].id, 1.5, text.as_mut_ptr(), text.len() as u32));
+            assert_eq!(CStr::from_ptr(text.as_ptr()), c"1.5");
+
+            let mut value = 0.0;
+            assert!(Plugin::param_text_to_value(plugin, PARAMS[4].id, c"robot".as_ptr(), &mut value));
+            assert_eq!(value, 1.0);
+            assert!(!Plugin::param_text_to_value(plugin, PARAMS[0].id, c"loud".as_ptr(), &mut value));
+            (*plugin).destroy.unwrap()(plugin);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::params::{ChainSettings, MAX_DELAY_MS};
+use montage_dsp::DspProcessor;
+use std::sync::{Arc, Mutex};
+
+/// the effect chain and output delay for one activation of the plugin, everything is
+/// allocated up front for the largest block the host announced
+pub struct Processor {
+    dsp: DspProcessor,
+    pitch: Arc<Mutex<f32>>,
+    sample_rate: f32,
+    /// settings last handed to the chain, changes are only passed on when they differ
+    applied: Option<ChainSettings>,
+    /// the input mixed down to mono, filled through `input` before each `process`
+    mono: Vec<f32>,
+    /// one left/right pair per frame, long enough for the longest delay
+    delay_line: Vec<[f32; 2]>,
+    delay_index: usize,
+    delay_frames: usize,
+}
+
+impl Processor {
+    pub fn new(sample_rate: f64, max_frames: usize) -> Self {
+        let pitch = Arc::new(Mutex::new(1.0));
+        let max_delay_frames = (sample_rate * MAX_DELAY_MS / 1000.0).ceil() as usize;
+        Self {
+            dsp: DspProcessor::new(pitch.clone(), sample_rate.round() as u32),
+            pitch,
+            sample_rate: sample_rate as f32,
+            applied: None,
+            mono: vec![0.0; max_frames],
+            delay_line: vec![[0.0; 2]; max_delay_frames + 1],
+            delay_index: 0,
+            delay_frames: 0,
+        }
+    }
+
+    pub fn max_frames(&self) -> usize {
+        self.mono.len()
+    }
+
+    /// delay of the chain for the host to compensate, the delay parameter is meant to be heard
+    pub fn latency(&self) -> u32 {
+        self.dsp.base_latency_samples() as u32
+    }
+
+    pub fn apply(&mut self, settings: &ChainSettings) {
+        if self.applied.as_ref() == Some(settings) {
+            return;
+        }
+        if let Ok(mut pitch) = self.pitch.try_lock() {
+            *pitch = settings.pitch;
+        }
+        self.dsp.set_levels(settings.input_gain_db, settings.output_volume_db, false);
+        if self.applied.is_none_or(|applied| applied.effects != settings.effects) {
+            self.dsp.set_effects(&settings.effects);
+        }
+        let delay_frames = (settings.delay_ms / 1000.0 * self.sample_rate).round() as usize;
+        self.delay_frames = delay_frames.min(self.delay_line.len() - 1);
+        self.applied = Some(*settings);
+    }
+
+    /// cleared buffer for the mono input of the next `frames` frames
+    pub fn input(&mut self, frames: usize) -> &mut [f32] {
+        let input = &mut self.mono[..frames];
+        input.fill(0.0);
+        input
+    }
+
+    /// runs the input of the last `input` call through the chain and the delay
+    pub fn process(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let frames = left.len().min(right.len()).min(self.mono.len());
+        self.dsp.process_stereo(&self.mono[..frames], &mut left[..frames], &mut right[..frames]);
+        if self.delay_frames == 0 {
+            return;
+        }
+        let len = self.delay_line.len();
+        for (left, right) in left[..frames].iter_mut().zip(&mut right[..frames]) {
+            self.delay_line[self.delay_index] = [*left, *right];
+            [*left, *right] = self.delay_line[(self.delay_index + len - self.delay_frames) % len];
+            self.delay_index = (self.delay_index + 1) % len;
+        }
+    }
+
+    /// drop everything still in the chain, for when the host jumps or restarts playback
+    pub fn reset(&mut self) {
+        self.dsp.reset();
+        self.delay_line.fill([0.0; 2]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ParamValues;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn first_sound(processor: &mut Processor, frames: usize) -> Option<usize> {
+        let (mut left, mut right) = (vec![0.0; frames], vec![0.0; frames]);
+        let input = processor.input(frames);
+        input[0] = 1.0;
+        processor.process(&mut left, &mut right);
+        left.iter().position(|x| x.abs() > 1e-3)
+    }
+
+    #[test]
+    fn delay_holds_back_the_output() {
+        let values = ParamValues::new();
+        let mut processor = Processor::new(SAMPLE_RATE, 8192);
+        processor.apply(&values.settings());
+        let undelayed = first_sound(&mut processor, 8192).expect("no output without a delay");
+
+        values.set(1, 50.0);
+        let mut processor = Processor::new(SAMPLE_RATE, 8192);
+        processor.apply(&values.settings());
+        let delayed = first_sound(&mut processor, 8192).expect("no output with a delay");
+        assert_eq!(delayed - undelayed, 2400);
+    }
+
+    #[test]
+    fn reset_drops_the_delayed_sound() {
+        let values = ParamValues::new();
+        values.set(1, 50.0);
+        let mut processor = Processor::new(SAMPLE_RATE, 1024);
+        processor.apply(&values.settings());
+        // still inside the delay line when the reset comes
+        assert_eq!(first_sound(&mut processor, 1024), None);
+        processor.reset();
+        for _ in 0..4 {
+            let (mut left, mut right) = (vec![0.0; 1024], vec![0.0; 1024]);
+            processor.input(1024);
+            processor.process(&mut left, &mut right);
+            assert!(left.iter().chain(&right).all(|x| x.abs() < 1e-6));
+        }
+    }
+}
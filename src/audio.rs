@@ -1,20 +1,267 @@
-use crate::{dsp::DspProcessor, gui::AudioSettings};
-use anyhow::{anyhow, Result};
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+mod cpal_backend;
+mod file_backend;
+#[cfg(feature = "jack")]
+mod jack_backend;
+mod null_backend;
+mod virtual_clock;
+
+use crate::dsp::DspProcessor;
+use crate::dsp::resample::Resampler;
+use crate::latency::{LatencyReport, LoopbackProbe};
+use crate::meters::{Meters, PeakMeter};
+use crate::params::MAX_DELAY_MS;
+use crate::settings::AudioSettings;
+use crate::status::{DeviceInfo, EngineStatus, StatusFeed, StreamState};
+use anyhow::{bail, Result};
+use cpal_backend::CpalBackend;
+use file_backend::FileBackend;
+#[cfg(feature = "jack")]
+use jack_backend::JackBackend;
+use null_backend::NullBackend;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// wait before the first reconnection attempt, doubled after every failure
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+/// how often the audio thread looks at the shutdown signal
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// what the engine asks of the devices when it (re)starts
+pub struct StreamRequest<'a> {
+    /// devices of the last successful start, tried before the defaults
+    pub input_name: Option<&'a str>,
+    pub output_name: Option<&'a str>,
+    pub sample_rate: u32,
+    pub buffer_size: u32,
+}
+
+/// gets interleaved input frames at the device's rate and channel count,
+/// with the time since they were captured if the device knows it
+pub type InputCallback = Box<dyn FnMut(&[f32], Option<Duration>) + Send>;
+/// fills interleaved output frames, with the time until they are played if the device knows it
+pub type OutputCallback = Box<dyn FnMut(&mut [f32], Option<Duration>) + Send>;
+
+/// where the engine gets its input and sends its output. the engine runs the same
+/// on real devices, files or a virtual clock, converting to whatever rate, channel
+/// count and sample format the backend settles on
+pub trait AudioBackend {
+    /// choose the devices for `request` and agree on a configuration with them
+    fn open(&mut self, request: &StreamRequest) -> Result<(DeviceInfo, DeviceInfo)>;
+    /// start the devices of the last `open`, errors that end the streams are sent to `errors`
+    fn start(&mut self, input: InputCallback, output: OutputCallback, errors: mpsc::Sender<String>) -> Result<()>;
+    /// stop the audio and drop the callbacks
+    fn stop(&mut self);
+}
+
+/// which backend `run_audio` uses, picked at startup
+#[derive(Debug, Clone, Default)]
+pub enum BackendKind {
+    /// the system's audio devices
+    #[default]
+    Cpal,
+    /// a client in the JACK or PipeWire graph with ports to patch
+    #[cfg(feature = "jack")]
+    Jack,
+    /// no devices, silence in and the output thrown away, for headless machines
+    Null,
+    /// the voice is read from one wav file and the result written to another
+    File { input: PathBuf, output: PathBuf },
+}
+
+impl BackendKind {
+    /// created on the audio thread, some hosts' streams can't be moved between threads
+    fn create(&self) -> Box<dyn AudioBackend> {
+        match self {
+            BackendKind::Cpal => Box::new(CpalBackend::new()),
+            #[cfg(feature = "jack")]
+            BackendKind::Jack => Box::new(JackBackend::new()),
+            BackendKind::Null => Box::new(NullBackend::new()),
+            BackendKind::File { input, output } => Box::new(FileBackend::new(input.clone(), output.clone())),
+        }
+    }
+}
+
+/// the backend flags understood by every front-end:
+/// `--jack` joins the JACK/PipeWire graph,
+/// `--null-audio` runs without devices,
+/// `--input-file voice.wav --output-file out.wav` processes a recording instead of the mic
+#[derive(Debug, Default)]
+pub struct BackendArgs {
+    null: bool,
+    jack: bool,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+}
+
+impl BackendArgs {
+    /// consumes `arg`, and its value from `rest`, if it is one of the backend flags
+    pub fn take(&mut self, arg: &str, rest: &mut impl Iterator<Item = String>) -> bool {
+        match arg {
+            "--jack" => self.jack = true,
+            "--null-audio" => self.null = true,
+            "--input-file" => self.input = rest.next().map(PathBuf::from),
+            "--output-file" => self.output = rest.next().map(PathBuf::from),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn backend(self) -> Result<BackendKind> {
+        let BackendArgs { null, jack, input, output } = self;
+        if jack {
+            if null || input.is_some() || output.is_some() {
+                bail!("--jack can't be combined with other backends");
+            }
+            #[cfg(feature = "jack")]
+            return Ok(BackendKind::Jack);
+            #[cfg(not(feature = "jack"))]
+            bail!("montage was built without JACK support, rebuild it with `--features jack`");
+        }
+        Ok(match (null, input, output) {
+            (true, None, None) => BackendKind::Null,
+            (false, Some(input), Some(output)) => BackendKind::File { input, output },
+            (false, None, None) => BackendKind::Cpal,
+            (false, Some(_), None) | (false, None, Some(_)) => bail!("--input-file and --output-file go together"),
+            _ => bail!("--null-audio can't be combined with files"),
+        })
+    }
+}
+
+/// the devices the engine runs on
+struct Streams {
+    input: DeviceInfo,
+    output: DeviceInfo,
+    sample_rate: u32,
+    buffer_size: u32,
+}
+
+/// updated by the callbacks, read for the status reports
+#[derive(Default)]
+struct Counters {
+    /// input blocks thrown away because the output side fell behind
+    dropped_input: AtomicU64,
+    /// output buffers that had to be padded with silence
+    underruns: AtomicU64,
+    /// time spent in and covered by the output callbacks since the last report
+    busy_ns: AtomicU64,
+    period_ns: AtomicU64,
+    /// highest callback time over its buffer period since the last report, f32 bits
+    peak_load: AtomicU32,
+}
+
+/// what every set of streams shares with the supervising loop
+struct Shared {
+    settings: Arc<Mutex<AudioSettings>>,
+    latency: Arc<Mutex<LatencyReport>>,
+    meters: Arc<Mutex<Meters>>,
+    counters: Arc<Counters>,
+}
+
+/// runs the audio engine until shutdown, reopening the devices whenever a stream
+/// fails (e.g. a USB device was unplugged). the state of the engine is reported
+/// to the front-ends through `status_feed`
+pub(crate) fn run_audio(
+    backend: BackendKind,
+    settings: Arc<Mutex<AudioSettings>>,
+    meters: Arc<Mutex<Meters>>,
+    status_feed: StatusFeed,
+    shutdown_signal: Arc<AtomicBool>,
+) -> Result<()> {
+    let mut backend = backend.create();
+    let shared = Shared {
+        settings,
+        latency: Arc::new(Mutex::new(LatencyReport::default())),
+        meters,
+        counters: Arc::new(Counters::default()),
+    };
+    let mut status = EngineStatus::default();
+    let publish = |status: &mut EngineStatus, state: StreamState| {
+        let counters = &shared.counters;
+        status.state = state;
+        status.dropped_inputs = counters.dropped_input.load(Ordering::Relaxed);
+        status.underruns = counters.underruns.load(Ordering::Relaxed);
+        let busy = counters.busy_ns.swap(0, Ordering::Relaxed);
+        let period = counters.period_ns.swap(0, Ordering::Relaxed);
+        status.cpu_load = if period > 0 { busy as f32 / period as f32 } else { 0.0 };
+        status.cpu_peak = f32::from_bits(counters.peak_load.swap(0, Ordering::Relaxed));
+        if let Ok(report) = shared.latency.try_lock() {
+            status.latency = *report;
+        }
+        status_feed.publish(status);
+    };
+
+    // devices of the last successful start, tried first when reconnecting
+    let mut input_name = None;
+    let mut output_name = None;
+    let mut backoff = MIN_BACKOFF;
+    let mut attempt = 0;
+    let mut reason;
+
+    while !shutdown_signal.load(Ordering::Relaxed) {
+        let (error_tx, error_rx) = mpsc::channel();
+        match start_streams(backend.as_mut(), input_name.as_deref(), output_name.as_deref(), &shared, error_tx) {
+            Ok(streams) => {
+                input_name = Some(streams.input.name.clone());
+                output_name = Some(streams.output.name.clone());
+                backoff = MIN_BACKOFF;
+                attempt = 0;
+                status.input = Some(streams.input.clone());
+                status.output = Some(streams.output.clone());
+                status.sample_rate = streams.sample_rate;
+                status.buffer_size = streams.buffer_size;
+
+                // keep streams alive until shutdown or until one of them fails,
+                // reporting on the way
+                reason = loop {
+                    if shutdown_signal.load(Ordering::Relaxed) {
+                        backend.stop();
+                        return Ok(());
+                    }
+                    publish(&mut status, StreamState::Running);
+                    match error_rx.recv_timeout(POLL_INTERVAL) {
+                        Ok(err) => break err,
+                        Err(mpsc::RecvTimeoutError::Timeout) => {}
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break "streams stopped".to_string(),
+                    }
+                };
+                backend.stop();
+            }
+            Err(err) => reason = err.to_string(),
+        }
+        eprintln!("Audio stream error: {}", reason);
+        status.last_error = Some(reason.clone());
+        publish(&mut status, StreamState::Disconnected(reason.clone()));
 
-pub fn run_audio(settings: Arc<Mutex<AudioSettings>>, shutdown_signal: Arc<AtomicBool>) -> Result<()> {
-    let host = cpal::default_host();
+        attempt += 1;
+        publish(&mut status, StreamState::Reconnecting { reason, attempt, delay_ms: backoff.as_millis() as u64 });
+        let mut waited = Duration::ZERO;
+        while waited < backoff && !shutdown_signal.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL_INTERVAL);
+            waited += POLL_INTERVAL;
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
 
-    let input_device = host
-        .default_input_device()
-        .ok_or_else(|| anyhow!("No input device available"))?;
+    Ok(())
+}
 
-    let output_device = host
-        .default_output_device()
-        .ok_or_else(|| anyhow!("No output device available"))?;
+/// opens and starts both streams on `backend`, stream errors are sent to `errors`
+fn start_streams(
+    backend: &mut dyn AudioBackend,
+    input_name: Option<&str>,
+    output_name: Option<&str>,
+    shared: &Shared,
+    errors: mpsc::Sender<String>,
+) -> Result<Streams> {
+    let settings = &shared.settings;
+    let latency = shared.latency.clone();
+    let meters = shared.meters.clone();
+    let input_counters = shared.counters.clone();
+    let counters = shared.counters.clone();
 
     // get initial settings
     let initial_settings = {
@@ -22,61 +269,113 @@ pub fn run_audio(settings: Arc<Mutex<AudioSettings>>, shutdown_signal: Arc<Atomi
         settings_lock.clone()
     };
 
-    let channels = 2; // force stereo for better compatibility
     let sample_rate = initial_settings.sample_rate.to_hz();
-    let buffer_size = cpal::BufferSize::Fixed(initial_settings.buffer_size);
+
+    // the devices run at their own rate, channel count and sample format when they
+    // can't do what the engine uses internally, everything in between is converted
+    let (input_info, output_info) = backend.open(&StreamRequest {
+        input_name,
+        output_name,
+        sample_rate,
+        buffer_size: initial_settings.buffer_size,
+    })?;
+    let input_rate = input_info.sample_rate;
+    let output_rate = output_info.sample_rate;
+    let input_channels = input_info.channels.max(1) as usize;
+    let output_channels = output_info.channels.max(1) as usize;
 
     // create DSP processor with pitch reference
     let pitch_ref = Arc::new(Mutex::new(initial_settings.pitch));
-    let mut dsp = DspProcessor::new(pitch_ref.clone());
-
-    // create delay buffer for output delay
-    let max_delay_samples = (sample_rate as f32 * 0.1) as usize; // max 100ms delay
-    let mut delay_buffer: VecDeque<f32> = VecDeque::with_capacity(max_delay_samples);
-    
-    let input_stream_config = cpal::StreamConfig {
-        channels: channels as u16,
-        sample_rate: cpal::SampleRate(sample_rate),
-        buffer_size,
-    };
-
-    let output_stream_config = input_stream_config.clone();
+    let mut dsp = DspProcessor::new(pitch_ref.clone(), sample_rate);
 
-    let err_fn = |err| {
-        eprintln!("Audio stream error: {}", err);
-    };
+    // create delay buffer for output delay, one left/right pair per frame
+    let max_delay_samples = (sample_rate as f32 * MAX_DELAY_MS / 1000.0) as usize;
+    let mut delay_buffer: VecDeque<[f32; 2]> = VecDeque::with_capacity(max_delay_samples);
 
     // channel for audio data
-    let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<f32>>(4);
+    let (tx, rx) = std::sync::mpsc::sync_channel::<(u64, Vec<f32>)>(4);
+
+    // latency bookkeeping shared between the two callbacks
+    let queued_frames = Arc::new(AtomicUsize::new(0));
+    let input_latency_us = Arc::new(AtomicU32::new(0));
+    let probe = Arc::new(Mutex::new(LoopbackProbe::new(sample_rate)));
+    // counts panics, the input side starts over from silence when it moves
+    let panics = Arc::new(AtomicU32::new(0));
 
     // input stream
-    let input_stream = input_device.build_input_stream(
-        &input_stream_config,
-        move |data: &[f32], _: &cpal::InputCallbackInfo| {
+    let input_queued_frames = queued_frames.clone();
+    let input_latency = input_latency_us.clone();
+    let input_probe = probe.clone();
+    let mut input_clock: u64 = 0;
+    let mut input_resampler = Resampler::new(input_rate, sample_rate);
+    let input_resample_ms = input_resampler.latency_ms();
+    let input_panics = panics.clone();
+    let mut input_panic_count = 0;
+    let input_meters = meters.clone();
+    let mut input_peak = PeakMeter::new(input_rate);
+    let input_callback: InputCallback = Box::new(
+        move |data: &[f32], capture_delay: Option<Duration>| {
+            if let Some(elapsed) = capture_delay {
+                input_latency.store(elapsed.as_micros() as u32, Ordering::Relaxed);
+            }
+
+            // the resampler still holds what was captured before the panic
+            let panic_count = input_panics.load(Ordering::Relaxed);
+            if panic_count != input_panic_count {
+                input_panic_count = panic_count;
+                input_resampler.reset();
+            }
+
             // convert to mono signal
-            let mut buffer = Vec::with_capacity(data.len() / channels);
-            for frame in data.chunks(channels) {
-                if !frame.is_empty() {
-                    let mono_sample = if channels == 2 && frame.len() >= 2 {
-                        (frame[0] + frame[1]) * 0.5
-                    } else {
-                        frame[0]
-                    };
-                    buffer.push(mono_sample);
-                }
+            let mut mono = Vec::with_capacity(data.len() / input_channels);
+            for frame in data.chunks(input_channels) {
+                mono.push(frame.iter().sum::<f32>() / frame.len() as f32);
+            }
+
+            // and to the internal rate
+            let mut buffer = Vec::with_capacity(mono.len() * sample_rate as usize / input_rate as usize + 1);
+            input_resampler.process(&mono, &mut buffer);
+
+            let peak_db = input_peak.process(&mono, mono.len());
+            if let Ok(mut meters) = input_meters.try_lock() {
+                meters.input_peak_db = peak_db;
+            }
+
+            if let Ok(mut probe) = input_probe.try_lock() {
+                probe.observe_input(input_clock, &buffer);
+            }
+            // the block carries the number of its first frame, so the output side knows
+            // which input frame it is replacing even after blocks were dropped
+            let start = input_clock;
+            input_clock += buffer.len() as u64;
+
+            // count the frames before sending so the output side never sees them first
+            let frames = buffer.len();
+            input_queued_frames.fetch_add(frames, Ordering::Relaxed);
+            if tx.try_send((start, buffer)).is_err() {
+                input_queued_frames.fetch_sub(frames, Ordering::Relaxed);
+                input_counters.dropped_input.fetch_add(1, Ordering::Relaxed);
             }
-            
-            let _ = tx.try_send(buffer);
         },
-        err_fn.clone(),
-        None,
-    )?;
+    );
 
     // output stream with settings monitoring
     let settings_clone = settings.clone();
-    let output_stream = output_device.build_output_stream(
-        &output_stream_config,
-        move |output: &mut [f32], _: &cpal::OutputCallbackInfo| {
+    // input frame the block being played was captured at, for the loopback probe
+    let mut output_clock: u64 = 0;
+    let mut loopback_request = initial_settings.loopback_request;
+    let mut panic_request = initial_settings.panic_request;
+    // converted frames at the device rate waiting to be played
+    let mut output_fifo: VecDeque<[f32; 2]> = VecDeque::new();
+    let mut output_resamplers = [
+        Resampler::new(sample_rate, output_rate),
+        Resampler::new(sample_rate, output_rate),
+    ];
+    let resample_ms = input_resample_ms + output_resamplers[0].latency_ms();
+    let mut output_peak = PeakMeter::new(output_rate);
+    let output_callback: OutputCallback = Box::new(
+        move |output: &mut [f32], playback_delay: Option<Duration>| {
+            let started = Instant::now();
             // update settings from GUI
             let current_settings = {
                 if let Ok(settings_lock) = settings_clone.try_lock() {
@@ -85,6 +384,8 @@ pub fn run_audio(settings: Arc<Mutex<AudioSettings>>, shutdown_signal: Arc<Atomi
                     if let Ok(mut pitch_lock) = pitch_ref.try_lock() {
                         *pitch_lock = settings.pitch;
                     }
+                    dsp.set_effects(&settings.effects);
+                    dsp.set_levels(settings.input_gain_db, settings.output_volume_db, settings.mute);
                     settings
                 } else {
                     return; // skip this buffer if we can't get settings
@@ -94,93 +395,310 @@ pub fn run_audio(settings: Arc<Mutex<AudioSettings>>, shutdown_signal: Arc<Atomi
             // calculate delay samples
             let delay_samples = ((current_settings.delay_ms / 1000.0) * sample_rate as f32) as usize;
             let delay_samples = delay_samples.min(max_delay_samples);
+            let frames = output.len() / output_channels;
 
-            match rx.try_recv() {
-                Ok(input_buffer) => {
-                    if !input_buffer.is_empty() {
-                        let process_len = output.len().min(input_buffer.len() * channels);
-                        let mono_len = process_len / channels;
-                        
-                        if mono_len > 0 {
-                            // process audio
-                            let mut processed_buffer = vec![0.0f32; mono_len];
-                            dsp.process(&input_buffer[..mono_len], &mut processed_buffer);
-                            
-                            // apply delay
-                            for (i, &sample) in processed_buffer.iter().enumerate() {
-                                // add to delay buffer
-                                delay_buffer.push_back(sample);
-                                
-                                // get delayed sample
-                                let delayed_sample = if delay_buffer.len() > delay_samples {
-                                    delay_buffer.pop_front().unwrap_or(0.0)
-                                } else {
-                                    0.0 // silence during initial delay buildup
-                                };
-                                
-                                // output to both channels
-                                if i * channels < output.len() {
-                                    output[i * channels] = delayed_sample;
-                                    if channels == 2 && i * channels + 1 < output.len() {
-                                        output[i * channels + 1] = delayed_sample;
-                                    }
-                                }
-                            }
-                            
-                            // fill remaining with silence
-                            for sample in output[mono_len * channels..].iter_mut() {
-                                *sample = 0.0;
-                            }
-                        } else {
-                            // fill with silence
-                            for sample in output.iter_mut() {
-                                *sample = 0.0;
-                            }
-                        }
-                    } else {
-                        // fill with silence
-                        for sample in output.iter_mut() {
-                            *sample = 0.0;
-                        }
+            // panic: throw away everything queued or ringing and start over from silence
+            if current_settings.panic_request != panic_request {
+                panic_request = current_settings.panic_request;
+                panics.fetch_add(1, Ordering::Relaxed);
+                dsp.reset();
+                delay_buffer.clear();
+                output_fifo.clear();
+                output_resamplers.iter_mut().for_each(Resampler::reset);
+                while let Ok((_, stale)) = rx.try_recv() {
+                    queued_frames.fetch_sub(stale.len(), Ordering::Relaxed);
+                }
+                output.fill(0.0);
+                return;
+            }
+
+            // a loopback measurement replaces the processed signal with a single click
+            if let Ok(mut probe) = probe.try_lock()
+                && current_settings.loopback_request != loopback_request
+            {
+                loopback_request = current_settings.loopback_request;
+                probe.arm(output_clock);
+            }
+
+            // process input blocks at the internal rate until there is enough
+            // converted audio to fill this callback
+            while output_fifo.len() < frames {
+                let Ok((input_start, input_buffer)) = rx.try_recv() else {
+                    // no input data, the rest is played as silence
+                    counters.underruns.fetch_add(1, Ordering::Relaxed);
+                    break;
+                };
+                queued_frames.fetch_sub(input_buffer.len(), Ordering::Relaxed);
+                let len = input_buffer.len();
+                output_clock = input_start;
+
+                let mut processed_left = vec![0.0f32; len];
+                let mut processed_right = vec![0.0f32; len];
+                if current_settings.bypass {
+                    // the voice as it came in, only muting still applies
+                    let gain = if current_settings.mute { 0.0 } else { 1.0 };
+                    for ((left, right), input) in processed_left.iter_mut().zip(processed_right.iter_mut()).zip(&input_buffer) {
+                        (*left, *right) = (input * gain, input * gain);
                     }
-                },
-                Err(_) => {
-                    // no input data, output delayed samples or silence
-                    for i in 0..(output.len() / channels) {
-                        let delayed_sample = if delay_buffer.len() > delay_samples {
-                            delay_buffer.pop_front().unwrap_or(0.0)
+                } else {
+                    dsp.process_stereo(&input_buffer, &mut processed_left, &mut processed_right);
+
+                    // apply delay
+                    for (left, right) in processed_left.iter_mut().zip(processed_right.iter_mut()) {
+                        delay_buffer.push_back([*left, *right]);
+                        let delayed_frame = if delay_buffer.len() > delay_samples {
+                            delay_buffer.pop_front().unwrap_or([0.0; 2])
                         } else {
-                            0.0
+                            [0.0; 2] // silence during initial delay buildup
                         };
-                        
-                        if i * channels < output.len() {
-                            output[i * channels] = delayed_sample;
-                            if channels == 2 && i * channels + 1 < output.len() {
-                                output[i * channels + 1] = delayed_sample;
-                            }
-                        }
+                        [*left, *right] = delayed_frame;
+                    }
+                }
+
+                if let Ok(mut probe) = probe.try_lock()
+                    && probe.is_active()
+                {
+                    for (i, (left, right)) in processed_left.iter_mut().zip(processed_right.iter_mut()).enumerate() {
+                        let click = probe.output_sample(output_clock + i as u64);
+                        (*left, *right) = (click, click);
+                    }
+                }
+                output_clock += len as u64;
+
+                // convert to the device rate
+                let mut converted_left = Vec::with_capacity(frames);
+                let mut converted_right = Vec::with_capacity(frames);
+                output_resamplers[0].process(&processed_left, &mut converted_left);
+                output_resamplers[1].process(&processed_right, &mut converted_right);
+                output_fifo.extend(converted_left.into_iter().zip(converted_right).map(|(l, r)| [l, r]));
+            }
+
+            // left and right to the first two channels, mono devices get both mixed
+            for frame in output.chunks_mut(output_channels) {
+                let [left, right] = output_fifo.pop_front().unwrap_or([0.0; 2]);
+                match frame {
+                    [mono] => *mono = (left + right) * 0.5,
+                    [l, r, rest @ ..] => {
+                        (*l, *r) = (left, right);
+                        rest.fill(0.0);
                     }
+                    [] => {}
                 }
             }
+
+            if let Ok(probe) = probe.try_lock()
+                && let Ok(mut report) = latency.try_lock()
+            {
+                let to_ms = |samples: f32| samples * 1000.0 / sample_rate as f32;
+                report.sample_rate = sample_rate;
+                report.input_ms = input_latency_us.load(Ordering::Relaxed) as f32 / 1000.0;
+                report.queue_ms = to_ms(queued_frames.load(Ordering::Relaxed) as f32)
+                    + output_fifo.len() as f32 * 1000.0 / output_rate as f32;
+                report.resample_ms = resample_ms;
+                report.pitch_ms = to_ms(dsp.latency_samples());
+                report.delay_ms = to_ms(delay_buffer.len() as f32);
+                report.output_ms = match playback_delay {
+                    Some(elapsed) => elapsed.as_secs_f32() * 1000.0,
+                    None => frames as f32 * 1000.0 / output_rate as f32,
+                };
+                report.loopback = probe.result();
+            }
+
+            let peak_db = output_peak.process(output, frames);
+            if let Ok(mut meters) = meters.try_lock() {
+                meters.loudness_lufs = dsp.loudness();
+                meters.agc_gain_db = dsp.agc_gain_db();
+                meters.output_peak_db = peak_db;
+            }
+
+            // how much of the time this buffer lasts went into producing it
+            let busy = started.elapsed().as_nanos() as u64;
+            let period = (frames as u64 * 1_000_000_000 / output_rate as u64).max(1);
+            counters.busy_ns.fetch_add(busy, Ordering::Relaxed);
+            counters.period_ns.fetch_add(period, Ordering::Relaxed);
+            // positive floats order the same as their bits
+            let load = busy as f32 / period as f32;
+            counters.peak_load.fetch_max(load.to_bits(), Ordering::Relaxed);
         },
-        err_fn,
-        None,
-    )?;
+    );
 
-    input_stream.play()?;
-    output_stream.play()?;
+    backend.start(input_callback, output_callback, errors)?;
 
-    println!("Audio streams running with configurable settings...");
-    
-    // keep streams alive until shutdown signal is received
-    while !shutdown_signal.load(Ordering::Relaxed) {
-        std::thread::sleep(std::time::Duration::from_millis(50));
+    Ok(Streams {
+        input: input_info,
+        output: output_info,
+        sample_rate,
+        buffer_size: initial_settings.buffer_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_signals::{amplitude, rising_crossings, rms, sine};
+    use virtual_clock::VirtualClock;
+
+    const FRAMES: usize = 256;
+
+    /// the whole engine on a null backend that only moves when ticked
+    struct Engine {
+        settings: Arc<Mutex<AudioSettings>>,
+        counters: Arc<Counters>,
+        clock: VirtualClock,
+        // keeps the callbacks attached and the error channel open
+        _backend: NullBackend,
+        _errors: mpsc::Receiver<String>,
+    }
+
+    impl Engine {
+        fn start(settings: AudioSettings, device_rate: Option<u32>) -> Self {
+            let (mut backend, clock) = NullBackend::manual(device_rate);
+            let shared = Shared {
+                settings: Arc::new(Mutex::new(settings)),
+                latency: Arc::new(Mutex::new(LatencyReport::default())),
+                meters: Arc::new(Mutex::new(Meters::default())),
+                counters: Arc::new(Counters::default()),
+            };
+            let (error_tx, error_rx) = mpsc::channel();
+            start_streams(&mut backend, None, None, &shared, error_tx).unwrap();
+            Self {
+                settings: shared.settings,
+                counters: shared.counters,
+                clock,
+                _backend: backend,
+                _errors: error_rx,
+            }
+        }
+
+        /// mono `input` through the engine a buffer per tick, returns the left channel
+        fn run(&self, input: &[f32]) -> Vec<f32> {
+            let mut output = vec![0.0; FRAMES * 2];
+            let mut left = Vec::with_capacity(input.len());
+            for block in input.chunks(FRAMES) {
+                let frames = block.len();
+                self.clock.tick(block, &mut output[..frames * 2]);
+                left.extend(output[..frames * 2].iter().step_by(2));
+            }
+            left
+        }
+
+        fn update(&self, change: impl FnOnce(&mut AudioSettings)) {
+            change(&mut self.settings.lock().unwrap());
+        }
+    }
+
+    #[test]
+    fn tone_passes_through_the_engine() {
+        let engine = Engine::start(AudioSettings::default(), None);
+        let input = sine(300.0, 44100, 1.0, 0.3);
+        let output = engine.run(&input);
+
+        assert_eq!(output.len(), input.len());
+        let settled = &output[22050..];
+        assert!(rms(settled) > 0.05, "output level {}", rms(settled));
+        assert!(rising_crossings(settled).abs_diff(rising_crossings(&input[22050..])) <= 1);
+        assert_eq!(engine.counters.underruns.load(Ordering::Relaxed), 0);
+        assert_eq!(engine.counters.dropped_input.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn delay_holds_back_the_output() {
+        let onset = |delay_ms| {
+            let engine = Engine::start(AudioSettings { delay_ms, ..AudioSettings::default() }, None);
+            let output = engine.run(&vec![0.5; 44100 / 4]);
+            output.iter().position(|x| x.abs() > 1e-3).unwrap()
+        };
+        let extra = onset(50.0) - onset(0.0);
+        assert!(extra.abs_diff(2205) <= 1, "{} frames", extra);
+    }
+
+    #[test]
+    fn settings_changes_reach_the_running_engine() {
+        let engine = Engine::start(AudioSettings::default(), None);
+        let input = sine(300.0, 44100, 0.5, 0.3);
+        let normal = engine.run(&input);
+
+        // the pitch shifted voice leaves 300Hz, only the dry blend stays there
+        engine.update(|settings| settings.pitch = 2.0);
+        let shifted = engine.run(&input);
+        let share = |output: &[f32]| {
+            let output = &output[11025..];
+            amplitude(output, 300.0, 44100).powi(2) / 2.0 / rms(output).powi(2)
+        };
+        assert!(share(&normal) > 0.95, "{:.3} of the energy at 300Hz before the change", share(&normal));
+        assert!(share(&shifted) < 0.5, "{:.3} of the energy at 300Hz after it", share(&shifted));
+
+        engine.update(|settings| settings.mute = true);
+        let muted = engine.run(&input);
+        assert!(rms(&muted[11025..]) < 1e-4, "{}", rms(&muted[11025..]));
+    }
+
+    #[test]
+    fn panic_silences_the_next_buffer() {
+        // at another device rate the input resampler holds some of the voice as well
+        for (device_rate, rate) in [(None, 44100), (Some(48000), 48000)] {
+            let engine = Engine::start(AudioSettings::default(), device_rate);
+            engine.run(&sine(300.0, rate, 0.25, 0.3));
+
+            engine.update(|settings| settings.panic_request += 1);
+            let output = engine.run(&sine(300.0, rate, FRAMES as f32 / rate as f32, 0.3));
+            assert!(output.iter().all(|&x| x == 0.0));
+            // nothing from before the panic comes out after it
+            let output = engine.run(&vec![0.0; rate as usize / 10]);
+            assert!(output.iter().all(|&x| x.abs() < 1e-6), "{} Hz device: {}", rate, rms(&output));
+        }
+    }
+
+    #[test]
+    fn devices_at_another_rate_are_converted() {
+        let engine = Engine::start(AudioSettings::default(), Some(48000));
+        let input = sine(300.0, 48000, 1.0, 0.3);
+        let output = engine.run(&input);
+
+        let settled = &output[24000..];
+        assert!(rms(settled) > 0.05, "output level {}", rms(settled));
+        assert!(rising_crossings(settled).abs_diff(rising_crossings(&input[24000..])) <= 1);
+    }
+
+    #[test]
+    fn file_backend_renders_a_recording() {
+        let dir = std::env::temp_dir().join(format!("montage-file-backend-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input_path, output_path) = (dir.join("voice.wav"), dir.join("out.wav"));
+        let spec = hound::WavSpec { channels: 1, sample_rate: 22050, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(&input_path, spec).unwrap();
+        for sample in sine(300.0, 22050, 0.1, 0.3) {
+            writer.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut backend = FileBackend::new(input_path, output_path.clone());
+        let shared = Shared {
+            settings: Arc::new(Mutex::new(AudioSettings::default())),
+            latency: Arc::new(Mutex::new(LatencyReport::default())),
+            meters: Arc::new(Mutex::new(Meters::default())),
+            counters: Arc::new(Counters::default()),
+        };
+        let (error_tx, _error_rx) = mpsc::channel();
+        let streams = start_streams(&mut backend, None, None, &shared, error_tx).unwrap();
+        assert_eq!(streams.input.sample_rate, 22050);
+
+        // runs in real time, the file is finished once the tail after the input is written
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let rendered = loop {
+            if let Ok(reader) = hound::WavReader::open(&output_path)
+                && reader.duration() > 0
+            {
+                break reader.into_samples::<f32>().map(Result::unwrap).collect::<Vec<f32>>();
+            }
+            assert!(Instant::now() < deadline, "output file was never finished");
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        backend.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let frames = rendered.len() / 2;
+        assert!(frames >= 22050 * 21 / 10, "{} frames", frames);
+        assert!(rms(&rendered[..4410]) > 0.05, "the voice is missing from the output");
     }
-    
-    println!("Shutting down audio streams...");
-    
-    drop(input_stream);
-    drop(output_stream);
-    
-    Ok(())
 }
@@ -0,0 +1,285 @@
+use super::{AudioBackend, InputCallback, OutputCallback, StreamRequest};
+use crate::dsp::primitives::Noise;
+use crate::status::DeviceInfo;
+use anyhow::{anyhow, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, Sample, SampleFormat, SizedSample};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// the devices picked by `open`, waiting to be started
+struct Pending {
+    input_device: cpal::Device,
+    output_device: cpal::Device,
+    input_config: cpal::SupportedStreamConfig,
+    output_config: cpal::SupportedStreamConfig,
+    buffer_size: u32,
+}
+
+/// the system's audio devices through cpal's default host
+pub struct CpalBackend {
+    host: cpal::Host,
+    pending: Option<Pending>,
+    /// the running input and output streams, dropping them stops the audio
+    streams: Vec<cpal::Stream>,
+}
+
+impl CpalBackend {
+    pub fn new() -> Self {
+        Self {
+            host: cpal::default_host(),
+            pending: None,
+            streams: Vec::new(),
+        }
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn open(&mut self, request: &StreamRequest) -> Result<(DeviceInfo, DeviceInfo)> {
+        self.stop();
+        let host = &self.host;
+        let input_device = find_device(host.input_devices(), request.input_name, host.default_input_device())
+            .ok_or_else(|| anyhow!("No input device available"))?;
+
+        let output_device = find_device(host.output_devices(), request.output_name, host.default_output_device())
+            .ok_or_else(|| anyhow!("No output device available"))?;
+
+        // the devices run at their own rate, channel count and sample format when they
+        // can't do what the engine uses internally, the engine converts in between
+        let input_config = negotiate(input_device.supported_input_configs()?.collect(), request.sample_rate)
+            .or_else(|| input_device.default_input_config().ok())
+            .ok_or_else(|| anyhow!("Input device has no usable configuration"))?;
+        let output_config = negotiate(output_device.supported_output_configs()?.collect(), request.sample_rate)
+            .or_else(|| output_device.default_output_config().ok())
+            .ok_or_else(|| anyhow!("Output device has no usable configuration"))?;
+
+        let device_info = |device: &cpal::Device, config: &cpal::SupportedStreamConfig| DeviceInfo {
+            name: device.name().unwrap_or_else(|_| "unknown device".to_string()),
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+            format: config.sample_format().to_string(),
+        };
+        let devices = (device_info(&input_device, &input_config), device_info(&output_device, &output_config));
+        self.pending = Some(Pending {
+            input_device,
+            output_device,
+            input_config,
+            output_config,
+            buffer_size: request.buffer_size,
+        });
+        Ok(devices)
+    }
+
+    fn start(&mut self, input: InputCallback, output: OutputCallback, errors: mpsc::Sender<String>) -> Result<()> {
+        let pending = self.pending.take().ok_or_else(|| anyhow!("Audio devices were not opened"))?;
+        let stream_config = |config: &cpal::SupportedStreamConfig| cpal::StreamConfig {
+            channels: config.channels(),
+            sample_rate: config.sample_rate(),
+            buffer_size: cpal::BufferSize::Fixed(pending.buffer_size),
+        };
+
+        let input_stream = open_input(
+            &pending.input_device,
+            &stream_config(&pending.input_config),
+            pending.input_config.sample_format(),
+            errors.clone(),
+            input,
+        )?;
+        let output_stream = open_output(
+            &pending.output_device,
+            &stream_config(&pending.output_config),
+            pending.output_config.sample_format(),
+            errors,
+            output,
+        )?;
+
+        input_stream.play()?;
+        output_stream.play()?;
+        self.streams = vec![input_stream, output_stream];
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.streams.clear();
+    }
+}
+
+/// the device called `name` if it is still there, otherwise the system default
+fn find_device(
+    devices: Result<impl Iterator<Item = cpal::Device>, cpal::DevicesError>,
+    name: Option<&str>,
+    default: Option<cpal::Device>,
+) -> Option<cpal::Device> {
+    let named = name.and_then(|name| {
+        devices.ok()?.find(|device| device.name().is_ok_and(|device_name| device_name == name))
+    });
+    named.or(default)
+}
+
+/// the device configuration closest to what the engine runs internally: first the
+/// rate, then stereo, then the sample format that needs the least conversion
+fn negotiate(configs: Vec<cpal::SupportedStreamConfigRange>, wanted_rate: u32) -> Option<cpal::SupportedStreamConfig> {
+    let rate = |config: &cpal::SupportedStreamConfigRange| {
+        wanted_rate.clamp(config.min_sample_rate().0, config.max_sample_rate().0)
+    };
+    configs
+        .into_iter()
+        .filter(|config| config.channels() > 0)
+        .min_by_key(|config| {
+            let format = match config.sample_format() {
+                SampleFormat::F32 => 0,
+                SampleFormat::I32 | SampleFormat::I24 => 1,
+                SampleFormat::I16 => 2,
+                SampleFormat::F64 => 3,
+                _ => 4,
+            };
+            (rate(config).abs_diff(wanted_rate), config.channels() != 2, format)
+        })
+        .map(|config| {
+            let rate = rate(&config);
+            config.with_sample_rate(cpal::SampleRate(rate))
+        })
+}
+
+/// builds the input stream in the device's own sample format, the callback always gets f32
+fn open_input<F>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    format: SampleFormat,
+    errors: mpsc::Sender<String>,
+    callback: F,
+) -> Result<cpal::Stream>
+where
+    F: FnMut(&[f32], Option<Duration>) + Send + 'static,
+{
+    match format {
+        SampleFormat::I8 => build_input::<i8, F>(device, config, errors, callback),
+        SampleFormat::I16 => build_input::<i16, F>(device, config, errors, callback),
+        SampleFormat::I24 => build_input::<cpal::I24, F>(device, config, errors, callback),
+        SampleFormat::I32 => build_input::<i32, F>(device, config, errors, callback),
+        SampleFormat::I64 => build_input::<i64, F>(device, config, errors, callback),
+        SampleFormat::U8 => build_input::<u8, F>(device, config, errors, callback),
+        SampleFormat::U16 => build_input::<u16, F>(device, config, errors, callback),
+        SampleFormat::U32 => build_input::<u32, F>(device, config, errors, callback),
+        SampleFormat::U64 => build_input::<u64, F>(device, config, errors, callback),
+        SampleFormat::F32 => build_input::<f32, F>(device, config, errors, callback),
+        SampleFormat::F64 => build_input::<f64, F>(device, config, errors, callback),
+        other => Err(anyhow!("Unsupported input sample format {}", other)),
+    }
+}
+
+fn build_input<T, F>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    errors: mpsc::Sender<String>,
+    mut callback: F,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+    F: FnMut(&[f32], Option<Duration>) + Send + 'static,
+{
+    let mut converted = Vec::new();
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], info: &cpal::InputCallbackInfo| {
+            converted.clear();
+            converted.extend(data.iter().map(|&sample| f32::from_sample(sample)));
+            let timestamp = info.timestamp();
+            callback(&converted, timestamp.callback.duration_since(&timestamp.capture));
+        },
+        move |err| {
+            let _ = errors.send(err.to_string());
+        },
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// builds the output stream in the device's own sample format, the callback always fills f32
+fn open_output<F>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    format: SampleFormat,
+    errors: mpsc::Sender<String>,
+    callback: F,
+) -> Result<cpal::Stream>
+where
+    F: FnMut(&mut [f32], Option<Duration>) + Send + 'static,
+{
+    match format {
+        SampleFormat::I8 => build_output::<i8, F>(device, config, format, errors, callback),
+        SampleFormat::I16 => build_output::<i16, F>(device, config, format, errors, callback),
+        SampleFormat::I24 => build_output::<cpal::I24, F>(device, config, format, errors, callback),
+        SampleFormat::I32 => build_output::<i32, F>(device, config, format, errors, callback),
+        SampleFormat::I64 => build_output::<i64, F>(device, config, format, errors, callback),
+        SampleFormat::U8 => build_output::<u8, F>(device, config, format, errors, callback),
+        SampleFormat::U16 => build_output::<u16, F>(device, config, format, errors, callback),
+        SampleFormat::U32 => build_output::<u32, F>(device, config, format, errors, callback),
+        SampleFormat::U64 => build_output::<u64, F>(device, config, format, errors, callback),
+        SampleFormat::F32 => build_output::<f32, F>(device, config, format, errors, callback),
+        SampleFormat::F64 => build_output::<f64, F>(device, config, format, errors, callback),
+        other => Err(anyhow!("Unsupported output sample format {}", other)),
+    }
+}
+
+fn build_output<T, F>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    format: SampleFormat,
+    errors: mpsc::Sender<String>,
+    mut callback: F,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+    F: FnMut(&mut [f32], Option<Duration>) + Send + 'static,
+{
+    let mut buffer = Vec::new();
+    let mut dither = Dither::new(format);
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+            buffer.clear();
+            buffer.resize(data.len(), 0.0);
+            let timestamp = info.timestamp();
+            callback(&mut buffer, timestamp.playback.duration_since(&timestamp.callback));
+            for (out, &sample) in data.iter_mut().zip(buffer.iter()) {
+                *out = T::from_sample(dither.apply(sample).clamp(-1.0, 1.0));
+            }
+        },
+        move |err| {
+            let _ = errors.send(err.to_string());
+        },
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// triangular (TPDF) dither of one LSB, so quantising to an integer format turns into
+/// a constant noise floor instead of distortion that follows the signal
+struct Dither {
+    lsb: f32,
+    noise: [Noise; 2],
+}
+
+impl Dither {
+    fn new(format: SampleFormat) -> Self {
+        let bits = match format {
+            SampleFormat::I24 => 24,
+            format => format.sample_size() as i32 * 8,
+        };
+        Self {
+            lsb: if format.is_float() { 0.0 } else { 2.0_f32.powi(1 - bits) },
+            noise: [Noise::new(0x6d2b_79f5), Noise::new(0x1b87_3593)],
+        }
+    }
+
+    fn apply(&mut self, sample: f32) -> f32 {
+        if self.lsb == 0.0 {
+            return sample;
+        }
+        // two uniform sources of half an LSB each add up to a triangular distribution
+        let [a, b] = &mut self.noise;
+        sample + (a.next_sample() + b.next_sample()) * 0.5 * self.lsb
+    }
+}
@@ -0,0 +1,151 @@
+use super::virtual_clock::{Ticker, VirtualClock};
+use super::{AudioBackend, InputCallback, OutputCallback, StreamRequest};
+use crate::status::DeviceInfo;
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+/// output kept after the input file ends, so echoes and reverb tails aren't cut off
+const TAIL_SECONDS: u32 = 2;
+
+type Samples = Box<dyn Iterator<Item = f32> + Send>;
+
+/// reads the voice from a wav file and writes the processed result to another as a
+/// stereo float wav, paced in real time so the controls can be used while it runs.
+/// the file is played once, after it and the tail have been written the output file
+/// is finished and the engine keeps running on silence
+pub struct FileBackend {
+    input_path: PathBuf,
+    output_path: PathBuf,
+    clock: VirtualClock,
+    opened: Option<(DeviceInfo, DeviceInfo, u32)>,
+    ticker: Option<Ticker>,
+    /// held while running, the engine takes a closed error channel for stopped streams
+    errors: Option<mpsc::Sender<String>>,
+}
+
+impl FileBackend {
+    pub fn new(input_path: PathBuf, output_path: PathBuf) -> Self {
+        Self {
+            input_path,
+            output_path,
+            clock: VirtualClock::default(),
+            opened: None,
+            ticker: None,
+            errors: None,
+        }
+    }
+
+    fn open_input(&self) -> Result<hound::WavReader<BufReader<File>>> {
+        hound::WavReader::open(&self.input_path)
+            .with_context(|| format!("Failed to open {}", self.input_path.display()))
+    }
+}
+
+/// every sample of the file as f32, whatever it is stored as
+fn samples(reader: hound::WavReader<BufReader<File>>) -> Samples {
+    let spec = reader.spec();
+    match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(reader.into_samples::<f32>().map_while(|sample| sample.ok())),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            Box::new(reader.into_samples::<i32>().map_while(move |sample| Some(sample.ok()? as f32 * scale)))
+        }
+    }
+}
+
+impl AudioBackend for FileBackend {
+    fn open(&mut self, request: &StreamRequest) -> Result<(DeviceInfo, DeviceInfo)> {
+        self.stop();
+        let spec = self.open_input()?.spec();
+        let name = |path: &PathBuf| {
+            path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+        };
+        let format = match spec.sample_format {
+            hound::SampleFormat::Float => "f32".to_string(),
+            hound::SampleFormat::Int => format!("i{}", spec.bits_per_sample),
+        };
+        let input = DeviceInfo {
+            name: name(&self.input_path),
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            format,
+        };
+        // written at the rate of the input so both sides of the engine run in step
+        let output = DeviceInfo {
+            name: name(&self.output_path),
+            sample_rate: spec.sample_rate,
+            channels: 2,
+            format: "f32".to_string(),
+        };
+        self.opened = Some((input.clone(), output.clone(), request.buffer_size));
+        Ok((input, output))
+    }
+
+    fn start(&mut self, input: InputCallback, output: OutputCallback, errors: mpsc::Sender<String>) -> Result<()> {
+        let (input_info, output_info, buffer_size) =
+            self.opened.take().ok_or_else(|| anyhow!("Audio devices were not opened"))?;
+        let mut samples = samples(self.open_input()?);
+        let writer = hound::WavWriter::create(
+            &self.output_path,
+            hound::WavSpec {
+                channels: output_info.channels,
+                sample_rate: output_info.sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            },
+        )
+        .with_context(|| format!("Failed to create {}", self.output_path.display()))?;
+
+        let mut writer: Option<hound::WavWriter<BufWriter<File>>> = Some(writer);
+        let input_done = Arc::new(AtomicBool::new(false));
+        let source_done = input_done.clone();
+        let mut tail_frames = (output_info.sample_rate * TAIL_SECONDS) as usize;
+        let output_channels = output_info.channels as usize;
+        let output_path = self.output_path.clone();
+
+        self.clock.attach(input, output);
+        self.ticker = Some(Ticker::spawn(
+            self.clock.clone(),
+            &input_info,
+            &output_info,
+            buffer_size,
+            move |buffer| {
+                for sample in buffer.iter_mut() {
+                    *sample = samples.next().unwrap_or_else(|| {
+                        source_done.store(true, Ordering::Relaxed);
+                        0.0
+                    });
+                }
+            },
+            move |buffer| {
+                let Some(file) = writer.as_mut() else { return };
+                let written = buffer.iter().try_for_each(|&sample| file.write_sample(sample));
+                if input_done.load(Ordering::Relaxed) {
+                    tail_frames = tail_frames.saturating_sub(buffer.len() / output_channels);
+                }
+                if let Err(err) = written {
+                    eprintln!("Failed to write {}: {}", output_path.display(), err);
+                    writer = None;
+                } else if tail_frames == 0
+                    && let Some(file) = writer.take()
+                    && let Err(err) = file.finalize()
+                {
+                    eprintln!("Failed to finish {}: {}", output_path.display(), err);
+                }
+            },
+        ));
+        self.errors = Some(errors);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        // dropping the ticker drops the writer, which finishes the file
+        self.ticker = None;
+        self.clock.detach();
+        self.errors = None;
+    }
+}
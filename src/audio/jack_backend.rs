@@ -0,0 +1,123 @@
+use super::{AudioBackend, InputCallback, OutputCallback, StreamRequest};
+use crate::status::DeviceInfo;
+use anyhow::{anyhow, Context, Result};
+use std::sync::mpsc;
+
+/// name of the client in the JACK graph, the ports show up as `montage:<port>`
+const CLIENT_NAME: &str = "montage";
+const INPUT_PORT: &str = "voice_in";
+const OUTPUT_PORTS: [&str; 2] = ["out_left", "out_right"];
+
+/// one client in a JACK (or PipeWire's JACK) graph with a mono input and a stereo output
+/// that are left unconnected for the session to patch. it runs at the server's rate and
+/// period and ignores the transport, the server decides when the engine runs
+pub struct JackBackend {
+    /// created by `open`, activated by `start`
+    client: Option<jack::Client>,
+    active: Option<jack::AsyncClient<Notifications, Process>>,
+}
+
+impl JackBackend {
+    pub fn new() -> Self {
+        Self { client: None, active: None }
+    }
+}
+
+/// forwards the server going away or changing rate to the engine, which reconnects
+pub struct Notifications {
+    errors: mpsc::Sender<String>,
+    sample_rate: jack::Frames,
+}
+
+impl jack::NotificationHandler for Notifications {
+    unsafe fn shutdown(&mut self, _status: jack::ClientStatus, reason: &str) {
+        let _ = self.errors.send(format!("JACK server shut down: {}", reason));
+    }
+
+    fn sample_rate(&mut self, _: &jack::Client, sample_rate: jack::Frames) -> jack::Control {
+        // the engine's converters are set up for one device rate
+        if sample_rate != self.sample_rate {
+            let _ = self.errors.send(format!("JACK sample rate changed to {} Hz", sample_rate));
+        }
+        jack::Control::Continue
+    }
+}
+
+/// runs the engine's callbacks from the JACK process cycle
+pub struct Process {
+    input_port: jack::Port<jack::AudioIn>,
+    output_ports: [jack::Port<jack::AudioOut>; 2],
+    input: InputCallback,
+    output: OutputCallback,
+    /// interleaved stereo for the output callback, grown when the period grows
+    interleaved: Vec<f32>,
+}
+
+impl jack::ProcessHandler for Process {
+    fn process(&mut self, _: &jack::Client, scope: &jack::ProcessScope) -> jack::Control {
+        let frames = scope.n_frames() as usize;
+        (self.input)(self.input_port.as_slice(scope), None);
+
+        self.interleaved.resize(frames * 2, 0.0);
+        (self.output)(&mut self.interleaved, None);
+        let [left_port, right_port] = &mut self.output_ports;
+        let (left, right) = (left_port.as_mut_slice(scope), right_port.as_mut_slice(scope));
+        for ((frame, left), right) in self.interleaved.chunks_exact(2).zip(left).zip(right) {
+            (*left, *right) = (frame[0], frame[1]);
+        }
+        jack::Control::Continue
+    }
+
+    fn buffer_size(&mut self, _: &jack::Client, frames: jack::Frames) -> jack::Control {
+        // called outside the process cycle, so the allocation doesn't hurt here
+        self.interleaved.reserve((frames as usize * 2).saturating_sub(self.interleaved.len()));
+        jack::Control::Continue
+    }
+}
+
+impl AudioBackend for JackBackend {
+    fn open(&mut self, _request: &StreamRequest) -> Result<(DeviceInfo, DeviceInfo)> {
+        self.stop();
+        let (client, _status) = jack::Client::new(CLIENT_NAME, jack::ClientOptions::NO_START_SERVER)
+            .context("Failed to connect to the JACK server")?;
+
+        // the client may have been renamed if another montage is already running
+        let device = |ports: &[&str], channels| DeviceInfo {
+            name: ports.iter().map(|port| format!("{}:{}", client.name(), port)).collect::<Vec<_>>().join(", "),
+            sample_rate: client.sample_rate(),
+            channels,
+            format: "f32".to_string(),
+        };
+        let devices = (device(&[INPUT_PORT], 1), device(&OUTPUT_PORTS, 2));
+        self.client = Some(client);
+        Ok(devices)
+    }
+
+    fn start(&mut self, input: InputCallback, output: OutputCallback, errors: mpsc::Sender<String>) -> Result<()> {
+        let client = self.client.take().ok_or_else(|| anyhow!("JACK client was not opened"))?;
+        let input_port = client.register_port(INPUT_PORT, jack::AudioIn::default())?;
+        let output_ports = [
+            client.register_port(OUTPUT_PORTS[0], jack::AudioOut::default())?,
+            client.register_port(OUTPUT_PORTS[1], jack::AudioOut::default())?,
+        ];
+        let notifications = Notifications { errors, sample_rate: client.sample_rate() };
+        let process = Process {
+            input_port,
+            output_ports,
+            input,
+            output,
+            interleaved: Vec::with_capacity(client.buffer_size() as usize * 2),
+        };
+        self.active = Some(client.activate_async(notifications, process)?);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(active) = self.active.take()
+            && let Err(err) = active.deactivate()
+        {
+            eprintln!("Failed to deactivate the JACK client: {}", err);
+        }
+        self.client = None;
+    }
+}
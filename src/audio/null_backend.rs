@@ -0,0 +1,79 @@
+use super::virtual_clock::{Ticker, VirtualClock};
+use super::{AudioBackend, InputCallback, OutputCallback, StreamRequest};
+use crate::status::DeviceInfo;
+use anyhow::{anyhow, Result};
+use std::sync::mpsc;
+
+/// runs the engine without any devices: the input is silence and the output goes
+/// nowhere. normally a ticker keeps the time, in tests the clock is advanced by hand
+pub struct NullBackend {
+    clock: VirtualClock,
+    realtime: bool,
+    /// forces the device rate instead of following the request
+    device_rate: Option<u32>,
+    opened: Option<(DeviceInfo, DeviceInfo, u32)>,
+    ticker: Option<Ticker>,
+    /// held while running, the engine takes a closed error channel for stopped streams
+    errors: Option<mpsc::Sender<String>>,
+}
+
+impl NullBackend {
+    pub fn new() -> Self {
+        Self {
+            clock: VirtualClock::default(),
+            realtime: true,
+            device_rate: None,
+            opened: None,
+            ticker: None,
+            errors: None,
+        }
+    }
+
+    /// a backend that only moves when the returned clock is ticked, with devices
+    /// at `device_rate` if given
+    #[cfg(test)]
+    pub fn manual(device_rate: Option<u32>) -> (Self, VirtualClock) {
+        let backend = Self { realtime: false, device_rate, ..Self::new() };
+        let clock = backend.clock.clone();
+        (backend, clock)
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn open(&mut self, request: &StreamRequest) -> Result<(DeviceInfo, DeviceInfo)> {
+        self.stop();
+        let device = |name: &str, channels| DeviceInfo {
+            name: name.to_string(),
+            sample_rate: self.device_rate.unwrap_or(request.sample_rate),
+            channels,
+            format: "f32".to_string(),
+        };
+        let devices = (device("null input", 1), device("null output", 2));
+        self.opened = Some((devices.0.clone(), devices.1.clone(), request.buffer_size));
+        Ok(devices)
+    }
+
+    fn start(&mut self, input: InputCallback, output: OutputCallback, errors: mpsc::Sender<String>) -> Result<()> {
+        let (input_info, output_info, buffer_size) =
+            self.opened.take().ok_or_else(|| anyhow!("Audio devices were not opened"))?;
+        self.clock.attach(input, output);
+        if self.realtime {
+            self.ticker = Some(Ticker::spawn(
+                self.clock.clone(),
+                &input_info,
+                &output_info,
+                buffer_size,
+                |input| input.fill(0.0),
+                |_| {},
+            ));
+        }
+        self.errors = Some(errors);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.ticker = None;
+        self.clock.detach();
+        self.errors = None;
+    }
+}
@@ -0,0 +1,103 @@
+use super::{InputCallback, OutputCallback};
+use crate::status::DeviceInfo;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// how far the ticker may fall behind before it gives up catching up
+const MAX_LAG_PERIODS: u32 = 4;
+
+struct Callbacks {
+    input: InputCallback,
+    output: OutputCallback,
+}
+
+/// stands in for the audio hardware of the null and file backends: every tick passes
+/// one buffer through the input and then the output callback, so the engine only
+/// moves when the clock is advanced, by a `Ticker` in real time or by hand in tests
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    callbacks: Arc<Mutex<Option<Callbacks>>>,
+}
+
+impl VirtualClock {
+    pub fn attach(&self, input: InputCallback, output: OutputCallback) {
+        if let Ok(mut callbacks) = self.callbacks.lock() {
+            *callbacks = Some(Callbacks { input, output });
+        }
+    }
+
+    pub fn detach(&self) {
+        if let Ok(mut callbacks) = self.callbacks.lock() {
+            *callbacks = None;
+        }
+    }
+
+    /// one buffer period: `input` goes to the input callback, then the output callback
+    /// fills `output`. the output is silence while nothing is attached
+    pub fn tick(&self, input: &[f32], output: &mut [f32]) {
+        match self.callbacks.lock().as_deref_mut() {
+            Ok(Some(callbacks)) => {
+                (callbacks.input)(input, None);
+                (callbacks.output)(output, None);
+            }
+            _ => output.fill(0.0),
+        }
+    }
+}
+
+/// advances a clock one buffer at a time in real time on its own thread, until dropped
+pub struct Ticker {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Ticker {
+    /// `source` fills each input buffer and `sink` gets each output buffer, both interleaved
+    /// in the channel counts of `input` and `output`
+    pub fn spawn(
+        clock: VirtualClock,
+        input: &DeviceInfo,
+        output: &DeviceInfo,
+        buffer_size: u32,
+        mut source: impl FnMut(&mut [f32]) + Send + 'static,
+        mut sink: impl FnMut(&[f32]) + Send + 'static,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let frames = buffer_size.max(1) as usize;
+        let mut input_buffer = vec![0.0; frames * input.channels as usize];
+        let mut output_buffer = vec![0.0; frames * output.channels as usize];
+        let period = Duration::from_secs_f64(frames as f64 / output.sample_rate as f64);
+
+        let thread = std::thread::spawn(move || {
+            let mut deadline = Instant::now();
+            while !thread_stop.load(Ordering::Relaxed) {
+                source(&mut input_buffer);
+                clock.tick(&input_buffer, &mut output_buffer);
+                sink(&output_buffer);
+
+                deadline += period;
+                let now = Instant::now();
+                if now < deadline {
+                    std::thread::sleep(deadline - now);
+                } else if now - deadline > period * MAX_LAG_PERIODS {
+                    // too slow to keep up, drop the lost time instead of rushing through it
+                    deadline = now;
+                }
+            }
+        });
+
+        Self { stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
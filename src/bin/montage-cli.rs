@@ -0,0 +1,129 @@
+//! runs the engine without a window, e.g. on a headless box or to render a file:
+//! `montage-cli --input-file voice.wav --output-file out.wav --pitch 1.5 --seconds 10`,
+//! or `montage-cli --osc 9000` to be driven by show control
+
+use anyhow::{anyhow, bail, Context, Result};
+use montage::audio::BackendArgs;
+use montage::control::{self, ControlServer};
+use montage::midi::{self, MidiControl, MidiInput};
+use montage::osc::{self, OscServer};
+use montage::presets::PresetStore;
+use montage::params::Param;
+use montage::status::StreamState;
+use montage::{Engine, EngineConfig};
+use std::io::BufRead;
+use std::time::Duration;
+
+/// settings that can be given on the command line
+const PARAM_FLAGS: [(&str, Param); 4] = [
+    ("--pitch", Param::Pitch),
+    ("--delay", Param::Delay),
+    ("--input-gain", Param::InputGain),
+    ("--volume", Param::OutputVolume),
+];
+
+fn main() -> Result<()> {
+    let mut backend = BackendArgs::default();
+    let mut config = EngineConfig::default();
+    let mut seconds = None;
+    let mut osc_bind = None;
+    let mut control_bind = None;
+    let mut midi_port = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if backend.take(&arg, &mut args) {
+            continue;
+        }
+        let mut value = || -> Result<f32> {
+            let value = args.next().ok_or_else(|| anyhow!("{} needs a value", arg))?;
+            value.parse().with_context(|| format!("{} needs a number, not {}", arg, value))
+        };
+        if let Some((_, param)) = PARAM_FLAGS.iter().find(|(flag, _)| *flag == arg) {
+            param.set(&mut config.settings, value()?);
+        } else if arg == "--osc" {
+            let bind = args.next().ok_or_else(|| anyhow!("--osc needs a port or address"))?;
+            osc_bind = Some(osc::parse_bind(&bind)?);
+        } else if arg == "--control" {
+            let bind = args.next().ok_or_else(|| anyhow!("--control needs a port or address"))?;
+            control_bind = Some(control::parse_bind(&bind)?);
+        } else if arg == "--midi" {
+            midi_port = Some(args.next().ok_or_else(|| anyhow!("--midi needs the name or number of an input"))?);
+        } else if arg == "--list-midi" {
+            for (index, name) in midi::input_ports()?.iter().enumerate() {
+                println!("{}: {}", index, name);
+            }
+            return Ok(());
+        } else if arg == "--seconds" {
+            let value = value()?;
+            seconds = Some(Duration::try_from_secs_f32(value.max(0.0)).map_err(|_| anyhow!("--seconds can't be {}", value))?);
+        } else {
+            bail!("Unknown argument {}", arg);
+        }
+    }
+    config.backend = backend.backend()?;
+
+    let engine = Engine::start(config);
+    let osc_server = osc_bind.map(|bind| OscServer::start(&engine, bind)).transpose()?;
+    if let Some(server) = &osc_server {
+        eprintln!("OSC: listening on {}", server.local_addr());
+    }
+    let control_server = control_bind.map(|bind| ControlServer::start(&engine, bind)).transpose()?;
+    if let Some(server) = &control_server {
+        eprintln!("Control: listening on http://{}", server.local_addr());
+    }
+    // plays through the mappings saved by the app, learning needs the window
+    let midi_input = match midi_port {
+        Some(port) => {
+            let store = match PresetStore::default_path().map(|path| PresetStore::load(&path)).transpose() {
+                Ok(store) => store.unwrap_or_default(),
+                Err(e) => {
+                    eprintln!("Failed to load presets: {:#}", e);
+                    PresetStore::default()
+                }
+            };
+            let control = std::sync::Arc::new(std::sync::Mutex::new(MidiControl::new(store.midi)));
+            let input = MidiInput::start(&engine, &port, control)?;
+            eprintln!("MIDI: listening to {}", input.port_name());
+            Some(input)
+        }
+        None => None,
+    };
+    let reports = engine.subscribe();
+    // runs for the given time, or until enter is pressed or stdin is closed
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    match seconds {
+        Some(seconds) => {
+            std::thread::spawn(move || {
+                std::thread::sleep(seconds);
+                let _ = done_tx.send(());
+            });
+        }
+        None => {
+            eprintln!("Running, press enter to stop");
+            std::thread::spawn(move || {
+                let _ = std::io::stdin().lock().lines().next();
+                let _ = done_tx.send(());
+            });
+        }
+    }
+
+    let mut state = StreamState::Starting;
+    while done_rx.try_recv().is_err() {
+        let Ok(status) = reports.recv_timeout(Duration::from_millis(100)) else { continue };
+        if status.state != state {
+            state = status.state.clone();
+            eprintln!("{}", state);
+            if state == StreamState::Running
+                && let (Some(input), Some(output)) = (&status.input, &status.output)
+            {
+                eprintln!("  input:  {}\n  output: {}", input, output);
+            }
+        }
+    }
+
+    drop(midi_input);
+    drop(control_server);
+    drop(osc_server);
+    engine.stop();
+    Ok(())
+}
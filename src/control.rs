@@ -0,0 +1,602 @@
+//! optional local control API: a small HTTP server on localhost speaking JSON, for
+//! stream deck tools and shell scripts, and the `montage ctl` client for it.
+//!
+//! there is no authentication, so anything a web page could send is refused: requests
+//! with an `Origin`, a `Host` other than localhost (DNS rebinding), and changes that
+//! aren't `Content-Type: application/json`, which browsers can't send without asking
+//!
+//! - `GET /settings` is every field of `AudioSettings`, `PATCH /settings` with any part
+//!   of it changes just those fields, values are clamped to the ranges of the sliders
+//! - `GET /presets` lists the presets, `POST /preset` with `{"name": ...}` or `{"index": ...}` picks one
+//! - `POST /bypass` with `{"enabled": ...}` sets bypass, without a body it toggles
+//! - `POST /panic` clears every buffer
+//! - `GET /status` and `GET /levels` report the engine and the meters
+
+use crate::engine::Engine;
+use crate::meters::Meters;
+use crate::params;
+use crate::presets;
+use crate::settings::{AudioSettings, MAX_BUFFER_SIZE, MIN_BUFFER_SIZE};
+use crate::status::StatusFeed;
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// port used when only a host is given, and by `montage ctl` by default
+pub const DEFAULT_PORT: u16 = 9100;
+/// how often the server looks for new connections and for being stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// a client that stalls longer than this is dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+/// larger requests are refused, a whole settings object is a few KB
+const MAX_BODY: usize = 64 * 1024;
+/// longest request or header line, and the most headers, before the request is refused
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+/// names a browser on this machine uses for it, any other `Host` came through a rebound name
+const LOCAL_HOSTS: [&str; 4] = ["localhost", "127.0.0.1", "[::1]", "::1"];
+
+/// `9100`, `127.0.0.1:9100` or `localhost`. anything that isn't loopback is refused,
+/// there is no authentication
+pub fn parse_bind(text: &str) -> Result<SocketAddr> {
+    let addr = if let Ok(port) = text.parse::<u16>() {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    } else {
+        let with_port = if text.contains(':') { text.to_string() } else { format!("{}:{}", text, DEFAULT_PORT) };
+        with_port
+            .to_socket_addrs()
+            .with_context(|| format!("{} is not an address to listen on", text))?
+            .next()
+            .with_context(|| format!("{} did not resolve", text))?
+    };
+    if !addr.ip().is_loopback() {
+        bail!("The control API only listens on localhost, not on {}", addr);
+    }
+    Ok(addr)
+}
+
+/// the listening socket and its thread, stops when dropped
+pub struct ControlServer {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    pub fn start(engine: &Engine, bind: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(bind).with_context(|| format!("Could not listen for control on {}", bind))?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let api = Api { settings: engine.shared_settings(), meters: engine.shared_meters(), status: engine.status_feed() };
+        let thread = {
+            let shutdown = shutdown.clone();
+            std::thread::spawn(move || {
+                while !shutdown.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = api.serve(stream) {
+                                eprintln!("Control: {:#}", e);
+                            }
+                        }
+                        Err(_) => std::thread::sleep(POLL_INTERVAL),
+                    }
+                }
+            })
+        };
+        Ok(Self { local_addr, shutdown, thread: Some(thread) })
+    }
+
+    /// where the server listens, with the port the system picked for port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+/// what the server needs from a request
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    content_type: Option<String>,
+    body: Option<Value>,
+}
+
+impl Request {
+    /// only tools on this machine get through, never a web page
+    fn check(&self) -> Result<(), Failure> {
+        if let Some(origin) = &self.origin {
+            return Err(Failure(403, format!("requests from web pages ({}) are not accepted", origin)));
+        }
+        match &self.host {
+            Some(host) if LOCAL_HOSTS.contains(&strip_port(host)) => {}
+            Some(host) => return Err(Failure(403, format!("{} is not localhost", host))),
+            None => return Err(Failure::bad_request("the request has no Host")),
+        }
+        let json = self.content_type.as_deref().is_some_and(|content_type| {
+            content_type.split(';').next().is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+        });
+        if self.method != "GET" && !json {
+            return Err(Failure(415, "changes need Content-Type: application/json".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// `localhost:9100` to `localhost`, `[::1]:9100` to `[::1]`
+fn strip_port(host: &str) -> &str {
+    let host = host.trim();
+    match host.rfind(':') {
+        Some(colon) if !host.ends_with(']') && (host.starts_with('[') || host[..colon].find(':').is_none()) => &host[..colon],
+        _ => host,
+    }
+}
+
+/// an answer that went wrong, with the HTTP status to send it with
+struct Failure(u16, String);
+
+impl Failure {
+    fn bad_request(message: impl std::fmt::Display) -> Self {
+        Failure(400, message.to_string())
+    }
+}
+
+struct Api {
+    settings: Arc<Mutex<AudioSettings>>,
+    meters: Arc<Mutex<Meters>>,
+    status: StatusFeed,
+}
+
+impl Api {
+    /// one request per connection, the answer is always JSON
+    fn serve(&self, stream: TcpStream) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut reader = BufReader::new(stream);
+        let (code, body) = match read_request(&mut reader) {
+            Ok(request) => match request.check().and_then(|()| self.answer(&request.method, &request.path, request.body)) {
+                Ok(value) => (200, value),
+                Err(Failure(code, error)) => (code, json!({ "error": error })),
+            },
+            Err(e) => (400, json!({ "error": format!("{:#}", e) })),
+        };
+        write_response(reader.get_mut(), code, &body)?;
+        // read what is left of a refused request, closing with unread data would reset
+        // the connection before the client got the answer
+        let stream = reader.get_mut();
+        stream.shutdown(Shutdown::Write)?;
+        let _ = std::io::copy(&mut stream.take(MAX_BODY as u64), &mut std::io::sink());
+        Ok(())
+    }
+
+    fn answer(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value, Failure> {
+        match (method, path) {
+            ("GET", "/settings") => Ok(self.settings_json()),
+            ("PATCH", "/settings") => {
+                let patch = body.ok_or_else(|| Failure::bad_request("send the fields to change as a JSON object"))?;
+                self.patch_settings(patch)?;
+                Ok(self.settings_json())
+            }
+            ("GET", "/presets") => {
+                let available = presets::available();
+                let current = self.with_settings(|settings| presets::current(&available, settings).map(|preset| preset.name.clone()));
+                let names: Vec<&str> = available.iter().map(|preset| preset.name.as_str()).collect();
+                Ok(json!({ "presets": names, "current": current }))
+            }
+            ("POST", "/preset") => {
+                let body = body.unwrap_or(Value::Null);
+                let key = match (body.get("name"), body.get("index")) {
+                    (Some(Value::String(name)), _) => name.clone(),
+                    (_, Some(Value::Number(index))) => index.to_string(),
+                    _ => return Err(Failure::bad_request("send {\"name\": ...} or {\"index\": ...}")),
+                };
+                let available = presets::available();
+                let preset = presets::find(&available, &key).ok_or_else(|| Failure(404, format!("there is no preset {}", key)))?;
+                self.with_settings(|settings| preset.apply(settings));
+                Ok(json!({ "preset": preset.name }))
+            }
+            ("POST", "/bypass") => {
+                let enabled = match body.as_ref().map(|body| body.get("enabled")) {
+                    None => None,
+                    Some(Some(Value::Bool(enabled))) => Some(*enabled),
+                    Some(_) => return Err(Failure::bad_request("send {\"enabled\": true or false}, or nothing to toggle")),
+                };
+                let bypass = self.with_settings(|settings| {
+                    settings.bypass = enabled.unwrap_or(!settings.bypass);
+                    settings.bypass
+                });
+                Ok(json!({ "bypass": bypass }))
+            }
+            ("POST", "/panic") => {
+                self.with_settings(|settings| settings.panic_request = settings.panic_request.wrapping_add(1));
+                Ok(json!({ "panic": true }))
+            }
+            ("GET", "/status") => {
+                let status = self.status.latest();
+                let device = |info: &Option<crate::status::DeviceInfo>| info.as_ref().map(|info| info.to_string());
+                Ok(json!({
+                    "state": status.state.to_string(),
+                    "running": status.state == crate::status::StreamState::Running,
+                    "input": device(&status.input),
+                    "output": device(&status.output),
+                    "sample_rate": status.sample_rate,
+                    "buffer_size": status.buffer_size,
+                    "dropped_inputs": status.dropped_inputs,
+                    "underruns": status.underruns,
+                    "cpu_load": status.cpu_load,
+                    "cpu_peak": status.cpu_peak,
+                    "latency_ms": status.latency.total_ms(),
+                    "last_error": status.last_error,
+                }))
+            }
+            ("GET", "/levels") => {
+                let meters = self.meters.lock().map(|meters| *meters).unwrap_or_default();
+                Ok(json!({
+                    "input_peak_db": meters.input_peak_db,
+                    "output_peak_db": meters.output_peak_db,
+                    "loudness_lufs": meters.loudness_lufs,
+                    "agc_gain_db": meters.agc_gain_db,
+                }))
+            }
+            (_, "/settings" | "/presets" | "/preset" | "/bypass" | "/panic" | "/status" | "/levels") => {
+                Err(Failure(405, format!("{} is not used on {}", method, path)))
+            }
+            _ => Err(Failure(404, format!("nothing at {}", path))),
+        }
+    }
+
+    fn with_settings<T>(&self, f: impl FnOnce(&mut AudioSettings) -> T) -> T {
+        let mut settings = self.settings.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut settings)
+    }
+
+    fn settings_json(&self) -> Value {
+        self.with_settings(|settings| serde_json::to_value(&*settings).unwrap_or_default())
+    }
+
+    /// the fields in `patch` replace those of the settings, the rest stays as it is
+    fn patch_settings(&self, patch: Value) -> Result<(), Failure> {
+        self.with_settings(|settings| {
+            let mut merged = serde_json::to_value(&*settings).map_err(|e| Failure(500, e.to_string()))?;
+            merge(&mut merged, patch, "").map_err(Failure::bad_request)?;
+            let mut patched: AudioSettings = serde_json::from_value(merged).map_err(Failure::bad_request)?;
+            // clamped like the sliders, a stage may not cope with a value outside their range
+            params::clamp_all(&mut patched);
+            patched.buffer_size = patched.buffer_size.clamp(MIN_BUFFER_SIZE, MAX_BUFFER_SIZE);
+            *settings = patched;
+            Ok(())
+        })
+    }
+}
+
+/// writes `patch` over `target`, objects field by field. unknown fields are an error so
+/// a typo isn't silently ignored
+fn merge(target: &mut Value, patch: Value, path: &str) -> Result<(), String> {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                let field = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                let existing = target.get_mut(&key).ok_or_else(|| format!("there is no setting {}", field))?;
+                merge(existing, value, &field)?;
+            }
+            Ok(())
+        }
+        (target, patch) => {
+            *target = patch;
+            Ok(())
+        }
+    }
+}
+
+/// one line of the request, refused when longer than `MAX_LINE`
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<usize> {
+    line.clear();
+    let len = reader.take(MAX_LINE as u64 + 1).read_line(line)?;
+    if len > MAX_LINE {
+        bail!("request line too long");
+    }
+    Ok(len)
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request> {
+    let mut line = String::new();
+    read_line(reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else { bail!("not an HTTP request") };
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.trim_end_matches('/').to_string(),
+        host: None,
+        origin: None,
+        content_type: None,
+        body: None,
+    };
+
+    let mut length = 0;
+    for headers in 0.. {
+        if read_line(reader, &mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if headers == MAX_HEADERS {
+            bail!("too many headers");
+        }
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim().to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.parse().context("bad Content-Length")?,
+            "host" => request.host = Some(value),
+            "origin" => request.origin = Some(value),
+            "content-type" => request.content_type = Some(value),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY {
+        bail!("request body too large");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    if !body.iter().all(u8::is_ascii_whitespace) {
+        request.body = Some(serde_json::from_slice(&body).context("body is not JSON")?);
+    }
+    Ok(request)
+}
+
+fn write_response(stream: &mut impl Write, code: u16, body: &Value) -> Result<()> {
+    let reason = match code {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        _ => "Internal Server Error",
+    };
+    let body = serde_json::to_string(body)?;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// sends one request to a control server and returns its answer, errors the server
+/// reports come back as errors with its message
+pub fn request(addr: SocketAddr, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+    let mut stream = TcpStream::connect_timeout(&addr, CLIENT_TIMEOUT)
+        .with_context(|| format!("Could not reach montage on {}, is it running with --control?", addr))?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let body = body.map(|body| body.to_string()).unwrap_or_default();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    )?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(|| anyhow!("Incomplete answer from {}", addr))?;
+    let code: u16 = head.split_whitespace().nth(1).and_then(|code| code.parse().ok()).unwrap_or(0);
+    let value: Value = serde_json::from_str(body).with_context(|| format!("Answer from {} is not JSON", addr))?;
+    if code != 200 {
+        bail!("{}", value.get("error").and_then(Value::as_str).unwrap_or(body));
+    }
+    Ok(value)
+}
+
+/// `{"a": {"b": value}}` for `a.b`
+fn nested(path: &str, value: Value) -> Value {
+    path.rsplit('.').fold(value, |value, key| Value::Object(Map::from_iter([(key.to_string(), value)])))
+}
+
+/// the `montage ctl` client:
+/// `montage ctl [--addr host:port] get [field.path] | set <field.path> <value> | preset [name|index]
+/// | bypass [on|off] | panic | status | levels`
+pub fn run_ctl(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut addr = SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT));
+    let mut command = args.next();
+    if command.as_deref() == Some("--addr") {
+        addr = parse_bind(&args.next().ok_or_else(|| anyhow!("--addr needs a port or address"))?)?;
+        command = args.next();
+    }
+    let Some(command) = command else {
+        bail!("Usage: montage ctl [--addr host:port] get|set|preset|bypass|panic|status|levels");
+    };
+    let rest: Vec<String> = args.collect();
+    let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+
+    let answer = match (command.as_str(), &rest[..]) {
+        ("get", []) => request(addr, "GET", "/settings", None)?,
+        ("get", [field]) => {
+            let settings = request(addr, "GET", "/settings", None)?;
+            let pointer = format!("/{}", field.replace('.', "/"));
+            settings.pointer(&pointer).cloned().ok_or_else(|| anyhow!("There is no setting {}", field))?
+        }
+        ("set", [field, value]) => {
+            // JSON when it parses, like `true`, `1.5` or `{"enabled": true}`, a plain string otherwise
+            let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+            let settings = request(addr, "PATCH", "/settings", Some(&nested(field, value)))?;
+            let pointer = format!("/{}", field.replace('.', "/"));
+            settings.pointer(&pointer).cloned().unwrap_or(settings)
+        }
+        ("preset", []) => request(addr, "GET", "/presets", None)?,
+        ("preset", [key]) => {
+            let body = match key.parse::<u64>() {
+                Ok(index) => json!({ "index": index }),
+                Err(_) => json!({ "name": key }),
+            };
+            request(addr, "POST", "/preset", Some(&body))?
+        }
+        ("bypass", []) => request(addr, "POST", "/bypass", None)?,
+        ("bypass", [state]) => {
+            let enabled = match *state {
+                "on" | "true" | "1" => true,
+                "off" | "false" | "0" => false,
+                _ => bail!("bypass takes on or off, or nothing to toggle"),
+            };
+            request(addr, "POST", "/bypass", Some(&json!({ "enabled": enabled })))?
+        }
+        ("panic", []) => request(addr, "POST", "/panic", None)?,
+        ("status", []) => request(addr, "GET", "/status", None)?,
+        ("levels", []) => request(addr, "GET", "/levels", None)?,
+        _ => bail!("Unknown ctl command: {} {}", command, rest.join(" ")),
+    };
+    println!("{}", serde_json::to_string_pretty(&answer)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::BackendKind;
+    use crate::engine::EngineConfig;
+    use crate::settings::SampleRate;
+
+    #[test]
+    fn settings_are_read_and_written_over_http() {
+        let engine = Engine::start(EngineConfig { backend: BackendKind::Null, ..EngineConfig::default() });
+        let server = ControlServer::start(&engine, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr();
+
+        let settings: AudioSettings = serde_json::from_value(request(addr, "GET", "/settings", None).unwrap()).unwrap();
+        assert_eq!(settings, engine.settings());
+
+        let patch = json!({ "pitch": 5.0, "sample_rate": 48000, "effects": { "echo": { "enabled": true } } });
+        request(addr, "PATCH", "/settings", Some(&patch)).unwrap();
+        let settings = engine.settings();
+        assert_eq!(settings.pitch, 2.0, "pitch should be clamped like the slider");
+        assert_eq!(settings.sample_rate, SampleRate::Rate48000);
+        assert!(settings.effects.echo.enabled);
+        assert_eq!(settings.effects.reverb, AudioSettings::default().effects.reverb);
+
+        // out of range effect values are clamped before the audio thread sees them
+        let patch = json!({ "effects": { "agc": { "enabled": true, "max_gain_db": -30.0 }, "echo": { "feedback": 4.0 } } });
+        request(addr, "PATCH", "/settings", Some(&patch)).unwrap();
+        let settings = engine.settings();
+        assert_eq!(settings.effects.agc.max_gain_db, 0.0);
+        assert_eq!(settings.effects.echo.feedback, 0.95);
+        assert!(settings.effects.agc.enabled);
+        let mut defaults = AudioSettings::default();
+        params::clamp_all(&mut defaults);
+        assert_eq!(defaults, AudioSettings::default(), "the defaults are all in range");
+
+        let error = request(addr, "PATCH", "/settings", Some(&json!({ "efects": {} }))).unwrap_err();
+        assert!(error.to_string().contains("efects"), "{}", error);
+        assert!(request(addr, "PATCH", "/settings", Some(&json!({ "sample_rate": 12345 }))).is_err());
+        assert!(request(addr, "GET", "/nothing", None).is_err());
+
+        assert_eq!(request(addr, "POST", "/bypass", None).unwrap(), json!({ "bypass": true }));
+        assert!(engine.settings().bypass);
+        request(addr, "POST", "/bypass", Some(&json!({ "enabled": false }))).unwrap();
+        assert!(!engine.settings().bypass);
+
+        request(addr, "POST", "/preset", Some(&json!({ "name": "radio" }))).unwrap();
+        assert_eq!(engine.settings().effects.character, crate::dsp::character::VoiceCharacter::Radio);
+        assert_eq!(request(addr, "GET", "/presets", None).unwrap()["current"], json!("Radio"));
+
+        assert!(request(addr, "GET", "/status", None).unwrap()["state"].is_string());
+        assert!(request(addr, "GET", "/levels", None).unwrap()["input_peak_db"].is_number());
+
+        server.stop();
+        engine.stop();
+    }
+
+    /// status line of the answer to a hand-written request
+    fn raw(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn requests_a_web_page_could_send_are_refused() {
+        let engine = Engine::start(EngineConfig { backend: BackendKind::Null, ..EngineConfig::default() });
+        let server = ControlServer::start(&engine, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr();
+        let post = |headers: &str| {
+            let body = r#"{"mute":true}"#;
+            raw(addr, &format!("PATCH /settings HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}", headers, body.len(), body))
+        };
+
+        assert!(post("Host: 127.0.0.1\r\nContent-Type: text/plain\r\n").contains(" 415 "));
+        assert!(post("Host: localhost:9100\r\nContent-Type: application/json\r\nOrigin: https://example.com\r\n").contains(" 403 "));
+        assert!(post("Host: rebound.example.com:9100\r\nContent-Type: application/json\r\n").contains(" 403 "));
+        assert!(raw(addr, "GET /status HTTP/1.1\r\nHost: rebound.example.com\r\n\r\n").contains(" 403 "));
+        let long = format!("GET /status HTTP/1.1\r\nHost: localhost\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(raw(addr, &long).contains(" 400 "));
+        assert!(!engine.settings().mute);
+
+        let post_settings = r#"{"mute":true}"#;
+        let answer = raw(
+            addr,
+            &format!(
+                "POST /settings HTTP/1.1\r\nHost: [::1]:9100\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                post_settings.len(),
+                post_settings
+            ),
+        );
+        assert!(answer.contains(" 405 "), "{}", answer);
+        assert!(post("Host: [::1]:9100\r\nContent-Type: application/json; charset=utf-8\r\n").contains(" 200 "));
+        assert!(engine.settings().mute);
+
+        server.stop();
+        engine.stop();
+    }
+
+    #[test]
+    fn ports_are_stripped_from_hosts() {
+        assert_eq!(strip_port("localhost:9100"), "localhost");
+        assert_eq!(strip_port("127.0.0.1"), "127.0.0.1");
+        assert_eq!(strip_port("[::1]:9100"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+        assert_eq!(strip_port("::1"), "::1");
+    }
+
+    #[test]
+    fn only_loopback_addresses_are_accepted() {
+        assert_eq!(parse_bind("9200").unwrap(), "127.0.0.1:9200".parse().unwrap());
+        assert_eq!(parse_bind("[::1]:9201").unwrap(), "[::1]:9201".parse().unwrap());
+        assert!(parse_bind("0.0.0.0:9100").is_err());
+    }
+
+    #[test]
+    fn dotted_fields_nest() {
+        assert_eq!(nested("effects.echo.enabled", json!(true)), json!({ "effects": { "echo": { "enabled": true } } }));
+        assert_eq!(nested("pitch", json!(1.5)), json!({ "pitch": 1.5 }));
+    }
+}
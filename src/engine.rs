@@ -0,0 +1,188 @@
+use crate::audio::{self, BackendKind};
+use crate::meters::Meters;
+use crate::params::{Param, Switch};
+use crate::settings::AudioSettings;
+use crate::status::{EngineStatus, StatusFeed};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// what the engine starts with
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfig {
+    pub backend: BackendKind,
+    pub settings: AudioSettings,
+}
+
+/// the voice processing running on its own audio thread, until stopped or dropped.
+/// settings can be changed from any thread while it runs, the audio thread picks
+/// them up at its next buffer
+pub struct Engine {
+    settings: Arc<Mutex<AudioSettings>>,
+    meters: Arc<Mutex<Meters>>,
+    status: StatusFeed,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Engine {
+    pub fn start(config: EngineConfig) -> Self {
+        let settings = Arc::new(Mutex::new(config.settings));
+        let meters = Arc::new(Mutex::new(Meters::default()));
+        let status = StatusFeed::default();
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let (settings, meters, status, shutdown) = (settings.clone(), meters.clone(), status.clone(), shutdown.clone());
+            std::thread::spawn(move || {
+                if let Err(e) = audio::run_audio(config.backend, settings, meters, status, shutdown) {
+                    eprintln!("Audio error: {}", e);
+                }
+            })
+        };
+
+        Self { settings, meters, status, shutdown, thread: Some(thread) }
+    }
+
+    pub fn settings(&self) -> AudioSettings {
+        self.settings.lock().map(|settings| settings.clone()).unwrap_or_default()
+    }
+
+    pub fn update(&self, change: impl FnOnce(&mut AudioSettings)) {
+        if let Ok(mut settings) = self.settings.lock() {
+            change(&mut settings);
+        }
+    }
+
+    /// the settings themselves, for front-ends that keep their own copy and write it back whole
+    pub fn shared_settings(&self) -> Arc<Mutex<AudioSettings>> {
+        self.settings.clone()
+    }
+
+    pub fn param(&self, param: Param) -> ParamHandle {
+        ParamHandle { param, settings: self.settings.clone() }
+    }
+
+    pub fn switch(&self, switch: Switch) -> SwitchHandle {
+        SwitchHandle { switch, settings: self.settings.clone() }
+    }
+
+    /// clear every buffer and filter at once, like the panic button
+    pub fn panic(&self) {
+        self.update(|settings| settings.panic_request = settings.panic_request.wrapping_add(1));
+    }
+
+    pub fn meters(&self) -> Meters {
+        self.meters.lock().map(|meters| *meters).unwrap_or_default()
+    }
+
+    pub fn shared_meters(&self) -> Arc<Mutex<Meters>> {
+        self.meters.clone()
+    }
+
+    /// the most recent report of the audio thread
+    pub fn status(&self) -> EngineStatus {
+        self.status.latest()
+    }
+
+    pub(crate) fn status_feed(&self) -> StatusFeed {
+        self.status.clone()
+    }
+
+    /// every report of the audio thread from now on, about 20 a second
+    pub fn subscribe(&self) -> Receiver<EngineStatus> {
+        self.status.subscribe()
+    }
+
+    /// stops the audio and waits for the audio thread to finish
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+/// one continuous parameter of a running engine, cheap to clone and hand to another thread
+#[derive(Clone)]
+pub struct ParamHandle {
+    param: Param,
+    settings: Arc<Mutex<AudioSettings>>,
+}
+
+impl ParamHandle {
+    pub fn param(&self) -> Param {
+        self.param
+    }
+
+    pub fn get(&self) -> f32 {
+        self.settings.lock().map(|settings| self.param.get(&settings)).unwrap_or_default()
+    }
+
+    /// clamped to the parameter's range
+    pub fn set(&self, value: f32) {
+        if let Ok(mut settings) = self.settings.lock() {
+            self.param.set(&mut settings, value);
+        }
+    }
+}
+
+/// one on/off switch of a running engine
+#[derive(Clone)]
+pub struct SwitchHandle {
+    switch: Switch,
+    settings: Arc<Mutex<AudioSettings>>,
+}
+
+impl SwitchHandle {
+    pub fn switch(&self) -> Switch {
+        self.switch
+    }
+
+    pub fn get(&self) -> bool {
+        self.settings.lock().map(|settings| self.switch.get(&settings)).unwrap_or_default()
+    }
+
+    pub fn set(&self, enabled: bool) {
+        if let Ok(mut settings) = self.settings.lock() {
+            self.switch.set(&mut settings, enabled);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::StreamState;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn engine_runs_headless_and_takes_changes() {
+        let engine = Engine::start(EngineConfig { backend: BackendKind::Null, ..EngineConfig::default() });
+        let reports = engine.subscribe();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while engine.status().state != StreamState::Running {
+            assert!(Instant::now() < deadline, "engine never started: {}", engine.status().state);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(reports.recv_timeout(Duration::from_secs(1)).is_ok());
+
+        let pitch = engine.param(Param::Pitch);
+        pitch.set(5.0);
+        assert_eq!(pitch.get(), 2.0);
+        engine.switch(Switch::Echo).set(true);
+        assert!(engine.settings().effects.echo.enabled);
+        engine.stop();
+    }
+}
@@ -1,73 +1,100 @@
-use iced::widget::{Column, Container, Image, PickList, Row, Slider, Stack, Text};
+use montage::dsp::character::VoiceCharacter;
+use montage::dsp::echo::NoteDivision;
+use montage::dsp::harmonizer::MAX_VOICES;
+use montage::dsp::oversample::{Oversampling, OversamplingQuality};
+use montage::dsp::vocoder::{CarrierWave, Chord};
+use montage::latency::{LatencyReport, LoopbackState};
+use montage::meters::Meters;
+use montage::midi::{Curve, MidiControl, MidiMap};
+use montage::params::{note_name, Param, Switch};
+use montage::presets::{self, Preset, PresetStore};
+use montage::settings::{AudioSettings, SampleRate, MAX_BUFFER_SIZE, MIN_BUFFER_SIZE};
+use montage::status::{EngineStatus, StreamState};
+use iced::widget::{Button, Column, Container, Image, MouseArea, PickList, Row, Scrollable, Slider, Stack, Text, TextInput, Toggler};
 use iced::{Element, Length, Alignment, Settings, Task, Color, Background, Border, Shadow, Vector};
 use anyhow::Result;
 use iced_wgpu::Renderer;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Copy)]
+/// status reports kept for the load graph, the audio thread sends about 20 a second
+const LOAD_HISTORY: usize = 200;
+/// height of the load graph in pixels, the top is 150% load
+const LOAD_GRAPH_HEIGHT: f32 = 60.0;
+const LOAD_GRAPH_MAX: f32 = 1.5;
+/// peak load above which the buffer is considered too small for the chain
+const LOAD_WARNING: f32 = 0.9;
+/// key that leaves the pitch alone when keys start playing it, middle C
+const DEFAULT_KEY_ROOT: u8 = 60;
+
+#[derive(Debug, Clone)]
 pub enum Message {
     PitchChanged(f32),
     SampleRateChanged(SampleRate),
     BufferSizeChanged(f32),
     DelayChanged(f32),
+    ParamChanged(Param, f32),
+    SwitchToggled(Switch, bool),
+    PresetSelected(Box<Preset>),
+    CharacterChanged(VoiceCharacter),
+    VocoderCarrierChanged(CarrierWave),
+    VocoderChordChanged(Chord),
+    EchoDivisionChanged(NoteDivision),
+    OversamplingChanged(Oversampling),
+    OversamplingQualityChanged(OversamplingQuality),
+    PresetNameChanged(String),
+    SavePreset,
+    /// map the next MIDI control that moves to this parameter
+    MidiLearn(Param),
+    MidiLearnCancel,
+    MidiMinChanged(usize, f32),
+    MidiMaxChanged(usize, f32),
+    /// a range or key root slider was let go, the mappings are saved only then
+    MidiSliderReleased,
+    MidiCurveChanged(usize, Curve),
+    MidiMappingRemoved(usize),
+    KeyPitchToggled(bool),
+    KeyRootChanged(f32),
+    MeasureLoopback,
+    Panic,
     Tick(Instant),
+    /// latest report from the audio thread
+    EngineStatus(Box<EngineStatus>),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum SampleRate {
-    Rate22050,
-    Rate44100,
-    Rate48000,
-    Rate96000,
+/// MIDI mappings shared with the MIDI input, and the name of the input if one is connected
+pub struct MidiLink {
+    pub control: Arc<Mutex<MidiControl>>,
+    pub port: Option<String>,
 }
 
-impl SampleRate {
-    const ALL: [SampleRate; 4] = [
-        SampleRate::Rate22050,
-        SampleRate::Rate44100,
-        SampleRate::Rate48000,
-        SampleRate::Rate96000,
-    ];
-
-    pub fn to_hz(self) -> u32 {
-        match self {
-            SampleRate::Rate22050 => 22050,
-            SampleRate::Rate44100 => 44100,
-            SampleRate::Rate48000 => 48000,
-            SampleRate::Rate96000 => 96000,
-        }
-    }
-}
-
-impl std::fmt::Display for SampleRate {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} Hz", self.to_hz())
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct AudioSettings {
-    pub pitch: f32,
-    pub sample_rate: SampleRate,
-    pub buffer_size: u32,
-    pub delay_ms: f32,
-}
-
-impl Default for AudioSettings {
-    fn default() -> Self {
-        Self {
-            pitch: 1.0,
-            sample_rate: SampleRate::Rate44100,
-            buffer_size: 512,
-            delay_ms: 0.0,
-        }
-    }
+/// presets saved by the user and where they are kept, None if there is nowhere to save
+pub struct SavedPresets {
+    pub store: PresetStore,
+    pub path: Option<PathBuf>,
 }
 
 pub struct Montage {
     settings: AudioSettings,
     shared_settings: Arc<Mutex<AudioSettings>>,
+    /// built-in presets, then the saved ones
+    presets: Vec<Preset>,
+    saved: SavedPresets,
+    preset_name: String,
+    midi: MidiLink,
+    /// copies of the shared MIDI state, refreshed every tick
+    midi_map: MidiMap,
+    midi_learning: Option<Param>,
+    latency: LatencyReport,
+    shared_meters: Arc<Mutex<Meters>>,
+    meters: Meters,
+    status_rx: Receiver<EngineStatus>,
+    status: EngineStatus,
+    /// average and peak callback load of the recent status reports, oldest first
+    load_history: VecDeque<(f32, f32)>,
     buffer_size_slider: f32, // for slider (log scale)
     animation_time: f32,
     last_interaction: Instant,
@@ -98,7 +125,13 @@ impl Default for SliderAnimations {
 }
 
 impl Montage {
-    fn new(shared_settings: Arc<Mutex<AudioSettings>>) -> (Self, Task<Message>) {
+    fn new(
+        shared_settings: Arc<Mutex<AudioSettings>>,
+        shared_meters: Arc<Mutex<Meters>>,
+        status_rx: Receiver<EngineStatus>,
+        midi: MidiLink,
+        saved: SavedPresets,
+    ) -> (Self, Task<Message>) {
         let initial_settings = match shared_settings.lock() {
             Ok(settings) => settings.clone(),
             Err(_) => {
@@ -109,11 +142,24 @@ impl Montage {
         
         // convert buffer size to slider scale (log scale for better UX)
         let buffer_size_slider = (initial_settings.buffer_size as f32).log2();
+        let midi_map = midi.control.lock().map(|control| control.map.clone()).unwrap_or_default();
         
         (
             Self {
                 settings: initial_settings,
                 shared_settings,
+                presets: presets::builtin().into_iter().chain(saved.store.presets.iter().cloned()).collect(),
+                saved,
+                preset_name: String::new(),
+                midi,
+                midi_map,
+                midi_learning: None,
+                latency: LatencyReport::default(),
+                shared_meters,
+                meters: Meters::default(),
+                status_rx,
+                status: EngineStatus::default(),
+                load_history: VecDeque::with_capacity(LOAD_HISTORY),
                 buffer_size_slider,
                 animation_time: 0.0,
                 last_interaction: Instant::now(),
@@ -128,28 +174,142 @@ impl Montage {
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::PitchChanged(val) => {
-                self.settings.pitch = val;
+                self.edit_settings(|settings| settings.pitch = val);
                 self.last_interaction = Instant::now();
                 self.slider_animations.pitch_scale = 1.2; // squishy effect
                 self.slider_animations.pitch_glow = 1.0;
             }
             Message::SampleRateChanged(rate) => {
-                self.settings.sample_rate = rate;
+                self.edit_settings(|settings| settings.sample_rate = rate);
                 self.last_interaction = Instant::now();
             }
             Message::BufferSizeChanged(val) => {
                 self.buffer_size_slider = val;
-                self.settings.buffer_size = (2.0_f32.powf(val).round() as u32).max(64).min(2048);
+                let buffer_size = (2.0_f32.powf(val).round() as u32).clamp(MIN_BUFFER_SIZE, MAX_BUFFER_SIZE);
+                self.edit_settings(|settings| settings.buffer_size = buffer_size);
                 self.last_interaction = Instant::now();
                 self.slider_animations.buffer_scale = 1.2;
                 self.slider_animations.buffer_glow = 1.0;
             }
             Message::DelayChanged(val) => {
-                self.settings.delay_ms = val;
+                self.edit_settings(|settings| settings.delay_ms = val);
                 self.last_interaction = Instant::now();
                 self.slider_animations.delay_scale = 1.2;
                 self.slider_animations.delay_glow = 1.0;
             }
+            Message::ParamChanged(param, val) => {
+                self.edit_settings(|settings| param.set(settings, val));
+                self.last_interaction = Instant::now();
+            }
+            Message::SwitchToggled(switch, enabled) => {
+                self.edit_settings(|settings| switch.set(settings, enabled));
+                self.last_interaction = Instant::now();
+            }
+            Message::PresetSelected(preset) => {
+                self.edit_settings(|settings| preset.apply(settings));
+                if let Some(map) = &preset.midi {
+                    self.edit_midi(|control| control.map = map.clone());
+                }
+                self.last_interaction = Instant::now();
+            }
+            Message::PresetNameChanged(name) => {
+                self.preset_name = name;
+            }
+            Message::SavePreset => {
+                let name = self.preset_name.trim();
+                if name.is_empty() {
+                    return Task::none();
+                }
+                self.saved.store.insert(Preset::capture(name, &self.settings, &self.midi_map));
+                self.presets = presets::builtin().into_iter().chain(self.saved.store.presets.iter().cloned()).collect();
+                self.preset_name.clear();
+                self.save_presets();
+                return Task::none();
+            }
+            Message::MidiLearn(param) => {
+                self.edit_midi(|control| control.learning = Some(param));
+                return Task::none();
+            }
+            Message::MidiLearnCancel => {
+                self.edit_midi(|control| control.learning = None);
+                return Task::none();
+            }
+            Message::MidiMinChanged(index, value) => {
+                self.update_midi(|control| {
+                    if let Some(mapping) = control.map.mappings.get_mut(index) {
+                        mapping.min = value;
+                    }
+                });
+                return Task::none();
+            }
+            Message::MidiMaxChanged(index, value) => {
+                self.update_midi(|control| {
+                    if let Some(mapping) = control.map.mappings.get_mut(index) {
+                        mapping.max = value;
+                    }
+                });
+                return Task::none();
+            }
+            Message::MidiCurveChanged(index, curve) => {
+                self.edit_midi(|control| {
+                    if let Some(mapping) = control.map.mappings.get_mut(index) {
+                        mapping.curve = curve;
+                    }
+                });
+                return Task::none();
+            }
+            Message::MidiMappingRemoved(index) => {
+                self.edit_midi(|control| {
+                    if index < control.map.mappings.len() {
+                        control.map.mappings.remove(index);
+                    }
+                });
+                return Task::none();
+            }
+            Message::KeyPitchToggled(enabled) => {
+                self.edit_midi(|control| control.map.key_root = enabled.then_some(DEFAULT_KEY_ROOT));
+                return Task::none();
+            }
+            Message::KeyRootChanged(root) => {
+                self.update_midi(|control| control.map.key_root = Some(root.round() as u8));
+                return Task::none();
+            }
+            Message::MidiSliderReleased => {
+                self.save_presets();
+                return Task::none();
+            }
+            Message::CharacterChanged(character) => {
+                self.edit_settings(|settings| settings.effects.character = character);
+                self.last_interaction = Instant::now();
+            }
+            Message::VocoderCarrierChanged(carrier) => {
+                self.edit_settings(|settings| settings.effects.vocoder.carrier = carrier);
+                self.last_interaction = Instant::now();
+            }
+            Message::VocoderChordChanged(chord) => {
+                self.edit_settings(|settings| settings.effects.vocoder.chord = chord);
+                self.last_interaction = Instant::now();
+            }
+            Message::EchoDivisionChanged(division) => {
+                self.edit_settings(|settings| settings.effects.echo.division = division);
+                self.last_interaction = Instant::now();
+            }
+            Message::OversamplingChanged(factor) => {
+                self.edit_settings(|settings| settings.effects.oversampling.factor = factor);
+                self.last_interaction = Instant::now();
+            }
+            Message::OversamplingQualityChanged(quality) => {
+                self.edit_settings(|settings| settings.effects.oversampling.quality = quality);
+                self.last_interaction = Instant::now();
+            }
+            Message::MeasureLoopback => {
+                self.edit_settings(|settings| settings.loopback_request = settings.loopback_request.wrapping_add(1));
+                self.last_interaction = Instant::now();
+            }
+            Message::Panic => {
+                self.edit_settings(|settings| settings.panic_request = settings.panic_request.wrapping_add(1));
+                self.last_interaction = Instant::now();
+            }
             Message::Tick(now) => {
                 // update animation time
                 let _dt = now.duration_since(self.last_interaction).as_secs_f32();
@@ -167,27 +327,103 @@ impl Montage {
                 self.slider_animations.pitch_glow *= 0.95;
                 self.slider_animations.buffer_glow *= 0.95;
                 self.slider_animations.delay_glow *= 0.95;
-                
-                return Task::perform(
+
+                if let Ok(meters) = self.shared_meters.try_lock() {
+                    self.meters = *meters;
+                }
+
+                // pick up changes made by remote control so they aren't written over
+                if let Ok(shared_settings) = self.shared_settings.try_lock()
+                    && *shared_settings != self.settings
+                {
+                    if shared_settings.buffer_size != self.settings.buffer_size {
+                        self.buffer_size_slider = (shared_settings.buffer_size as f32).log2();
+                    }
+                    self.settings = shared_settings.clone();
+                }
+
+                // mappings learned on the MIDI thread are saved right away
+                let learned = match self.midi.control.try_lock() {
+                    Ok(control) => {
+                        self.midi_learning = control.learning;
+                        (control.map != self.midi_map).then(|| control.map.clone())
+                    }
+                    Err(_) => None,
+                };
+                if let Some(map) = learned {
+                    self.midi_map = map;
+                    self.save_presets();
+                }
+
+                let next_tick = Task::perform(
                     async move {
                         tokio::time::sleep(Duration::from_millis(16)).await;
                         Instant::now()
                     },
                     Message::Tick,
                 );
+                // only the newest report from the audio thread matters
+                return match self.status_rx.try_iter().last() {
+                    Some(status) => Task::batch([next_tick, Task::done(Message::EngineStatus(Box::new(status)))]),
+                    None => next_tick,
+                };
+            }
+            Message::EngineStatus(status) => {
+                self.latency = status.latency;
+                if status.state == StreamState::Running {
+                    if self.load_history.len() == LOAD_HISTORY {
+                        self.load_history.pop_front();
+                    }
+                    self.load_history.push_back((status.cpu_load, status.cpu_peak));
+                }
+                self.status = *status;
+                return Task::none();
             }
         }
-        
-        // Update shared settings
-        if let Ok(mut shared_settings) = self.shared_settings.lock() {
-            *shared_settings = self.settings.clone();
-        } else {
-            eprintln!("Failed to update shared settings");
-        }
-        
+
+
         Task::none()
     }
 
+    /// makes one change to the settings here and in the engine. only what changed is written,
+    /// so changes made over OSC, MIDI or the control API since the last tick are kept
+    fn edit_settings(&mut self, change: impl Fn(&mut AudioSettings)) {
+        change(&mut self.settings);
+        match self.shared_settings.lock() {
+            Ok(mut shared_settings) => change(&mut shared_settings),
+            Err(_) => eprintln!("Failed to update shared settings"),
+        }
+    }
+
+    /// changes the mappings and saves them if they changed
+    fn edit_midi(&mut self, change: impl FnOnce(&mut MidiControl)) {
+        if self.update_midi(change) {
+            self.save_presets();
+        }
+    }
+
+    /// changes the mappings without saving them, true if they changed
+    fn update_midi(&mut self, change: impl FnOnce(&mut MidiControl)) -> bool {
+        let Ok(mut control) = self.midi.control.lock() else { return false };
+        change(&mut control);
+        self.midi_learning = control.learning;
+        if control.map == self.midi_map {
+            return false;
+        }
+        self.midi_map = control.map.clone();
+        true
+    }
+
+    /// writes the saved presets and the mappings in use
+    fn save_presets(&mut self) {
+        self.saved.store.midi = self.midi_map.clone();
+        if let Some(path) = &self.saved.path
+            && let Err(e) = self.saved.store.save(path)
+        {
+            eprintln!("Failed to save presets: {:#}", e);
+        }
+    }
+
     fn view(&self) -> Element<'_, Message, iced::Theme, Renderer> {
         // animated, styled sliders with squishy effects
         let pitch_glow_intensity = self.slider_animations.pitch_glow;
@@ -196,12 +432,15 @@ impl Montage {
 
         // pitch control with animation
         let pitch_slider = Container::new(
-            Slider::new(
-                0.5..=2.0,
-                self.settings.pitch,
-                Message::PitchChanged,
+            MouseArea::new(
+                Slider::new(
+                    Param::Pitch.range(),
+                    self.settings.pitch,
+                    Message::PitchChanged,
+                )
+                .step(0.10)
             )
-            .step(0.10)
+            .on_right_press(Message::MidiLearn(Param::Pitch))
         )
         .style(move |_theme| {
             container_style_with_glow(pitch_glow_intensity)
@@ -216,7 +455,7 @@ impl Montage {
                         .color(Color::from_rgb(0.8, 0.9, 1.0))
                 )
                 .push(
-                    Text::new(format!("Pitch: {:.1}x", self.settings.pitch))
+                    Text::new(format!("Pitch: {:.1}x{}", self.settings.pitch, self.midi_tag(Param::Pitch)))
                         .size(14)
                         .color(Color::from_rgb(0.6, 0.8, 1.0))
                 )
@@ -233,19 +472,7 @@ impl Montage {
             Some(self.settings.sample_rate),
             Message::SampleRateChanged,
         )
-        .style(|_theme, _status| {
-            iced::widget::pick_list::Style {
-                text_color: Color::from_rgb(0.9, 0.9, 1.0),
-                placeholder_color: Color::from_rgb(0.6, 0.6, 0.8),
-                handle_color: Color::from_rgb(0.5, 0.7, 0.9),
-                background: Background::Color(Color::from_rgba(0.2, 0.3, 0.4, 0.8)),
-                border: Border {
-                    color: Color::from_rgb(0.4, 0.6, 0.8),
-                    width: 2.0,
-                    radius: 8.0.into(),
-                },
-            }
-        });
+        .style(|_theme, _status| pick_list_style());
 
         let sample_rate_section = Container::new(
             Column::new()
@@ -260,6 +487,49 @@ impl Montage {
         .padding(20)
         .style(|_theme| section_style());
 
+        // one-click voice characters
+        let character_section = Container::new(
+            Column::new()
+                .spacing(15)
+                .push(
+                    Text::new("Voice Character")
+                        .size(18)
+                        .color(Color::from_rgb(0.8, 0.9, 1.0))
+                )
+                .push(
+                    PickList::new(
+                        &self.presets[..],
+                        presets::current(&self.presets, &self.settings).cloned(),
+                        |preset| Message::PresetSelected(Box::new(preset)),
+                    )
+                    .placeholder("Preset")
+                    .style(|_theme, _status| pick_list_style())
+                )
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .align_y(Alignment::Center)
+                        .push(
+                            TextInput::new("Save as...", &self.preset_name)
+                                .on_input(Message::PresetNameChanged)
+                                .on_submit(Message::SavePreset)
+                                .size(14)
+                        )
+                        .push(Button::new(Text::new("Save").size(14)).on_press(Message::SavePreset))
+                )
+                .push(
+                    PickList::new(
+                        &VoiceCharacter::ALL[..],
+                        Some(self.settings.effects.character),
+                        Message::CharacterChanged,
+                    )
+                    .style(|_theme, _status| pick_list_style())
+                )
+        )
+        .padding(20)
+        .width(Length::Fill)
+        .style(|_theme| section_style());
+
         // buffer size control with animation
         let buffer_size_slider = Container::new(
             Slider::new(
@@ -299,12 +569,15 @@ impl Montage {
 
         // delay control with animation
         let delay_slider = Container::new(
-            Slider::new(
-                0.0..=100.0,
-                self.settings.delay_ms,
-                Message::DelayChanged,
+            MouseArea::new(
+                Slider::new(
+                    Param::Delay.range(),
+                    self.settings.delay_ms,
+                    Message::DelayChanged,
+                )
+                .step(1.0)
             )
-            .step(1.0)
+            .on_right_press(Message::MidiLearn(Param::Delay))
         )
         .style(move |_theme| {
             container_style_with_glow(delay_glow_intensity)
@@ -319,7 +592,7 @@ impl Montage {
                         .color(Color::from_rgb(0.8, 0.9, 1.0))
                 )
                 .push(
-                    Text::new(format!("Delay: {:.0}ms", self.settings.delay_ms))
+                    Text::new(format!("Delay: {:.0}ms{}", self.settings.delay_ms, self.midi_tag(Param::Delay)))
                         .size(14)
                         .color(Color::from_rgb(0.6, 0.8, 1.0))
                 )
@@ -330,6 +603,56 @@ impl Montage {
             section_style_with_scale(self.slider_animations.delay_scale)
         });
 
+        // measured latency breakdown and loopback test
+        let loopback_text = match self.latency.loopback {
+            LoopbackState::Idle => "Round trip: not measured".to_string(),
+            LoopbackState::Running => "Round trip: measuring...".to_string(),
+            LoopbackState::Measured(ms) => format!("Round trip: {:.1}ms", ms),
+            LoopbackState::TimedOut => "Round trip: no click detected, check mic and speakers".to_string(),
+        };
+
+        let latency_section = Container::new(
+            Column::new()
+                .spacing(15)
+                .push(
+                    Text::new("Latency")
+                        .size(18)
+                        .color(Color::from_rgb(0.8, 0.9, 1.0))
+                )
+                .push(
+                    Text::new(format!("Total: {:.1}ms", self.latency.total_ms()))
+                        .size(14)
+                        .color(Color::from_rgb(0.6, 0.8, 1.0))
+                )
+                .push(
+                    Text::new(format!(
+                        "input {:.1}ms + queue {:.1}ms + resample {:.1}ms + pitch {:.1}ms + delay {:.1}ms + output {:.1}ms",
+                        self.latency.input_ms,
+                        self.latency.queue_ms,
+                        self.latency.resample_ms,
+                        self.latency.pitch_ms,
+                        self.latency.delay_ms,
+                        self.latency.output_ms,
+                    ))
+                    .size(12)
+                    .color(Color::from_rgb(0.6, 0.8, 1.0))
+                )
+                .push(
+                    Row::new()
+                        .spacing(15)
+                        .align_y(Alignment::Center)
+                        .push(Button::new(Text::new("Measure round trip").size(14)).on_press(Message::MeasureLoopback))
+                        .push(
+                            Text::new(loopback_text)
+                                .size(12)
+                                .color(Color::from_rgb(0.6, 0.8, 1.0))
+                        )
+                )
+        )
+        .padding(20)
+        .width(Length::Fill)
+        .style(|_theme| section_style());
+
         // layout controls in a grid
         let left_column = Column::new()
             .spacing(25)
@@ -355,18 +678,55 @@ impl Montage {
             .width(Length::Fill)
             .height(Length::Fill);
 
-        let content = Column::new()
+        let mut content = Column::new()
             .spacing(30)
             .align_x(Alignment::Center)
             .push(
                 Text::new("Voice Effects Control Panel")
                     .size(28)
                     .color(Color::from_rgb(1.0, 1.0, 1.0))
-            )
-            .push(controls_row);
+            );
+        if self.status.state != StreamState::Running {
+            content = content.push(self.stream_banner());
+        }
+        let content = content
+            .push(controls_row)
+            .push(self.levels_section())
+            .push(self.effect_section(
+                Switch::DeEsser,
+                Column::new()
+                    .spacing(15)
+                    .push(self.switch_toggler(Switch::DeEsserListen, 14))
+                    .push(self.param_row(&[Param::DeEsserFrequency, Param::DeEsserThreshold, Param::DeEsserRange]))
+                    .into(),
+            ))
+            .push(character_section)
+            .push(self.effect_section(Switch::Vocoder, self.vocoder_controls()))
+            .push(self.effect_section(Switch::Harmonizer, self.harmonizer_controls()))
+            .push(self.effect_section(Switch::Echo, self.echo_controls()))
+            .push(self.effect_section(
+                Switch::Reverb,
+                self.param_row(&[
+                    Param::ReverbRoomSize,
+                    Param::ReverbDamping,
+                    Param::ReverbPreDelay,
+                    Param::ReverbWidth,
+                    Param::ReverbWet,
+                ])
+                .into(),
+            ))
+            .push(self.agc_section())
+            .push(self.midi_section())
+            .push(latency_section)
+            .push(self.performance_section());
 
 
-        let container_element: Element<Message, iced::Theme, Renderer> = Container::new(content)
+        let page = Column::new()
+            .spacing(15)
+            .push(Scrollable::new(content).height(Length::Fill))
+            .push(self.status_bar());
+
+        let container_element: Element<Message, iced::Theme, Renderer> = Container::new(page)
             .width(Length::Fill)
             .height(Length::Fill)
             .center_x(Length::Fill)
@@ -397,6 +757,540 @@ impl Montage {
     }
 }
 
+impl Montage {
+    /// a slider for one effect parameter with its name and current value above it
+    fn param_slider(&self, param: Param) -> Element<'_, Message, iced::Theme, Renderer> {
+        let value = param.get(&self.settings);
+
+        Column::new()
+            .spacing(8)
+            .push(
+                Text::new(format!("{}: {}{}", param.label(), param.format(value), self.midi_tag(param)))
+                    .size(14)
+                    .color(Color::from_rgb(0.6, 0.8, 1.0))
+            )
+            .push(
+                // right-click to map a MIDI control to it
+                Container::new(
+                    MouseArea::new(
+                        Slider::new(param.range(), value, move |val| Message::ParamChanged(param, val))
+                            .step(param.step())
+                    )
+                    .on_right_press(Message::MidiLearn(param))
+                )
+                .style(|_theme| container_style_with_glow(0.0))
+            )
+            .into()
+    }
+
+    /// shown after the value of a parameter that is mapped or waiting for a control
+    fn midi_tag(&self, param: Param) -> &'static str {
+        if self.midi_learning == Some(param) {
+            "  (move a MIDI control...)"
+        } else if self.midi_map.mappings.iter().any(|mapping| mapping.param == param) {
+            "  (MIDI)"
+        } else {
+            ""
+        }
+    }
+
+    /// the learned mappings with their ranges and curves, and the keys for the pitch
+    fn midi_section(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let port = match &self.midi.port {
+            Some(port) => format!("Input: {}", port),
+            None => "No MIDI input, start with --midi <port>".to_string(),
+        };
+        let mut column = Column::new()
+            .spacing(15)
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .align_y(Alignment::Center)
+                    .push(
+                        Text::new("MIDI")
+                            .size(18)
+                            .color(Color::from_rgb(0.8, 0.9, 1.0))
+                    )
+                    .push(Text::new(port).size(12).color(Color::from_rgb(0.6, 0.8, 1.0)))
+            );
+
+        column = match self.midi_learning {
+            Some(param) => column.push(
+                Row::new()
+                    .spacing(20)
+                    .align_y(Alignment::Center)
+                    .push(
+                        Text::new(format!("Move a control to map it to {}", param.label()))
+                            .size(14)
+                            .color(Color::from_rgb(1.0, 0.8, 0.4))
+                    )
+                    .push(Button::new(Text::new("Cancel").size(14)).on_press(Message::MidiLearnCancel))
+            ),
+            None => column.push(
+                Text::new("Right-click a slider to map a MIDI control to it")
+                    .size(12)
+                    .color(Color::from_rgb(0.6, 0.8, 1.0))
+            ),
+        };
+
+        let mut keys = Row::new()
+            .spacing(20)
+            .align_y(Alignment::Center)
+            .push(
+                Toggler::new(self.midi_map.key_root.is_some())
+                    .label("Keys play the pitch")
+                    .text_size(14)
+                    .on_toggle(Message::KeyPitchToggled)
+            );
+        if let Some(root) = self.midi_map.key_root {
+            keys = keys
+                .push(
+                    Text::new(format!("Unshifted: {}", note_name(root as f32)))
+                        .size(14)
+                        .color(Color::from_rgb(0.6, 0.8, 1.0))
+                )
+                .push(Slider::new(24.0..=96.0, root as f32, Message::KeyRootChanged).step(1.0).on_release(Message::MidiSliderReleased));
+        }
+        column = column.push(keys);
+
+        for (index, mapping) in self.midi_map.mappings.iter().enumerate() {
+            let param = mapping.param;
+            column = column.push(
+                Row::new()
+                    .spacing(15)
+                    .align_y(Alignment::Center)
+                    .push(
+                        Text::new(format!("{} → {}", mapping.source, param.label()))
+                            .size(14)
+                            .width(Length::FillPortion(2))
+                            .color(Color::from_rgb(0.8, 0.9, 1.0))
+                    )
+                    .push(
+                        Column::new()
+                            .spacing(5)
+                            .width(Length::FillPortion(3))
+                            .push(
+                                Text::new(format!("{} to {}", param.format(mapping.min), param.format(mapping.max)))
+                                    .size(12)
+                                    .color(Color::from_rgb(0.6, 0.8, 1.0))
+                            )
+                            .push(
+                                Slider::new(param.range(), mapping.min, move |val| Message::MidiMinChanged(index, val))
+                                    .step(param.step())
+                                    .on_release(Message::MidiSliderReleased)
+                            )
+                            .push(
+                                Slider::new(param.range(), mapping.max, move |val| Message::MidiMaxChanged(index, val))
+                                    .step(param.step())
+                                    .on_release(Message::MidiSliderReleased)
+                            )
+                    )
+                    .push(
+                        PickList::new(&Curve::ALL[..], Some(mapping.curve), move |curve| Message::MidiCurveChanged(index, curve))
+                            .style(|_theme, _status| pick_list_style())
+                    )
+                    .push(Button::new(Text::new("Remove").size(14)).on_press(Message::MidiMappingRemoved(index)))
+            );
+        }
+
+        Container::new(column)
+            .padding(20)
+            .width(Length::Fill)
+            .style(|_theme| section_style())
+            .into()
+    }
+
+    /// sliders for several parameters side by side
+    fn param_row(&self, params: &[Param]) -> Row<'_, Message, iced::Theme, Renderer> {
+        let mut row = Row::new().spacing(20);
+        for &param in params {
+            row = row.push(Container::new(self.param_slider(param)).width(Length::Fill));
+        }
+        row
+    }
+
+    fn switch_toggler(&self, switch: Switch, text_size: u16) -> Toggler<'_, Message, iced::Theme, Renderer> {
+        Toggler::new(switch.get(&self.settings))
+            .label(switch.label())
+            .text_size(text_size)
+            .on_toggle(move |enabled| Message::SwitchToggled(switch, enabled))
+    }
+
+    fn vocoder_controls(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let vocoder = &self.settings.effects.vocoder;
+
+        let carrier = Row::new()
+            .spacing(20)
+            .align_y(Alignment::Center)
+            .push(
+                Text::new("Carrier")
+                    .size(14)
+                    .color(Color::from_rgb(0.6, 0.8, 1.0))
+            )
+            .push(
+                PickList::new(&CarrierWave::ALL[..], Some(vocoder.carrier), Message::VocoderCarrierChanged)
+                    .style(|_theme, _status| pick_list_style())
+            );
+        // chords only make sense for the pitched carriers
+        let carrier = if vocoder.carrier == CarrierWave::Noise {
+            carrier
+        } else {
+            carrier.push(
+                PickList::new(&Chord::ALL[..], Some(vocoder.chord), Message::VocoderChordChanged)
+                    .style(|_theme, _status| pick_list_style())
+            )
+        };
+
+        Column::new()
+            .spacing(15)
+            .push(carrier)
+            .push(self.param_row(&[Param::VocoderNote, Param::VocoderMix]))
+            .push(self.param_row(&[Param::VocoderBands, Param::VocoderLow, Param::VocoderHigh]))
+            .push(self.param_row(&[Param::VocoderAttack, Param::VocoderRelease]))
+            .into()
+    }
+
+    fn harmonizer_controls(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let mut column = Column::new()
+            .spacing(15)
+            .push(self.param_row(&[Param::HarmonizerDry]));
+
+        for i in 0..MAX_VOICES {
+            column = column.push(self.switch_toggler(Switch::HarmonyVoice(i), 14));
+            if Switch::HarmonyVoice(i).get(&self.settings) {
+                column = column.push(self.param_row(&[
+                    Param::HarmonyInterval(i),
+                    Param::HarmonyLevel(i),
+                    Param::HarmonyPan(i),
+                    Param::HarmonyDetune(i),
+                    Param::HarmonyDelay(i),
+                ]));
+            }
+        }
+
+        column.into()
+    }
+
+    /// one line summary of the engine along the bottom of the window
+    fn status_bar(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let status = &self.status;
+        let (state, color) = match status.state {
+            StreamState::Running => ("Running".to_string(), Color::from_rgb(0.5, 0.9, 0.6)),
+            StreamState::Starting => ("Starting".to_string(), Color::from_rgb(1.0, 0.8, 0.4)),
+            _ => ("Disconnected".to_string(), Color::from_rgb(1.0, 0.45, 0.45)),
+        };
+        let device = |info: &Option<montage::status::DeviceInfo>| match info {
+            Some(info) => info.to_string(),
+            None => "none".to_string(),
+        };
+
+        let mut bar = Row::new()
+            .spacing(20)
+            .align_y(Alignment::Center)
+            .push(Text::new(state).size(12).color(color))
+            .push(
+                Text::new(format!(
+                    "in: {}  out: {}  engine: {} Hz / {} frames",
+                    device(&status.input),
+                    device(&status.output),
+                    status.sample_rate,
+                    status.buffer_size,
+                ))
+                .size(12)
+                .color(Color::from_rgb(0.6, 0.8, 1.0))
+            )
+            .push(
+                Text::new(format!(
+                    "CPU {:.0}%  xruns {}  latency {:.1}ms",
+                    status.cpu_load * 100.0,
+                    status.xruns(),
+                    status.latency.total_ms(),
+                ))
+                .size(12)
+                .color(Color::from_rgb(0.6, 0.8, 1.0))
+            );
+        if let Some(error) = &status.last_error {
+            bar = bar.push(
+                Text::new(format!("last error: {}", error))
+                    .size(12)
+                    .color(Color::from_rgb(1.0, 0.6, 0.6))
+            );
+        }
+
+        Container::new(bar)
+            .padding(10)
+            .width(Length::Fill)
+            .style(|_theme| section_style())
+            .into()
+    }
+
+    /// callback load against the buffer period with a short history, to tell
+    /// whether the effect chain fits in the chosen buffer size
+    fn performance_section(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let status = &self.status;
+        let recent_peak = self.load_history.iter().map(|&(_, peak)| peak).fold(0.0, f32::max);
+        let load_color = |load: f32| {
+            if load >= 1.0 {
+                Color::from_rgb(1.0, 0.45, 0.45)
+            } else if load >= LOAD_WARNING * 0.8 {
+                Color::from_rgb(1.0, 0.8, 0.4)
+            } else {
+                Color::from_rgb(0.5, 0.9, 0.6)
+            }
+        };
+
+        // one bar per report, the peak drawn dim behind the average
+        let graph = self.load_history.iter().fold(
+            Row::new().spacing(1).height(Length::Fixed(LOAD_GRAPH_HEIGHT)).align_y(Alignment::End),
+            |graph, &(load, peak)| {
+                let bar = |value: f32, alpha: f32| {
+                    let height = (value / LOAD_GRAPH_MAX).clamp(0.0, 1.0) * LOAD_GRAPH_HEIGHT;
+                    let color = Color { a: alpha, ..load_color(value) };
+                    Container::new(Text::new(""))
+                        .width(Length::Fixed(2.0))
+                        .height(Length::Fixed(height.max(1.0)))
+                        .style(move |_theme| iced::widget::container::Style {
+                            background: Some(Background::Color(color)),
+                            ..Default::default()
+                        })
+                };
+                graph.push(
+                    Stack::new()
+                        .push(Container::new(bar(peak, 0.35)).height(Length::Fixed(LOAD_GRAPH_HEIGHT)).align_y(Alignment::End))
+                        .push(Container::new(bar(load, 1.0)).height(Length::Fixed(LOAD_GRAPH_HEIGHT)).align_y(Alignment::End)),
+                )
+            },
+        );
+
+        let mut column = Column::new()
+            .spacing(15)
+            .push(
+                Text::new("Performance")
+                    .size(18)
+                    .color(Color::from_rgb(0.8, 0.9, 1.0))
+            )
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .push(
+                        Text::new(format!("Load {:.0}%", status.cpu_load * 100.0))
+                            .size(14)
+                            .color(load_color(status.cpu_load))
+                    )
+                    .push(
+                        Text::new(format!("Peak {:.0}%", status.cpu_peak * 100.0))
+                            .size(14)
+                            .color(load_color(status.cpu_peak))
+                    )
+                    .push(
+                        Text::new(format!(
+                            "dropped input blocks {}  output underruns {}",
+                            status.dropped_inputs, status.underruns,
+                        ))
+                        .size(14)
+                        .color(Color::from_rgb(0.6, 0.8, 1.0))
+                    )
+            )
+            .push(
+                Container::new(graph)
+                    .width(Length::Fill)
+                    .height(Length::Fixed(LOAD_GRAPH_HEIGHT))
+                    .style(|_theme| iced::widget::container::Style {
+                        background: Some(Background::Color(Color::from_rgba(0.0, 0.0, 0.0, 0.25))),
+                        ..Default::default()
+                    })
+            );
+        if recent_peak >= LOAD_WARNING {
+            column = column.push(
+                Text::new(format!(
+                    "The effect chain is too heavy for {} frames: callbacks peak at {:.0}% of the buffer period. \
+                     Raise the buffer size or turn effects off.",
+                    status.buffer_size,
+                    recent_peak * 100.0,
+                ))
+                .size(12)
+                .color(Color::from_rgb(1.0, 0.6, 0.6))
+            );
+        }
+
+        Container::new(column)
+            .padding(20)
+            .width(Length::Fill)
+            .style(|_theme| section_style())
+            .into()
+    }
+
+    /// shown above the controls while the audio isn't running
+    fn stream_banner(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let color = match self.status.state {
+            StreamState::Disconnected(_) => Color::from_rgb(1.0, 0.45, 0.45),
+            _ => Color::from_rgb(1.0, 0.8, 0.4),
+        };
+        Container::new(
+            Text::new(self.status.state.to_string())
+                .size(16)
+                .color(color)
+        )
+        .padding(15)
+        .width(Length::Fill)
+        .style(|_theme| section_style())
+        .into()
+    }
+
+    /// input trim, output volume and the buttons to get out of trouble quickly
+    fn levels_section(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let column = Column::new()
+            .spacing(15)
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .align_y(Alignment::Center)
+                    .push(
+                        Text::new("Levels")
+                            .size(18)
+                            .color(Color::from_rgb(0.8, 0.9, 1.0))
+                    )
+                    .push(self.switch_toggler(Switch::Mute, 14))
+                    .push(self.switch_toggler(Switch::Bypass, 14))
+                    .push(Button::new(Text::new("Panic").size(14)).on_press(Message::Panic))
+            )
+            .push(self.param_row(&[Param::InputGain, Param::OutputVolume]))
+            .push(
+                // applies to the saturating stages, higher factors cost cpu and a little latency
+                Row::new()
+                    .spacing(15)
+                    .align_y(Alignment::Center)
+                    .push(
+                        Text::new("Oversampling")
+                            .size(14)
+                            .color(Color::from_rgb(0.6, 0.8, 1.0))
+                    )
+                    .push(
+                        PickList::new(
+                            &Oversampling::ALL[..],
+                            Some(self.settings.effects.oversampling.factor),
+                            Message::OversamplingChanged,
+                        )
+                        .style(|_theme, _status| pick_list_style())
+                    )
+                    .push(
+                        PickList::new(
+                            &OversamplingQuality::ALL[..],
+                            Some(self.settings.effects.oversampling.quality),
+                            Message::OversamplingQualityChanged,
+                        )
+                        .style(|_theme, _status| pick_list_style())
+                    )
+            );
+
+        Container::new(column)
+            .padding(20)
+            .width(Length::Fill)
+            .style(|_theme| section_style())
+            .into()
+    }
+
+    /// automatic gain, with the loudness meter visible even while it is off
+    fn agc_section(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let loudness = match self.meters.loudness_lufs {
+            Some(lufs) => format!("Loudness: {:.1} LUFS", lufs),
+            None => "Loudness: silence".to_string(),
+        };
+        let loudness = if self.settings.effects.agc.enabled {
+            format!("{}  (gain {:+.1}dB)", loudness, self.meters.agc_gain_db)
+        } else {
+            loudness
+        };
+
+        let mut column = Column::new()
+            .spacing(15)
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .align_y(Alignment::Center)
+                    .push(self.switch_toggler(Switch::Agc, 18))
+                    .push(
+                        Text::new(loudness)
+                            .size(14)
+                            .color(Color::from_rgb(0.6, 0.8, 1.0))
+                    )
+            );
+        if self.settings.effects.agc.enabled {
+            column = column.push(self.param_row(&[
+                Param::AgcTarget,
+                Param::AgcMaxGain,
+                Param::AgcGate,
+                Param::AgcResponse,
+            ]));
+        }
+
+        Container::new(column)
+            .padding(20)
+            .width(Length::Fill)
+            .style(|_theme| section_style())
+            .into()
+    }
+
+    fn echo_controls(&self) -> Element<'_, Message, iced::Theme, Renderer> {
+        let echo = &self.settings.effects.echo;
+
+        // the tempo replaces the free running time while synced
+        let timing = if echo.sync {
+            self.param_row(&[Param::EchoBpm, Param::EchoFeedback])
+        } else {
+            self.param_row(&[Param::EchoTime, Param::EchoFeedback])
+        };
+
+        let mut modes = Row::new()
+            .spacing(20)
+            .align_y(Alignment::Center)
+            .push(self.switch_toggler(Switch::EchoSync, 14))
+            .push(self.switch_toggler(Switch::EchoPingPong, 14));
+        if echo.sync {
+            modes = modes
+                .push(
+                    PickList::new(&NoteDivision::ALL[..], Some(echo.division), Message::EchoDivisionChanged)
+                        .style(|_theme, _status| pick_list_style())
+                )
+                .push(
+                    Text::new(format!("= {:.0}ms", echo.delay_ms()))
+                        .size(14)
+                        .color(Color::from_rgb(0.6, 0.8, 1.0))
+                );
+        }
+
+        Column::new()
+            .spacing(15)
+            .push(modes)
+            .push(timing)
+            .push(self.param_row(&[Param::EchoLowCut, Param::EchoHighCut]))
+            .push(self.param_row(&[Param::EchoWet, Param::EchoDry]))
+            .into()
+    }
+
+    /// an effect stage with its on/off switch and, while enabled, its controls
+    fn effect_section<'a>(
+        &'a self,
+        switch: Switch,
+        controls: Element<'a, Message, iced::Theme, Renderer>,
+    ) -> Element<'a, Message, iced::Theme, Renderer> {
+        let mut column = Column::new()
+            .spacing(15)
+            .push(self.switch_toggler(switch, 18));
+
+        // only show the knobs of stages that are in the chain
+        if switch.get(&self.settings) {
+            column = column.push(controls);
+        }
+
+        Container::new(column)
+            .padding(20)
+            .width(Length::Fill)
+            .style(|_theme| section_style())
+            .into()
+    }
+}
+
 // custom styling functions
 fn section_style() -> iced::widget::container::Style {
     iced::widget::container::Style {
@@ -415,6 +1309,20 @@ fn section_style() -> iced::widget::container::Style {
     }
 }
 
+fn pick_list_style() -> iced::widget::pick_list::Style {
+    iced::widget::pick_list::Style {
+        text_color: Color::from_rgb(0.9, 0.9, 1.0),
+        placeholder_color: Color::from_rgb(0.6, 0.6, 0.8),
+        handle_color: Color::from_rgb(0.5, 0.7, 0.9),
+        background: Background::Color(Color::from_rgba(0.2, 0.3, 0.4, 0.8)),
+        border: Border {
+            color: Color::from_rgb(0.4, 0.6, 0.8),
+            width: 2.0,
+            radius: 8.0.into(),
+        },
+    }
+}
+
 fn section_style_with_scale(scale: f32) -> iced::widget::container::Style {
     let mut style = section_style();
     // simulate scale effect with enhanced glow
@@ -451,6 +1359,10 @@ impl Montage {
         window_settings: iced::window::Settings,
         settings: Settings,
         shared_settings: Arc<Mutex<AudioSettings>>,
+        shared_meters: Arc<Mutex<Meters>>,
+        status_rx: Receiver<EngineStatus>,
+        midi: MidiLink,
+        saved: SavedPresets,
     ) -> Result<()> {
         iced::application(
             "Voice Effects Control Panel",
@@ -459,7 +1371,7 @@ impl Montage {
         )
         .settings(settings)
         .window(window_settings)
-        .run_with(move || Montage::new(shared_settings))
+        .run_with(move || Montage::new(shared_settings, shared_meters, status_rx, midi, saved))
         .map_err(|e| anyhow::anyhow!("GUI error: {}", e))
     }
 }
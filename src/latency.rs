@@ -0,0 +1,180 @@
+/// breakdown of the end-to-end signal path latency, refreshed by the audio thread
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencyReport {
+    pub sample_rate: u32,
+    /// capture to callback time of the input device
+    pub input_ms: f32,
+    /// audio waiting in the channel between the input and output callbacks
+    pub queue_ms: f32,
+    /// filter delay of the converters between the device rates and the internal rate
+    pub resample_ms: f32,
+    /// distance between the pitch ring buffer write and read heads
+    pub pitch_ms: f32,
+    /// audio waiting in the output delay line
+    pub delay_ms: f32,
+    /// callback to playback time of the output device
+    pub output_ms: f32,
+    pub loopback: LoopbackState,
+}
+
+impl LatencyReport {
+    pub fn total_ms(&self) -> f32 {
+        self.input_ms + self.queue_ms + self.resample_ms + self.pitch_ms + self.delay_ms + self.output_ms
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LoopbackState {
+    #[default]
+    Idle,
+    Running,
+    /// measured round trip from the click leaving the output to it arriving at the input
+    Measured(f32),
+    TimedOut,
+}
+
+/// silence before the click, used to measure the noise floor of the input
+const PRE_ROLL_SECONDS: f32 = 0.25;
+/// how long to listen for the click before giving up
+const LISTEN_SECONDS: f32 = 1.0;
+/// length of the click burst
+const CLICK_SECONDS: f32 = 0.002;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProbeState {
+    Idle,
+    /// waiting for the click to be written, measuring the input noise floor meanwhile
+    PreRoll { click_at: u64 },
+    /// click was written at `emitted_at` on the output clock, waiting for it to come back
+    Listening { emitted_at: u64 },
+}
+
+/// plays a click on the output and times how long it takes to show up on the input.
+/// both sides use one clock: input frames numbered from the start of the input stream.
+/// the output side writes the click in place of a known input frame, so the time from
+/// capturing that frame to capturing the click covers the whole path, device buffers,
+/// queue, converters and the acoustic (or cable) path
+pub struct LoopbackProbe {
+    state: ProbeState,
+    sample_rate: u32,
+    noise_floor: f32,
+    result: LoopbackState,
+}
+
+impl LoopbackProbe {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            state: ProbeState::Idle,
+            sample_rate,
+            noise_floor: 0.0,
+            result: LoopbackState::Idle,
+        }
+    }
+
+    /// start a new measurement, `now` is the input frame being played
+    pub fn arm(&mut self, now: u64) {
+        let pre_roll = (PRE_ROLL_SECONDS * self.sample_rate as f32) as u64;
+        self.state = ProbeState::PreRoll { click_at: now + pre_roll };
+        self.noise_floor = 0.0;
+        self.result = LoopbackState::Running;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state != ProbeState::Idle
+    }
+
+    pub fn result(&self) -> LoopbackState {
+        self.result
+    }
+
+    /// sample to play in place of input frame `frame` while the probe is active
+    pub fn output_sample(&mut self, frame: u64) -> f32 {
+        let click_len = ((CLICK_SECONDS * self.sample_rate as f32) as u64).max(1);
+        match self.state {
+            ProbeState::PreRoll { click_at } if frame >= click_at => {
+                self.state = ProbeState::Listening { emitted_at: frame };
+                0.8
+            }
+            ProbeState::Listening { emitted_at } if frame < emitted_at + click_len => 0.8,
+            _ => 0.0,
+        }
+    }
+
+    /// feed mono input frames starting at input frame `start`
+    pub fn observe_input(&mut self, start: u64, input: &[f32]) {
+        match self.state {
+            ProbeState::Idle => {}
+            ProbeState::PreRoll { .. } => {
+                for &sample in input {
+                    self.noise_floor = self.noise_floor.max(sample.abs());
+                }
+            }
+            ProbeState::Listening { emitted_at } => {
+                let threshold = (self.noise_floor * 4.0).max(0.02);
+                // frames captured before the click was written can't contain it
+                let arrived = input
+                    .iter()
+                    .enumerate()
+                    .map(|(i, sample)| (start + i as u64, sample))
+                    .find(|&(frame, sample)| frame >= emitted_at && sample.abs() > threshold);
+                if let Some((arrived_at, _)) = arrived {
+                    let frames = arrived_at - emitted_at;
+                    self.result = LoopbackState::Measured(frames as f32 * 1000.0 / self.sample_rate as f32);
+                    self.state = ProbeState::Idle;
+                    return;
+                }
+
+                let deadline = emitted_at + (LISTEN_SECONDS * self.sample_rate as f32) as u64;
+                if start + input.len() as u64 > deadline {
+                    self.result = LoopbackState::TimedOut;
+                    self.state = ProbeState::Idle;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 1000;
+    const BLOCK: usize = 10;
+
+    /// runs the probe against a path that returns the output `delay` frames later at
+    /// half level, over a noise floor, until it has a result
+    fn measure(delay: usize, noise: f32, returned: bool) -> LoopbackState {
+        let mut probe = LoopbackProbe::new(SAMPLE_RATE);
+        let mut path = vec![0.0; delay];
+        probe.arm(0);
+        let mut clock = 0;
+        while probe.is_active() {
+            let start = clock as u64;
+            let input: Vec<f32> = (0..BLOCK)
+                .map(|i| {
+                    let frame = clock + i;
+                    let hiss = if frame % 2 == 0 { noise } else { -noise };
+                    path.push(probe.output_sample(frame as u64));
+                    hiss + if returned { path[frame] * 0.5 } else { 0.0 }
+                })
+                .collect();
+            probe.observe_input(start, &input);
+            clock += BLOCK;
+            assert!(clock < 10 * SAMPLE_RATE as usize, "probe never finished");
+        }
+        probe.result()
+    }
+
+    #[test]
+    fn click_is_timed_over_the_noise_floor() {
+        assert_eq!(measure(37, 0.01, true), LoopbackState::Measured(37.0));
+        assert_eq!(measure(240, 0.05, true), LoopbackState::Measured(240.0));
+    }
+
+    #[test]
+    fn missing_click_times_out() {
+        assert_eq!(measure(37, 0.01, false), LoopbackState::TimedOut);
+        // a click that drowns in the noise is as good as missing
+        assert_eq!(measure(37, 0.2, true), LoopbackState::TimedOut);
+    }
+}
@@ -0,0 +1,19 @@
+//! the montage voice engine: the effect chain from `montage-dsp` running on an audio
+//! backend, with its settings, parameters and status reports. the iced app and the
+//! command line tool are thin front-ends over `Engine`
+
+pub mod audio;
+pub mod control;
+pub mod engine;
+pub mod latency;
+pub mod meters;
+pub mod midi;
+pub mod osc;
+pub mod params;
+pub mod presets;
+pub mod settings;
+pub mod status;
+
+pub use engine::{Engine, EngineConfig, ParamHandle, SwitchHandle};
+pub use montage_dsp as dsp;
+pub use settings::{AudioSettings, SampleRate};
@@ -1,26 +1,68 @@
 mod gui;
-mod audio;
-mod dsp;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+use gui::{MidiLink, SavedPresets};
 use iced::{window, Settings, Size};
+use montage::audio::BackendArgs;
+use montage::control::{self, ControlServer};
+use montage::midi::{MidiControl, MidiInput};
+use montage::osc::{self, OscServer};
+use montage::presets::PresetStore;
+use montage::{Engine, EngineConfig};
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
 
 fn main() -> Result<()> {
-    // create shared audio settings and shutdown signal
-    let audio_settings = Arc::new(Mutex::new(gui::AudioSettings::default()));
-    let shutdown_signal = Arc::new(AtomicBool::new(false));
-    
+    let mut backend = BackendArgs::default();
+    let mut osc_bind = None;
+    let mut control_bind = None;
+    let mut midi_port = None;
+    let mut args = std::env::args().skip(1).peekable();
+    // `montage ctl ...` talks to a running montage instead of starting one
+    if args.peek().map(String::as_str) == Some("ctl") {
+        args.next();
+        return control::run_ctl(args);
+    }
+    while let Some(arg) = args.next() {
+        if backend.take(&arg, &mut args) {
+            continue;
+        }
+        if arg == "--osc" {
+            let bind = args.next().ok_or_else(|| anyhow!("--osc needs a port or address"))?;
+            osc_bind = Some(osc::parse_bind(&bind)?);
+        } else if arg == "--control" {
+            let bind = args.next().ok_or_else(|| anyhow!("--control needs a port or address"))?;
+            control_bind = Some(control::parse_bind(&bind)?);
+        } else if arg == "--midi" {
+            midi_port = Some(args.next().ok_or_else(|| anyhow!("--midi needs the name or number of an input"))?);
+        } else {
+            bail!("Unknown argument {}", arg);
+        }
+    }
+
     // start audio processing in background thread
-    let audio_settings_clone = audio_settings.clone();
-    let audio_shutdown = shutdown_signal.clone();
-    let audio_handle = thread::spawn(move || {
-        if let Err(e) = audio::run_audio(audio_settings_clone, audio_shutdown) {
-            eprintln!("Audio error: {}", e);
+    let engine = Engine::start(EngineConfig { backend: backend.backend()?, ..EngineConfig::default() });
+    let osc_server = osc_bind.map(|bind| OscServer::start(&engine, bind)).transpose()?;
+    if let Some(server) = &osc_server {
+        eprintln!("OSC: listening on {}", server.local_addr());
+    }
+    let control_server = control_bind.map(|bind| ControlServer::start(&engine, bind)).transpose()?;
+    if let Some(server) = &control_server {
+        eprintln!("Control: listening on http://{}", server.local_addr());
+    }
+
+    // saved presets and the MIDI mappings in use
+    let mut store_path = PresetStore::default_path();
+    let store = match store_path.as_deref().map(PresetStore::load).transpose() {
+        Ok(store) => store.unwrap_or_default(),
+        Err(e) => {
+            // saving over a file that could not be read would lose what is in it
+            eprintln!("Failed to load presets, nothing will be saved: {:#}", e);
+            store_path = None;
+            PresetStore::default()
         }
-    });
+    };
+    let midi_control = Arc::new(Mutex::new(MidiControl::new(store.midi.clone())));
+    let midi_input = midi_port.map(|port| MidiInput::start(&engine, &port, midi_control.clone())).transpose()?;
 
     // configure window settings
     let window_settings = window::Settings {
@@ -37,13 +79,21 @@ fn main() -> Result<()> {
     };
 
     // run the GUI with shared audio settings
-    let gui_result = gui::Montage::run(window_settings, app_settings, audio_settings);
-    
-    // signal audio thread to shutdown
-    shutdown_signal.store(true, Ordering::Relaxed);
-    
-    // wait for audio thread to finish
-    let _ = audio_handle.join();
-    
+    let gui_result = gui::Montage::run(
+        window_settings,
+        app_settings,
+        engine.shared_settings(),
+        engine.shared_meters(),
+        engine.subscribe(),
+        MidiLink { control: midi_control, port: midi_input.as_ref().map(|input| input.port_name().to_string()) },
+        SavedPresets { store, path: store_path },
+    );
+
+    // stop the audio thread and wait for it to finish
+    drop(midi_input);
+    drop(control_server);
+    drop(osc_server);
+    engine.stop();
+
     gui_result
 }
@@ -0,0 +1,63 @@
+/// reading of the peak meters when there is no signal at all
+pub const SILENCE_DB: f32 = -90.0;
+/// how fast the peak meters fall back after a peak
+const PEAK_FALL_DB_PER_SECOND: f32 = 24.0;
+
+/// levels published by the audio thread for display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Meters {
+    /// short-term loudness of the processed voice, None while silent
+    pub loudness_lufs: Option<f32>,
+    /// gain currently applied by the automatic gain control
+    pub agc_gain_db: f32,
+    /// peak level of the voice coming in and of the processed output, in dBFS
+    pub input_peak_db: f32,
+    pub output_peak_db: f32,
+}
+
+impl Default for Meters {
+    fn default() -> Self {
+        Self {
+            loudness_lufs: None,
+            agc_gain_db: 0.0,
+            input_peak_db: SILENCE_DB,
+            output_peak_db: SILENCE_DB,
+        }
+    }
+}
+
+/// peak level that jumps up at once and falls back slowly, so short peaks stay visible
+pub(crate) struct PeakMeter {
+    level_db: f32,
+    fall_per_sample: f32,
+}
+
+impl PeakMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { level_db: SILENCE_DB, fall_per_sample: PEAK_FALL_DB_PER_SECOND / sample_rate as f32 }
+    }
+
+    /// level after `frames` frames of `samples`, which may be interleaved
+    pub fn process(&mut self, samples: &[f32], frames: usize) -> f32 {
+        let peak = samples.iter().fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        let peak_db = if peak > 0.0 { (20.0 * peak.log10()).max(SILENCE_DB) } else { SILENCE_DB };
+        let fallen = (self.level_db - self.fall_per_sample * frames as f32).max(SILENCE_DB);
+        self.level_db = peak_db.max(fallen);
+        self.level_db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peaks_jump_up_and_fall_back_slowly() {
+        let mut meter = PeakMeter::new(1000);
+        assert_eq!(meter.process(&[0.5, -1.0], 1), 0.0);
+        // a second of silence takes it down by the fall rate
+        let level = meter.process(&[0.0; 1000], 1000);
+        assert!((level + PEAK_FALL_DB_PER_SECOND).abs() < 1e-3, "{}", level);
+        assert_eq!(meter.process(&[0.0; 100_000], 100_000), SILENCE_DB);
+    }
+}
@@ -0,0 +1,309 @@
+//! MIDI control surfaces: CCs and notes move parameters through learned mappings,
+//! each with its own range and curve, and keys can play the pitch shifter
+
+use crate::engine::Engine;
+use crate::params::{note_name, Param};
+use crate::settings::AudioSettings;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// name the connection shows up under in other MIDI software
+const CLIENT_NAME: &str = "montage";
+
+/// what moves on the control surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Control {
+    Cc(u8),
+    Note(u8),
+}
+
+/// a control on one channel, channels counted from 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiSource {
+    pub channel: u8,
+    pub control: Control,
+}
+
+impl std::fmt::Display for MidiSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.control {
+            Control::Cc(number) => write!(f, "CC {} ch {}", number, self.channel + 1),
+            Control::Note(note) => write!(f, "{} ch {}", note_name(note as f32), self.channel + 1),
+        }
+    }
+}
+
+/// how the travel of a control is spread over the range of the parameter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// fine control at the bottom of the range
+    Exponential,
+    /// fine control at the top of the range
+    Logarithmic,
+}
+
+impl Curve {
+    pub const ALL: [Curve; 3] = [Curve::Linear, Curve::Exponential, Curve::Logarithmic];
+
+    /// position 0..=1 along the control to position 0..=1 along the range
+    pub fn shape(self, position: f32) -> f32 {
+        match self {
+            Curve::Linear => position,
+            Curve::Exponential => position * position,
+            Curve::Logarithmic => position.sqrt(),
+        }
+    }
+}
+
+impl std::fmt::Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Curve::Linear => "Linear",
+            Curve::Exponential => "Exponential",
+            Curve::Logarithmic => "Logarithmic",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// one control driving one parameter between `min` and `max`, which may be reversed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MidiMapping {
+    pub source: MidiSource,
+    pub param: Param,
+    pub min: f32,
+    pub max: f32,
+    pub curve: Curve,
+}
+
+impl MidiMapping {
+    /// over the whole range of the parameter
+    pub fn new(source: MidiSource, param: Param) -> Self {
+        let range = param.range();
+        Self { source, param, min: *range.start(), max: *range.end(), curve: Curve::Linear }
+    }
+
+    /// parameter value for a controller value or velocity
+    pub fn value(&self, raw: u8) -> f32 {
+        let position = self.curve.shape(raw.min(127) as f32 / 127.0);
+        self.min + (self.max - self.min) * position
+    }
+}
+
+/// the mappings that are saved with presets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiMap {
+    pub mappings: Vec<MidiMapping>,
+    /// keys that no mapping uses shift the pitch in semitones from this note while
+    /// held, None leaves them alone
+    pub key_root: Option<u8>,
+}
+
+impl MidiMap {
+    /// leaves out mappings to parameters that do not exist, e.g. a harmony voice past the last one
+    pub fn drop_invalid(&mut self) {
+        self.mappings.retain(|mapping| mapping.param.is_valid());
+    }
+}
+
+/// the mappings in use and what is going on right now, shared by the MIDI thread and the GUI
+#[derive(Debug, Clone)]
+pub struct MidiControl {
+    pub map: MidiMap,
+    /// the next control that moves is mapped to this parameter
+    pub learning: Option<Param>,
+    /// keys playing the pitch, the last one sounds
+    held_keys: Vec<u8>,
+    /// pitch to go back to once every key is released
+    pitch_before_keys: f32,
+}
+
+impl MidiControl {
+    pub fn new(map: MidiMap) -> Self {
+        Self { map, learning: None, held_keys: Vec::new(), pitch_before_keys: 1.0 }
+    }
+
+    /// applies one MIDI message to the settings, or learns it. true if anything changed
+    pub fn handle(&mut self, message: &[u8], settings: &mut AudioSettings) -> bool {
+        let Some((source, raw)) = parse(message) else { return false };
+
+        if let Some(param) = self.learning.take() {
+            // a control moves one parameter, and a parameter listens to one control
+            self.map.mappings.retain(|mapping| mapping.source != source && mapping.param != param);
+            self.map.mappings.push(MidiMapping::new(source, param));
+            return true;
+        }
+
+        let mut mapped = false;
+        for mapping in self.map.mappings.iter().filter(|mapping| mapping.source == source) {
+            mapping.param.set(settings, mapping.value(raw));
+            mapped = true;
+        }
+        if mapped {
+            return true;
+        }
+
+        match (source.control, self.map.key_root) {
+            (Control::Note(note), Some(root)) => {
+                self.play_key(note, raw > 0, root, settings);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn play_key(&mut self, note: u8, pressed: bool, root: u8, settings: &mut AudioSettings) {
+        if pressed {
+            if self.held_keys.is_empty() {
+                self.pitch_before_keys = settings.pitch;
+            }
+            self.held_keys.retain(|held| *held != note);
+            self.held_keys.push(note);
+        } else {
+            self.held_keys.retain(|held| *held != note);
+        }
+        let pitch = match self.held_keys.last() {
+            Some(&key) => 2.0_f32.powf((key as f32 - root as f32) / 12.0),
+            None => self.pitch_before_keys,
+        };
+        Param::Pitch.set(settings, pitch);
+    }
+}
+
+impl Default for MidiControl {
+    fn default() -> Self {
+        Self::new(MidiMap::default())
+    }
+}
+
+/// the control and its value or velocity, note offs give 0. other messages are None
+fn parse(message: &[u8]) -> Option<(MidiSource, u8)> {
+    let [status, data1, data2, ..] = *message else { return None };
+    let channel = status & 0x0f;
+    let (control, value) = match status & 0xf0 {
+        0xb0 => (Control::Cc(data1), data2),
+        0x90 => (Control::Note(data1), data2),
+        0x80 => (Control::Note(data1), 0),
+        _ => return None,
+    };
+    Some((MidiSource { channel, control }, value))
+}
+
+/// names of the MIDI inputs on this system
+pub fn input_ports() -> Result<Vec<String>> {
+    let input = midir::MidiInput::new(CLIENT_NAME)?;
+    Ok(input.ports().iter().filter_map(|port| input.port_name(port).ok()).collect())
+}
+
+/// a connected MIDI input driving the engine, disconnects when dropped
+pub struct MidiInput {
+    port_name: String,
+    _connection: midir::MidiInputConnection<()>,
+}
+
+impl MidiInput {
+    /// `port` is the index of the input or a part of its name
+    pub fn start(engine: &Engine, port: &str, control: Arc<Mutex<MidiControl>>) -> Result<Self> {
+        let input = midir::MidiInput::new(CLIENT_NAME)?;
+        let ports = input.ports();
+        let names: Vec<String> = ports.iter().map(|port| input.port_name(port).unwrap_or_default()).collect();
+        let index = match port.parse::<usize>() {
+            Ok(index) if index < ports.len() => index,
+            _ => {
+                let wanted = port.to_lowercase();
+                match names.iter().position(|name| name.to_lowercase().contains(&wanted)) {
+                    Some(index) => index,
+                    None if names.is_empty() => bail!("There is no MIDI input to connect to"),
+                    None => bail!("No MIDI input matches {}, there are: {}", port, names.join(", ")),
+                }
+            }
+        };
+
+        let settings = engine.shared_settings();
+        let connection = input
+            .connect(
+                &ports[index],
+                "montage-in",
+                move |_timestamp, message, _| {
+                    if let (Ok(mut control), Ok(mut settings)) = (control.lock(), settings.lock()) {
+                        control.handle(message, &mut settings);
+                    }
+                },
+                (),
+            )
+            .map_err(|e| anyhow!("Could not connect to MIDI input {}: {}", names[index], e))?;
+        Ok(Self { port_name: names[index].clone(), _connection: connection })
+    }
+
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CC_7: [u8; 3] = [0xb0, 7, 127];
+
+    #[test]
+    fn learned_control_moves_its_parameter_within_range() {
+        let mut control = MidiControl::default();
+        let mut settings = AudioSettings::default();
+        control.learning = Some(Param::Delay);
+        assert!(control.handle(&CC_7, &mut settings));
+        assert_eq!(settings.delay_ms, 0.0, "learning should not move the parameter");
+        assert_eq!(control.map.mappings[0].source, MidiSource { channel: 0, control: Control::Cc(7) });
+
+        let mapping = &mut control.map.mappings[0];
+        (mapping.min, mapping.max, mapping.curve) = (20.0, 60.0, Curve::Exponential);
+        control.handle(&[0xb0, 7, 127], &mut settings);
+        assert_eq!(settings.delay_ms, 60.0);
+        control.handle(&[0xb0, 7, 64], &mut settings);
+        assert!((settings.delay_ms - (20.0 + 40.0 * (64.0_f32 / 127.0).powi(2))).abs() < 1e-4);
+        // another channel is another control
+        assert!(!control.handle(&[0xb1, 7, 0], &mut settings));
+    }
+
+    #[test]
+    fn learning_again_replaces_the_old_mapping() {
+        let mut control = MidiControl::default();
+        let mut settings = AudioSettings::default();
+        control.learning = Some(Param::Delay);
+        control.handle(&CC_7, &mut settings);
+        control.learning = Some(Param::Pitch);
+        control.handle(&CC_7, &mut settings);
+        control.learning = Some(Param::Pitch);
+        control.handle(&[0x90, 60, 100], &mut settings);
+        assert_eq!(control.map.mappings.len(), 1);
+        assert_eq!(control.map.mappings[0].param, Param::Pitch);
+        assert_eq!(control.map.mappings[0].source.control, Control::Note(60));
+    }
+
+    #[test]
+    fn keys_play_the_pitch_in_semitones() {
+        let mut control = MidiControl::new(MidiMap { key_root: Some(60), ..MidiMap::default() });
+        let mut settings = AudioSettings { pitch: 0.8, ..AudioSettings::default() };
+        control.handle(&[0x90, 67, 100], &mut settings);
+        assert!((settings.pitch - 2.0_f32.powf(7.0 / 12.0)).abs() < 1e-5);
+        control.handle(&[0x90, 48, 100], &mut settings);
+        assert_eq!(settings.pitch, 0.5);
+        // back to the key still held, then to where the pitch was
+        control.handle(&[0x80, 48, 0], &mut settings);
+        assert!((settings.pitch - 2.0_f32.powf(7.0 / 12.0)).abs() < 1e-5);
+        control.handle(&[0x90, 67, 0], &mut settings);
+        assert_eq!(settings.pitch, 0.8);
+    }
+
+    #[test]
+    fn notes_are_named() {
+        assert_eq!(note_name(60.0), "C4");
+        assert_eq!(note_name(68.6), "A4");
+        assert_eq!(note_name(0.0), "C-1");
+        assert_eq!(MidiSource { channel: 9, control: Control::Note(45) }.to_string(), "A2 ch 10");
+    }
+}
@@ -0,0 +1,353 @@
+//! optional OSC control over UDP, for show control software, TouchOSC or a test script.
+//! every address below takes one argument to set the value, a message without
+//! arguments is answered with the current value. clients that sent anything get the
+//! levels with every status report and hear about changes made elsewhere.
+//! there is no authentication, so only this machine can reach it unless another address
+//! such as `0.0.0.0:9000` is given on purpose to take control from the network
+
+use crate::dsp::agc::ABSOLUTE_GATE_LUFS;
+use crate::engine::Engine;
+use crate::meters::Meters;
+use crate::params::{Param, Switch};
+use crate::presets::{self, Preset};
+use crate::settings::AudioSettings;
+use crate::status::EngineStatus;
+use anyhow::{Context, Result};
+use rosc::{OscMessage, OscPacket, OscType};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// port used when only an address is given
+pub const DEFAULT_PORT: u16 = 9000;
+/// how long the server waits for a packet before looking at the status reports again
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// feedback goes to the most recent senders only
+const MAX_CLIENTS: usize = 8;
+const MAX_PACKET: usize = 4096;
+
+/// continuous values reachable over OSC, below `/montage/`
+const PARAMS: [(&str, Param); 4] = [
+    ("pitch", Param::Pitch),
+    ("delay", Param::Delay),
+    ("input_gain", Param::InputGain),
+    ("volume", Param::OutputVolume),
+];
+const SWITCHES: [(&str, Switch); 2] = [("mute", Switch::Mute), ("bypass", Switch::Bypass)];
+
+/// `9000`, `127.0.0.1:9000` or `localhost:9000`, a bare port listens on localhost only.
+/// `0.0.0.0:9000` listens on every interface
+pub fn parse_bind(text: &str) -> Result<SocketAddr> {
+    if let Ok(port) = text.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    let with_port = if text.contains(':') { text.to_string() } else { format!("{}:{}", text, DEFAULT_PORT) };
+    with_port
+        .to_socket_addrs()
+        .with_context(|| format!("{} is not an address to listen on", text))?
+        .next()
+        .with_context(|| format!("{} did not resolve", text))
+}
+
+/// the listening socket and its thread, stops when dropped
+pub struct OscServer {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OscServer {
+    pub fn start(engine: &Engine, bind: SocketAddr) -> Result<Self> {
+        let socket = UdpSocket::bind(bind).with_context(|| format!("Could not listen for OSC on {}", bind))?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let local_addr = socket.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let mut server = Server {
+            socket,
+            settings: engine.shared_settings(),
+            meters: engine.shared_meters(),
+            reports: engine.subscribe(),
+            presets: presets::available(),
+            clients: Vec::new(),
+            reported: None,
+        };
+        let thread = {
+            let shutdown = shutdown.clone();
+            std::thread::spawn(move || server.run(&shutdown))
+        };
+        Ok(Self { local_addr, shutdown, thread: Some(thread) })
+    }
+
+    /// where the server listens, with the port the system picked for port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for OscServer {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+/// values sent as feedback, compared to the previous report to find changes
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    params: [f32; PARAMS.len()],
+    switches: [bool; SWITCHES.len()],
+    preset: String,
+}
+
+struct Server {
+    socket: UdpSocket,
+    settings: Arc<Mutex<AudioSettings>>,
+    meters: Arc<Mutex<Meters>>,
+    reports: Receiver<EngineStatus>,
+    presets: Vec<Preset>,
+    /// most recent first
+    clients: Vec<SocketAddr>,
+    /// what the clients were last told
+    reported: Option<Snapshot>,
+}
+
+impl Server {
+    fn run(&mut self, shutdown: &AtomicBool) {
+        let mut buffer = [0u8; MAX_PACKET];
+        while !shutdown.load(Ordering::Relaxed) {
+            if let Ok((len, sender)) = self.socket.recv_from(&mut buffer) {
+                match rosc::decoder::decode_udp(&buffer[..len]) {
+                    Ok((_, packet)) => {
+                        self.remember(sender);
+                        self.handle(packet, sender);
+                    }
+                    Err(e) => eprintln!("OSC: bad packet from {}: {}", sender, e),
+                }
+            }
+            // only the newest report matters, the rest would just be old levels
+            if let Some(status) = self.reports.try_iter().last() {
+                self.feedback(&status);
+            }
+        }
+    }
+
+    fn remember(&mut self, client: SocketAddr) {
+        self.clients.retain(|known| *known != client);
+        self.clients.insert(0, client);
+        self.clients.truncate(MAX_CLIENTS);
+    }
+
+    fn handle(&mut self, packet: OscPacket, sender: SocketAddr) {
+        match packet {
+            OscPacket::Message(message) => self.handle_message(message, sender),
+            OscPacket::Bundle(bundle) => {
+                for packet in bundle.content {
+                    self.handle(packet, sender);
+                }
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: OscMessage, sender: SocketAddr) {
+        let Some(name) = message.addr.strip_prefix("/montage/") else {
+            eprintln!("OSC: unknown address {}", message.addr);
+            return;
+        };
+        let arg = message.args.into_iter().next();
+        let Ok(mut settings) = self.settings.lock() else { return };
+        let reply = if let Some((_, param)) = PARAMS.iter().find(|(address, _)| *address == name) {
+            match arg.as_ref().and_then(number) {
+                Some(value) => param.set(&mut settings, value),
+                None if arg.is_none() => {}
+                None => return eprintln!("OSC: {} needs a number", message.addr),
+            }
+            OscType::Float(param.get(&settings))
+        } else if let Some((_, switch)) = SWITCHES.iter().find(|(address, _)| *address == name) {
+            match arg.as_ref().and_then(number) {
+                Some(value) => switch.set(&mut settings, value >= 0.5),
+                None if arg.is_none() => {}
+                None => return eprintln!("OSC: {} needs a number or a bool", message.addr),
+            }
+            OscType::Bool(switch.get(&settings))
+        } else if name == "preset" {
+            let key = match arg {
+                Some(OscType::String(name)) => Some(name),
+                Some(arg) => number(&arg).map(|index| (index.max(0.0) as usize).to_string()),
+                None => None,
+            };
+            if let Some(key) = key {
+                match presets::find(&self.presets, &key) {
+                    Some(preset) => preset.apply(&mut settings),
+                    None => return eprintln!("OSC: there is no preset {}", key),
+                }
+            }
+            OscType::String(self.preset_name(&settings))
+        } else if name == "panic" {
+            settings.panic_request = settings.panic_request.wrapping_add(1);
+            return;
+        } else {
+            return eprintln!("OSC: unknown address {}", message.addr);
+        };
+        drop(settings);
+        self.send(&message.addr, vec![reply], &[sender]);
+    }
+
+    fn preset_name(&self, settings: &AudioSettings) -> String {
+        presets::current(&self.presets, settings).map(|preset| preset.name.clone()).unwrap_or_default()
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        let settings = self.settings.lock().ok()?.clone();
+        Some(Snapshot {
+            params: PARAMS.map(|(_, param)| param.get(&settings)),
+            switches: SWITCHES.map(|(_, switch)| switch.get(&settings)),
+            preset: self.preset_name(&settings),
+        })
+    }
+
+    /// levels every report, values only when they changed since the last one
+    fn feedback(&mut self, status: &EngineStatus) {
+        if self.clients.is_empty() {
+            return;
+        }
+        let clients = self.clients.clone();
+        let meters = self.meters.lock().map(|meters| *meters).unwrap_or_default();
+        let levels = [
+            ("/montage/meter/input", meters.input_peak_db),
+            ("/montage/meter/output", meters.output_peak_db),
+            ("/montage/meter/loudness", meters.loudness_lufs.unwrap_or(ABSOLUTE_GATE_LUFS)),
+            ("/montage/meter/load", status.cpu_load),
+        ];
+        for (address, value) in levels {
+            self.send(address, vec![OscType::Float(value)], &clients);
+        }
+
+        let Some(snapshot) = self.snapshot() else { return };
+        let previous = self.reported.replace(snapshot.clone());
+        for (index, (name, _)) in PARAMS.iter().enumerate() {
+            if previous.as_ref().is_none_or(|previous| previous.params[index] != snapshot.params[index]) {
+                self.send(&format!("/montage/{}", name), vec![OscType::Float(snapshot.params[index])], &clients);
+            }
+        }
+        for (index, (name, _)) in SWITCHES.iter().enumerate() {
+            if previous.as_ref().is_none_or(|previous| previous.switches[index] != snapshot.switches[index]) {
+                self.send(&format!("/montage/{}", name), vec![OscType::Bool(snapshot.switches[index])], &clients);
+            }
+        }
+        if previous.is_none_or(|previous| previous.preset != snapshot.preset) {
+            self.send("/montage/preset", vec![OscType::String(snapshot.preset)], &clients);
+        }
+    }
+
+    fn send(&self, address: &str, args: Vec<OscType>, to: &[SocketAddr]) {
+        let packet = OscPacket::Message(OscMessage { addr: address.to_string(), args });
+        let Ok(bytes) = rosc::encoder::encode(&packet) else { return };
+        for client in to {
+            let _ = self.socket.send_to(&bytes, client);
+        }
+    }
+}
+
+/// OSC senders differ in what they send for a value, take any kind of number but NaN or infinity
+fn number(arg: &OscType) -> Option<f32> {
+    let value = match *arg {
+        OscType::Float(value) => Some(value),
+        OscType::Double(value) => Some(value as f32),
+        OscType::Int(value) => Some(value as f32),
+        OscType::Long(value) => Some(value as f32),
+        OscType::Bool(value) => Some(if value { 1.0 } else { 0.0 }),
+        _ => None,
+    };
+    value.filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::BackendKind;
+    use crate::engine::EngineConfig;
+    use std::time::Instant;
+
+    fn send(client: &UdpSocket, to: SocketAddr, address: &str, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage { addr: address.to_string(), args });
+        client.send_to(&rosc::encoder::encode(&packet).unwrap(), to).unwrap();
+    }
+
+    /// skips messages until one to `address` with the expected arguments, or any when None
+    fn wait_for(client: &UdpSocket, address: &str, expected: Option<Vec<OscType>>) -> OscMessage {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut buffer = [0u8; MAX_PACKET];
+        while Instant::now() < deadline {
+            let Ok(len) = client.recv(&mut buffer) else { continue };
+            if let Ok((_, OscPacket::Message(message))) = rosc::decoder::decode_udp(&buffer[..len])
+                && message.addr == address
+                && expected.as_ref().is_none_or(|expected| *expected == message.args)
+            {
+                return message;
+            }
+        }
+        panic!("never got {}", address);
+    }
+
+    #[test]
+    fn messages_change_settings_and_meters_come_back() {
+        let engine = Engine::start(EngineConfig { backend: BackendKind::Null, ..EngineConfig::default() });
+        let server = OscServer::start(&engine, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let to = server.local_addr();
+
+        send(&client, to, "/montage/preset", vec![OscType::String("robot".into())]);
+        send(&client, to, "/montage/delay", vec![OscType::Int(40)]);
+        send(&client, to, "/montage/bypass", vec![OscType::Bool(true)]);
+        send(&client, to, "/montage/pitch", vec![]);
+        wait_for(&client, "/montage/pitch", Some(vec![OscType::Float(1.0)]));
+
+        let settings = engine.settings();
+        assert_eq!(settings.effects.character, crate::dsp::character::VoiceCharacter::Robot);
+        assert_eq!(settings.delay_ms, 40.0);
+        assert!(settings.bypass);
+
+        assert!(matches!(wait_for(&client, "/montage/meter/input", None).args[..], [OscType::Float(_)]));
+        engine.update(|settings| settings.pitch = 1.5);
+        wait_for(&client, "/montage/preset", Some(vec![OscType::String(String::new())]));
+
+        server.stop();
+        engine.stop();
+    }
+
+    #[test]
+    fn non_finite_numbers_are_ignored() {
+        assert_eq!(number(&OscType::Float(f32::NAN)), None);
+        assert_eq!(number(&OscType::Double(f64::INFINITY)), None);
+        assert_eq!(number(&OscType::Double(0.5)), Some(0.5));
+
+        // from anywhere else the parameter keeps its value
+        let mut settings = AudioSettings::default();
+        Param::Pitch.set(&mut settings, f32::NAN);
+        Param::Delay.set(&mut settings, f32::NEG_INFINITY);
+        assert_eq!((settings.pitch, settings.delay_ms), (1.0, 0.0));
+    }
+
+    #[test]
+    fn bind_addresses_parse() {
+        assert_eq!(parse_bind("9001").unwrap(), "127.0.0.1:9001".parse().unwrap());
+        assert_eq!(parse_bind("0.0.0.0:9001").unwrap(), "0.0.0.0:9001".parse().unwrap());
+        assert_eq!(parse_bind("127.0.0.1:9002").unwrap(), "127.0.0.1:9002".parse().unwrap());
+        assert_eq!(parse_bind("127.0.0.1").unwrap().port(), DEFAULT_PORT);
+    }
+}
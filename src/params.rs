@@ -0,0 +1,456 @@
+use crate::dsp::agc::ABSOLUTE_GATE_LUFS;
+use crate::dsp::echo::MAX_ECHO_MS;
+use crate::dsp::harmonizer::{MAX_VOICES, MAX_VOICE_DELAY_MS};
+use crate::dsp::reverb::MAX_PRE_DELAY_MS;
+use crate::dsp::vocoder::{MAX_BANDS, MIN_BANDS};
+use crate::settings::AudioSettings;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// longest output delay the engine keeps room for
+pub const MAX_DELAY_MS: f32 = 100.0;
+
+/// continuous effect parameters that can be driven from a slider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Param {
+    /// playback speed of the pitch shifter
+    Pitch,
+    /// output delay in ms
+    Delay,
+    InputGain,
+    OutputVolume,
+    DeEsserFrequency,
+    DeEsserThreshold,
+    DeEsserRange,
+    VocoderBands,
+    VocoderLow,
+    VocoderHigh,
+    VocoderAttack,
+    VocoderRelease,
+    VocoderNote,
+    VocoderMix,
+    HarmonizerDry,
+    /// per harmony voice, indexed 0..MAX_VOICES
+    HarmonyInterval(usize),
+    HarmonyLevel(usize),
+    HarmonyPan(usize),
+    HarmonyDetune(usize),
+    HarmonyDelay(usize),
+    EchoTime,
+    EchoBpm,
+    EchoFeedback,
+    EchoLowCut,
+    EchoHighCut,
+    EchoWet,
+    EchoDry,
+    ReverbRoomSize,
+    ReverbDamping,
+    ReverbPreDelay,
+    ReverbWidth,
+    ReverbWet,
+    AgcTarget,
+    AgcMaxGain,
+    AgcGate,
+    AgcResponse,
+}
+
+/// on/off switches for the effect stages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Switch {
+    Mute,
+    Bypass,
+    DeEsser,
+    DeEsserListen,
+    Vocoder,
+    Harmonizer,
+    HarmonyVoice(usize),
+    Echo,
+    EchoSync,
+    EchoPingPong,
+    Reverb,
+    Agc,
+}
+
+impl Param {
+    /// every parameter, the harmony ones once per voice
+    pub fn all() -> Vec<Param> {
+        let mut all = vec![
+            Param::Pitch,
+            Param::Delay,
+            Param::InputGain,
+            Param::OutputVolume,
+            Param::DeEsserFrequency,
+            Param::DeEsserThreshold,
+            Param::DeEsserRange,
+            Param::VocoderBands,
+            Param::VocoderLow,
+            Param::VocoderHigh,
+            Param::VocoderAttack,
+            Param::VocoderRelease,
+            Param::VocoderNote,
+            Param::VocoderMix,
+            Param::HarmonizerDry,
+            Param::EchoTime,
+            Param::EchoBpm,
+            Param::EchoFeedback,
+            Param::EchoLowCut,
+            Param::EchoHighCut,
+            Param::EchoWet,
+            Param::EchoDry,
+            Param::ReverbRoomSize,
+            Param::ReverbDamping,
+            Param::ReverbPreDelay,
+            Param::ReverbWidth,
+            Param::ReverbWet,
+            Param::AgcTarget,
+            Param::AgcMaxGain,
+            Param::AgcGate,
+            Param::AgcResponse,
+        ];
+        for i in 0..MAX_VOICES {
+            all.extend([
+                Param::HarmonyInterval(i),
+                Param::HarmonyLevel(i),
+                Param::HarmonyPan(i),
+                Param::HarmonyDetune(i),
+                Param::HarmonyDelay(i),
+            ]);
+        }
+        all
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Param::Pitch => "Pitch",
+            Param::Delay => "Delay",
+            Param::InputGain => "Input gain",
+            Param::OutputVolume => "Output volume",
+            Param::DeEsserFrequency => "Frequency",
+            Param::DeEsserThreshold => "Threshold",
+            Param::DeEsserRange => "Range",
+            Param::VocoderBands => "Bands",
+            Param::VocoderLow => "Lowest band",
+            Param::VocoderHigh => "Highest band",
+            Param::VocoderAttack => "Attack",
+            Param::VocoderRelease => "Release",
+            Param::VocoderNote => "Carrier pitch",
+            Param::VocoderMix => "Mix",
+            Param::HarmonizerDry => "Lead voice",
+            Param::HarmonyInterval(_) => "Interval",
+            Param::HarmonyLevel(_) => "Level",
+            Param::HarmonyPan(_) => "Pan",
+            Param::HarmonyDetune(_) => "Detune",
+            Param::HarmonyDelay(_) => "Delay",
+            Param::EchoTime => "Time",
+            Param::EchoBpm => "Tempo",
+            Param::EchoFeedback => "Feedback",
+            Param::EchoLowCut => "Low cut",
+            Param::EchoHighCut => "High cut",
+            Param::EchoWet | Param::ReverbWet => "Wet",
+            Param::EchoDry => "Dry",
+            Param::ReverbRoomSize => "Room size",
+            Param::ReverbDamping => "Damping",
+            Param::ReverbPreDelay => "Pre-delay",
+            Param::AgcTarget => "Target",
+            Param::AgcMaxGain => "Max gain",
+            Param::AgcGate => "Silence gate",
+            Param::AgcResponse => "Response",
+            Param::ReverbWidth => "Width",
+        }
+    }
+
+    pub fn range(self) -> RangeInclusive<f32> {
+        match self {
+            Param::Pitch => 0.5..=2.0,
+            Param::Delay => 0.0..=MAX_DELAY_MS,
+            Param::InputGain => -24.0..=24.0,
+            Param::OutputVolume => -60.0..=12.0,
+            Param::DeEsserFrequency => 2000.0..=12000.0,
+            Param::DeEsserThreshold => -60.0..=0.0,
+            Param::DeEsserRange => 0.0..=24.0,
+            Param::VocoderBands => MIN_BANDS as f32..=MAX_BANDS as f32,
+            Param::VocoderLow => 50.0..=1000.0,
+            Param::VocoderHigh => 2000.0..=12000.0,
+            Param::VocoderAttack => 0.5..=50.0,
+            Param::VocoderRelease => 5.0..=500.0,
+            Param::VocoderNote => 24.0..=84.0,
+            Param::HarmonyInterval(_) => -24.0..=24.0,
+            Param::HarmonyPan(_) => -1.0..=1.0,
+            Param::HarmonyDetune(_) => -50.0..=50.0,
+            Param::HarmonyDelay(_) => 0.0..=MAX_VOICE_DELAY_MS,
+            Param::EchoTime => 1.0..=MAX_ECHO_MS,
+            Param::EchoBpm => 40.0..=240.0,
+            Param::EchoFeedback => 0.0..=0.95,
+            Param::EchoLowCut => 20.0..=2000.0,
+            Param::EchoHighCut => 1000.0..=20000.0,
+            Param::ReverbPreDelay => 0.0..=MAX_PRE_DELAY_MS,
+            Param::AgcTarget => -36.0..=-10.0,
+            Param::AgcMaxGain => 0.0..=30.0,
+            Param::AgcGate => ABSOLUTE_GATE_LUFS..=-30.0,
+            Param::AgcResponse => 0.5..=10.0,
+            Param::VocoderMix
+            | Param::HarmonizerDry
+            | Param::HarmonyLevel(_)
+            | Param::EchoWet
+            | Param::EchoDry
+            | Param::ReverbRoomSize
+            | Param::ReverbDamping
+            | Param::ReverbWidth
+            | Param::ReverbWet => 0.0..=1.0,
+        }
+    }
+
+    pub fn step(self) -> f32 {
+        match self {
+            Param::Delay
+            | Param::VocoderBands
+            | Param::VocoderNote
+            | Param::VocoderRelease
+            | Param::HarmonyInterval(_)
+            | Param::HarmonyDetune(_)
+            | Param::HarmonyDelay(_)
+            | Param::EchoTime
+            | Param::EchoBpm
+            | Param::ReverbPreDelay => 1.0,
+            Param::VocoderAttack
+            | Param::InputGain
+            | Param::OutputVolume
+            | Param::DeEsserThreshold
+            | Param::DeEsserRange
+            | Param::AgcTarget
+            | Param::AgcMaxGain
+            | Param::AgcGate => 0.5,
+            Param::AgcResponse | Param::Pitch => 0.1,
+            Param::DeEsserFrequency
+            | Param::VocoderLow | Param::VocoderHigh | Param::EchoLowCut | Param::EchoHighCut => 10.0,
+            _ => 0.01,
+        }
+    }
+
+    /// human readable value for the slider caption
+    pub fn format(self, value: f32) -> String {
+        match self {
+            Param::Pitch => format!("{:.1}x", value),
+            Param::InputGain | Param::OutputVolume => format!("{:+.1}dB", value),
+            Param::DeEsserThreshold => format!("{:.1}dB", value),
+            Param::DeEsserRange => format!("-{:.1}dB", value),
+            Param::AgcTarget | Param::AgcGate => format!("{:.1} LUFS", value),
+            Param::AgcMaxGain => format!("+{:.1}dB", value),
+            Param::AgcResponse => format!("{:.1}s", value),
+            Param::VocoderBands => format!("{:.0}", value),
+            Param::VocoderNote => note_name(value),
+            Param::VocoderAttack => format!("{:.1}ms", value),
+            Param::HarmonyInterval(_) => format!("{:+.0} semitones", value),
+            Param::HarmonyDetune(_) => format!("{:+.0} cents", value),
+            Param::HarmonyPan(_) if value.abs() < 0.01 => "C".to_string(),
+            Param::HarmonyPan(_) if value < 0.0 => format!("L{:.0}", -value * 100.0),
+            Param::HarmonyPan(_) => format!("R{:.0}", value * 100.0),
+            Param::Delay | Param::VocoderRelease | Param::HarmonyDelay(_) | Param::EchoTime | Param::ReverbPreDelay => format!("{:.0}ms", value),
+            Param::EchoBpm => format!("{:.0} BPM", value),
+            Param::DeEsserFrequency
+            | Param::VocoderLow
+            | Param::VocoderHigh
+            | Param::EchoLowCut
+            | Param::EchoHighCut => format!("{:.0}Hz", value),
+            _ => format!("{:.0}%", value * 100.0),
+        }
+    }
+
+    /// false for a harmony voice that does not exist, which a hand-edited preset file can ask for
+    pub fn is_valid(self) -> bool {
+        match self {
+            Param::HarmonyInterval(i)
+            | Param::HarmonyLevel(i)
+            | Param::HarmonyPan(i)
+            | Param::HarmonyDetune(i)
+            | Param::HarmonyDelay(i) => i < MAX_VOICES,
+            _ => true,
+        }
+    }
+
+    /// a harmony voice that does not exist reads as 0
+    pub fn get(self, settings: &AudioSettings) -> f32 {
+        match self {
+            Param::Pitch => settings.pitch,
+            Param::Delay => settings.delay_ms,
+            Param::InputGain => settings.input_gain_db,
+            Param::OutputVolume => settings.output_volume_db,
+            Param::DeEsserFrequency => settings.effects.deesser.frequency_hz,
+            Param::DeEsserThreshold => settings.effects.deesser.threshold_db,
+            Param::DeEsserRange => settings.effects.deesser.range_db,
+            Param::VocoderBands => settings.effects.vocoder.bands as f32,
+            Param::VocoderLow => settings.effects.vocoder.low_hz,
+            Param::VocoderHigh => settings.effects.vocoder.high_hz,
+            Param::VocoderAttack => settings.effects.vocoder.attack_ms,
+            Param::VocoderRelease => settings.effects.vocoder.release_ms,
+            Param::VocoderNote => settings.effects.vocoder.carrier_note,
+            Param::VocoderMix => settings.effects.vocoder.mix,
+            Param::HarmonizerDry => settings.effects.harmonizer.dry,
+            Param::HarmonyInterval(i) => settings.effects.harmonizer.voices.get(i).map_or(0.0, |voice| voice.interval),
+            Param::HarmonyLevel(i) => settings.effects.harmonizer.voices.get(i).map_or(0.0, |voice| voice.level),
+            Param::HarmonyPan(i) => settings.effects.harmonizer.voices.get(i).map_or(0.0, |voice| voice.pan),
+            Param::HarmonyDetune(i) => settings.effects.harmonizer.voices.get(i).map_or(0.0, |voice| voice.detune_cents),
+            Param::HarmonyDelay(i) => settings.effects.harmonizer.voices.get(i).map_or(0.0, |voice| voice.delay_ms),
+            Param::EchoTime => settings.effects.echo.time_ms,
+            Param::EchoBpm => settings.effects.echo.bpm,
+            Param::EchoFeedback => settings.effects.echo.feedback,
+            Param::EchoLowCut => settings.effects.echo.low_cut_hz,
+            Param::EchoHighCut => settings.effects.echo.high_cut_hz,
+            Param::EchoWet => settings.effects.echo.wet,
+            Param::EchoDry => settings.effects.echo.dry,
+            Param::ReverbRoomSize => settings.effects.reverb.room_size,
+            Param::ReverbDamping => settings.effects.reverb.damping,
+            Param::ReverbPreDelay => settings.effects.reverb.pre_delay_ms,
+            Param::ReverbWidth => settings.effects.reverb.width,
+            Param::ReverbWet => settings.effects.reverb.wet,
+            Param::AgcTarget => settings.effects.agc.target_lufs,
+            Param::AgcMaxGain => settings.effects.agc.max_gain_db,
+            Param::AgcGate => settings.effects.agc.gate_lufs,
+            Param::AgcResponse => settings.effects.agc.response_s,
+        }
+    }
+
+    /// a harmony voice that does not exist, NaN and infinity are left alone
+    pub fn set(self, settings: &mut AudioSettings, value: f32) {
+        if !value.is_finite() {
+            return;
+        }
+        let range = self.range();
+        let value = value.clamp(*range.start(), *range.end());
+        match self {
+            Param::Pitch => settings.pitch = value,
+            Param::Delay => settings.delay_ms = value,
+            Param::InputGain => settings.input_gain_db = value,
+            Param::OutputVolume => settings.output_volume_db = value,
+            Param::DeEsserFrequency => settings.effects.deesser.frequency_hz = value,
+            Param::DeEsserThreshold => settings.effects.deesser.threshold_db = value,
+            Param::DeEsserRange => settings.effects.deesser.range_db = value,
+            Param::VocoderBands => settings.effects.vocoder.bands = value.round() as u32,
+            Param::VocoderLow => settings.effects.vocoder.low_hz = value,
+            Param::VocoderHigh => settings.effects.vocoder.high_hz = value,
+            Param::VocoderAttack => settings.effects.vocoder.attack_ms = value,
+            Param::VocoderRelease => settings.effects.vocoder.release_ms = value,
+            Param::VocoderNote => settings.effects.vocoder.carrier_note = value.round(),
+            Param::VocoderMix => settings.effects.vocoder.mix = value,
+            Param::HarmonizerDry => settings.effects.harmonizer.dry = value,
+            Param::HarmonyInterval(i) => {
+                if let Some(voice) = settings.effects.harmonizer.voices.get_mut(i) {
+                    voice.interval = value.round();
+                }
+            }
+            Param::HarmonyLevel(i) => {
+                if let Some(voice) = settings.effects.harmonizer.voices.get_mut(i) {
+                    voice.level = value;
+                }
+            }
+            Param::HarmonyPan(i) => {
+                if let Some(voice) = settings.effects.harmonizer.voices.get_mut(i) {
+                    voice.pan = value;
+                }
+            }
+            Param::HarmonyDetune(i) => {
+                if let Some(voice) = settings.effects.harmonizer.voices.get_mut(i) {
+                    voice.detune_cents = value;
+                }
+            }
+            Param::HarmonyDelay(i) => {
+                if let Some(voice) = settings.effects.harmonizer.voices.get_mut(i) {
+                    voice.delay_ms = value;
+                }
+            }
+            Param::EchoTime => settings.effects.echo.time_ms = value,
+            Param::EchoBpm => settings.effects.echo.bpm = value,
+            Param::EchoFeedback => settings.effects.echo.feedback = value,
+            Param::EchoLowCut => settings.effects.echo.low_cut_hz = value,
+            Param::EchoHighCut => settings.effects.echo.high_cut_hz = value,
+            Param::EchoWet => settings.effects.echo.wet = value,
+            Param::EchoDry => settings.effects.echo.dry = value,
+            Param::ReverbRoomSize => settings.effects.reverb.room_size = value,
+            Param::ReverbDamping => settings.effects.reverb.damping = value,
+            Param::ReverbPreDelay => settings.effects.reverb.pre_delay_ms = value,
+            Param::ReverbWidth => settings.effects.reverb.width = value,
+            Param::ReverbWet => settings.effects.reverb.wet = value,
+            Param::AgcTarget => settings.effects.agc.target_lufs = value,
+            Param::AgcMaxGain => settings.effects.agc.max_gain_db = value,
+            Param::AgcGate => settings.effects.agc.gate_lufs = value,
+            Param::AgcResponse => settings.effects.agc.response_s = value,
+        }
+    }
+}
+
+impl Switch {
+    pub fn label(self) -> &'static str {
+        match self {
+            Switch::Mute => "Mute",
+            Switch::Bypass => "Bypass",
+            Switch::DeEsser => "De-esser",
+            Switch::DeEsserListen => "Listen to sidechain",
+            Switch::Vocoder => "Vocoder",
+            Switch::Harmonizer => "Harmonizer",
+            Switch::HarmonyVoice(0) => "Voice 1",
+            Switch::HarmonyVoice(1) => "Voice 2",
+            Switch::HarmonyVoice(2) => "Voice 3",
+            Switch::HarmonyVoice(_) => "Voice 4",
+            Switch::Echo => "Echo",
+            Switch::EchoSync => "Sync to tempo",
+            Switch::EchoPingPong => "Ping-pong",
+            Switch::Reverb => "Reverb",
+            Switch::Agc => "Automatic gain",
+        }
+    }
+
+    pub fn get(self, settings: &AudioSettings) -> bool {
+        match self {
+            Switch::Mute => settings.mute,
+            Switch::Bypass => settings.bypass,
+            Switch::DeEsser => settings.effects.deesser.enabled,
+            Switch::DeEsserListen => settings.effects.deesser.listen,
+            Switch::Vocoder => settings.effects.vocoder.enabled,
+            Switch::Harmonizer => settings.effects.harmonizer.enabled,
+            Switch::HarmonyVoice(i) => settings.effects.harmonizer.voices.get(i).is_some_and(|voice| voice.enabled),
+            Switch::Echo => settings.effects.echo.enabled,
+            Switch::EchoSync => settings.effects.echo.sync,
+            Switch::EchoPingPong => settings.effects.echo.ping_pong,
+            Switch::Reverb => settings.effects.reverb.enabled,
+            Switch::Agc => settings.effects.agc.enabled,
+        }
+    }
+
+    pub fn set(self, settings: &mut AudioSettings, enabled: bool) {
+        match self {
+            Switch::Mute => settings.mute = enabled,
+            Switch::Bypass => settings.bypass = enabled,
+            Switch::DeEsser => settings.effects.deesser.enabled = enabled,
+            Switch::DeEsserListen => settings.effects.deesser.listen = enabled,
+            Switch::Vocoder => settings.effects.vocoder.enabled = enabled,
+            Switch::Harmonizer => settings.effects.harmonizer.enabled = enabled,
+            Switch::HarmonyVoice(i) => {
+                if let Some(voice) = settings.effects.harmonizer.voices.get_mut(i) {
+                    voice.enabled = enabled;
+                }
+            }
+            Switch::Echo => settings.effects.echo.enabled = enabled,
+            Switch::EchoSync => settings.effects.echo.sync = enabled,
+            Switch::EchoPingPong => settings.effects.echo.ping_pong = enabled,
+            Switch::Reverb => settings.effects.reverb.enabled = enabled,
+            Switch::Agc => settings.effects.agc.enabled = enabled,
+        }
+    }
+}
+
+/// brings every parameter into the range of its slider, for settings that came from
+/// somewhere else. a value that isn't a number goes back to its default
+pub fn clamp_all(settings: &mut AudioSettings) {
+    let defaults = AudioSettings::default();
+    for param in Param::all() {
+        let value = param.get(settings);
+        param.set(settings, if value.is_finite() { value } else { param.get(&defaults) });
+    }
+}
+
+/// note name of a MIDI note number, rounded to the nearest note. `C4` is middle C, 60
+pub fn note_name(note: f32) -> String {
+    const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    let note = note.round() as i32;
+    format!("{}{}", NAMES[note.rem_euclid(12) as usize], note.div_euclid(12) - 1)
+}
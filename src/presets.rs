@@ -0,0 +1,243 @@
+use crate::dsp::EffectSettings;
+use crate::dsp::character::VoiceCharacter;
+use crate::midi::MidiMap;
+use crate::params;
+use crate::settings::AudioSettings;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// a named voice: the pitch and the effect chain, levels and delay are left alone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub pitch: f32,
+    pub effects: EffectSettings,
+    /// MIDI mappings that come with the preset, None keeps the ones in use
+    #[serde(default)]
+    pub midi: Option<MidiMap>,
+}
+
+impl Preset {
+    pub fn new(name: &str, pitch: f32, effects: EffectSettings) -> Self {
+        Self { name: name.to_string(), pitch, effects, midi: None }
+    }
+
+    /// the voice the settings are now, with the mappings in use
+    pub fn capture(name: &str, settings: &AudioSettings, midi: &MidiMap) -> Self {
+        Self { midi: Some(midi.clone()), ..Self::new(name, settings.pitch, settings.effects) }
+    }
+
+    pub fn apply(&self, settings: &mut AudioSettings) {
+        settings.pitch = self.pitch;
+        settings.effects = self.effects;
+    }
+
+    /// brings the pitch and the effects into the ranges of their sliders
+    pub fn clamp(&mut self) {
+        let mut settings = AudioSettings::default();
+        self.apply(&mut settings);
+        params::clamp_all(&mut settings);
+        (self.pitch, self.effects) = (settings.pitch, settings.effects);
+    }
+
+    /// whether the settings are exactly this preset, so it can be shown as the current one
+    pub fn matches(&self, settings: &AudioSettings) -> bool {
+        settings.pitch == self.pitch && settings.effects == self.effects
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// the voices that ship with montage
+pub fn builtin() -> Vec<Preset> {
+    let preset = |name, pitch, configure: fn(&mut EffectSettings)| {
+        let mut effects = EffectSettings::default();
+        configure(&mut effects);
+        Preset::new(name, pitch, effects)
+    };
+    vec![
+        preset("Natural", 1.0, |_| {}),
+        preset("Chipmunk", 1.6, |_| {}),
+        preset("Deep", 0.7, |_| {}),
+        preset("Robot", 1.0, |effects| effects.character = VoiceCharacter::Robot),
+        preset("Monster", 1.0, |effects| effects.character = VoiceCharacter::Monster),
+        preset("Radio", 1.0, |effects| effects.character = VoiceCharacter::Radio),
+        preset("Telephone", 1.0, |effects| effects.character = VoiceCharacter::Telephone),
+        preset("Vocoder", 1.0, |effects| effects.vocoder.enabled = true),
+        preset("Harmonizer", 1.0, |effects| effects.harmonizer.enabled = true),
+    ]
+}
+
+/// the built-in presets followed by the ones the user saved, which can't be read is left out
+pub fn available() -> Vec<Preset> {
+    let saved = PresetStore::default_path().and_then(|path| PresetStore::load(&path).ok()).unwrap_or_default();
+    builtin().into_iter().chain(saved.presets).collect()
+}
+
+/// presets saved by the user and the MIDI mappings in use, kept together in one file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetStore {
+    pub presets: Vec<Preset>,
+    pub midi: MidiMap,
+}
+
+impl PresetStore {
+    /// `presets.json` in the `montage` config directory of the user
+    pub fn default_path() -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config.join("montage").join("presets.json"))
+    }
+
+    /// empty if nothing was saved yet. values out of range are clamped and mappings to
+    /// parameters that do not exist are left out, the file may have been edited by hand
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut store: Self = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("{} is not a preset file", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("Could not read {}", path.display())),
+        };
+        store.midi.drop_invalid();
+        for preset in &mut store.presets {
+            preset.clamp();
+            if let Some(midi) = &mut preset.midi {
+                midi.drop_invalid();
+            }
+        }
+        Ok(store)
+    }
+
+    /// written next to the old file first, so a crash never leaves half a file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        }
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write {}", partial.display()))?;
+        std::fs::rename(&partial, path).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    /// a preset with the same name is replaced
+    pub fn insert(&mut self, preset: Preset) {
+        match self.presets.iter_mut().find(|saved| saved.name.eq_ignore_ascii_case(&preset.name)) {
+            Some(saved) => *saved = preset,
+            None => self.presets.push(preset),
+        }
+    }
+}
+
+/// by name, ignoring case, or by position in the list
+pub fn find<'a>(presets: &'a [Preset], key: &str) -> Option<&'a Preset> {
+    let key = key.trim();
+    presets
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(key))
+        .or_else(|| presets.get(key.parse::<usize>().ok()?))
+}
+
+/// the preset the settings currently are, if any
+pub fn current<'a>(presets: &'a [Preset], settings: &AudioSettings) -> Option<&'a Preset> {
+    presets.iter().find(|preset| preset.matches(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_found_by_name_or_index() {
+        let presets = builtin();
+        assert_eq!(find(&presets, "robot").map(|p| p.name.as_str()), Some("Robot"));
+        assert_eq!(find(&presets, " 1 ").map(|p| p.name.as_str()), Some("Chipmunk"));
+        assert!(find(&presets, "nothing").is_none());
+        assert!(find(&presets, "99").is_none());
+    }
+
+    #[test]
+    fn applied_preset_is_current_until_changed() {
+        let presets = builtin();
+        let mut settings = AudioSettings { output_volume_db: -6.0, ..AudioSettings::default() };
+        presets[2].apply(&mut settings);
+        assert_eq!(settings.output_volume_db, -6.0);
+        assert_eq!(current(&presets, &settings), Some(&presets[2]));
+        settings.effects.echo.enabled = true;
+        assert_eq!(current(&presets, &settings), None);
+    }
+
+    #[test]
+    fn saved_presets_load_back_with_their_mappings() {
+        use crate::midi::{Control, MidiMapping, MidiSource};
+        use crate::params::Param;
+
+        let path = std::env::temp_dir().join(format!("montage-presets-{}", std::process::id())).join("presets.json");
+        assert_eq!(PresetStore::load(&path).unwrap(), PresetStore::default());
+
+        let source = MidiSource { channel: 2, control: Control::Cc(74) };
+        let midi = MidiMap { mappings: vec![MidiMapping::new(source, Param::HarmonyLevel(1))], key_root: Some(60) };
+        let mut settings = AudioSettings::default();
+        settings.effects.reverb.enabled = true;
+        let mut store = PresetStore { presets: Vec::new(), midi: midi.clone() };
+        store.insert(Preset::capture("Hall", &settings, &MidiMap::default()));
+        store.insert(Preset::capture("hall", &settings, &midi));
+        store.save(&path).unwrap();
+
+        let loaded = PresetStore::load(&path).unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(loaded, store);
+        assert_eq!(loaded.presets.len(), 1);
+        assert_eq!(loaded.presets[0].midi, Some(midi));
+    }
+
+    #[test]
+    fn mappings_to_missing_harmony_voices_are_dropped_on_load() {
+        use crate::midi::MidiControl;
+
+        let path = std::env::temp_dir().join(format!("montage-bad-voice-{}.json", std::process::id()));
+        let mapping = |param| format!(
+            r#"{{"source": {{"channel": 0, "control": {{"Cc": 7}}}}, "param": {}, "min": 0.0, "max": 1.0, "curve": "Linear"}}"#,
+            param
+        );
+        let text = format!(
+            r#"{{"presets": [{{"name": "Bad", "pitch": 1.0, "effects": {{}}, "midi": {{"mappings": [{}]}}}}],
+                "midi": {{"mappings": [{}, {}]}}}}"#,
+            mapping(r#"{"HarmonyPan": 4}"#),
+            mapping(r#"{"HarmonyLevel": 7}"#),
+            mapping(r#"{"HarmonyLevel": 3}"#),
+        );
+        std::fs::write(&path, text).unwrap();
+        let loaded = PresetStore::load(&path);
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.presets[0].midi.as_ref().map(|midi| midi.mappings.len()), Some(0));
+        assert_eq!(loaded.midi.mappings.len(), 1);
+        let mut settings = AudioSettings::default();
+        assert!(MidiControl::new(loaded.midi).handle(&[0xb0, 7, 127], &mut settings));
+        assert_eq!(settings.effects.harmonizer.voices[3].level, 1.0);
+    }
+
+    #[test]
+    fn out_of_range_preset_values_are_clamped_on_load() {
+        let path = std::env::temp_dir().join(format!("montage-out-of-range-{}.json", std::process::id()));
+        let text = r#"{"presets": [{"name": "Loud", "pitch": 9.0,
+            "effects": {"agc": {"enabled": true, "max_gain_db": -30.0}, "reverb": {"wet": 3.0}}}]}"#;
+        std::fs::write(&path, text).unwrap();
+        let loaded = PresetStore::load(&path);
+        let _ = std::fs::remove_file(&path);
+        let preset = &loaded.unwrap().presets[0];
+
+        assert_eq!(preset.pitch, 2.0);
+        assert_eq!(preset.effects.agc.max_gain_db, 0.0);
+        assert_eq!(preset.effects.reverb.wet, 1.0);
+        assert!(preset.effects.agc.enabled);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::dsp::EffectSettings;
+use serde::{Deserialize, Serialize};
+
+/// smallest and largest buffer the GUI and the control API let through, in frames
+pub const MIN_BUFFER_SIZE: u32 = 64;
+pub const MAX_BUFFER_SIZE: u32 = 2048;
+
+/// rate the effect chain runs at, the devices are converted to it. saved and sent as Hz
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum SampleRate {
+    Rate22050,
+    Rate44100,
+    Rate48000,
+    Rate96000,
+}
+
+impl SampleRate {
+    pub const ALL: [SampleRate; 4] = [
+        SampleRate::Rate22050,
+        SampleRate::Rate44100,
+        SampleRate::Rate48000,
+        SampleRate::Rate96000,
+    ];
+
+    pub fn to_hz(self) -> u32 {
+        match self {
+            SampleRate::Rate22050 => 22050,
+            SampleRate::Rate44100 => 44100,
+            SampleRate::Rate48000 => 48000,
+            SampleRate::Rate96000 => 96000,
+        }
+    }
+}
+
+impl TryFrom<u32> for SampleRate {
+    type Error = String;
+
+    fn try_from(hz: u32) -> Result<Self, Self::Error> {
+        SampleRate::ALL
+            .into_iter()
+            .find(|rate| rate.to_hz() == hz)
+            .ok_or_else(|| format!("{} Hz is not a supported sample rate", hz))
+    }
+}
+
+impl From<SampleRate> for u32 {
+    fn from(rate: SampleRate) -> u32 {
+        rate.to_hz()
+    }
+}
+
+impl std::fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} Hz", self.to_hz())
+    }
+}
+
+/// everything the engine is told to do, written by the front-ends and read by the
+/// audio thread at the start of every output buffer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub pitch: f32,
+    pub sample_rate: SampleRate,
+    pub buffer_size: u32,
+    pub delay_ms: f32,
+    pub effects: EffectSettings,
+    /// trim in front of the chain and volume after it, in dB
+    pub input_gain_db: f32,
+    pub output_volume_db: f32,
+    pub mute: bool,
+    /// the voice passes untouched, the effect chain is skipped
+    pub bypass: bool,
+    /// bumped by the GUI to ask the audio thread for a new loopback measurement
+    pub loopback_request: u32,
+    /// bumped by the panic button, the audio thread clears every buffer and filter
+    pub panic_request: u32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            pitch: 1.0,
+            sample_rate: SampleRate::Rate44100,
+            buffer_size: 512,
+            delay_ms: 0.0,
+            effects: EffectSettings::default(),
+            input_gain_db: 0.0,
+            output_volume_db: 0.0,
+            mute: false,
+            bypass: false,
+            loopback_request: 0,
+            panic_request: 0,
+        }
+    }
+}
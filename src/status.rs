@@ -0,0 +1,107 @@
+use crate::latency::LatencyReport;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// state of the audio streams, written by the audio thread and shown by the GUI
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum StreamState {
+    #[default]
+    Starting,
+    Running,
+    /// the streams stopped or could not be opened, with the reason
+    Disconnected(String),
+    /// waiting before the next attempt to open the devices again
+    Reconnecting { reason: String, attempt: u32, delay_ms: u64 },
+}
+
+impl std::fmt::Display for StreamState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamState::Starting => write!(f, "Starting audio..."),
+            StreamState::Running => write!(f, "Running"),
+            StreamState::Disconnected(reason) => write!(f, "Disconnected: {}", reason),
+            StreamState::Reconnecting { reason, attempt, delay_ms } => write!(
+                f,
+                "Disconnected: {}, reconnecting in {:.1}s (attempt {})",
+                reason,
+                *delay_ms as f32 / 1000.0,
+                attempt
+            ),
+        }
+    }
+}
+
+/// a device as it was actually opened
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub format: String,
+}
+
+impl std::fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({} Hz, {}ch, {})", self.name, self.sample_rate, self.channels, self.format)
+    }
+}
+
+/// snapshot of the engine sent from the audio thread to the GUI
+#[derive(Debug, Clone, Default)]
+pub struct EngineStatus {
+    pub state: StreamState,
+    pub input: Option<DeviceInfo>,
+    pub output: Option<DeviceInfo>,
+    /// rate the effects run at, the devices may differ
+    pub sample_rate: u32,
+    pub buffer_size: u32,
+    /// input blocks thrown away because processing fell behind
+    pub dropped_inputs: u64,
+    /// output buffers that ran out of audio and were padded with silence
+    pub underruns: u64,
+    /// time spent in the output callback relative to the buffer period,
+    /// averaged over and the worst callback of the last report interval
+    pub cpu_load: f32,
+    pub cpu_peak: f32,
+    pub latency: LatencyReport,
+    /// most recent stream or device error, kept after the engine recovers
+    pub last_error: Option<String>,
+}
+
+impl EngineStatus {
+    pub fn xruns(&self) -> u64 {
+        self.dropped_inputs + self.underruns
+    }
+}
+
+/// passes every report of the audio thread on to each front-end that subscribed,
+/// and keeps the latest one for those that only look now and then
+#[derive(Clone, Default)]
+pub struct StatusFeed {
+    subscribers: Arc<Mutex<Vec<Sender<EngineStatus>>>>,
+    latest: Arc<Mutex<EngineStatus>>,
+}
+
+impl StatusFeed {
+    /// reports from now on, until the receiver is dropped
+    pub fn subscribe(&self) -> Receiver<EngineStatus> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(tx);
+        }
+        rx
+    }
+
+    pub fn latest(&self) -> EngineStatus {
+        self.latest.lock().map(|status| status.clone()).unwrap_or_default()
+    }
+
+    pub(crate) fn publish(&self, status: &EngineStatus) {
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = status.clone();
+        }
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| subscriber.send(status.clone()).is_ok());
+        }
+    }
+}